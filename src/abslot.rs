@@ -0,0 +1,313 @@
+//! A/B slots of ChromeOS and Android disks.
+//!
+//! Both platforms keep two copies of their boot partitions and update the inactive one.
+//! ChromeOS names them KERN-A/KERN-B and ROOT-A/ROOT-B and stores the boot state of each kernel
+//! in the type-specific GPT attribute bits (priority, tries left, successful). Android names
+//! them boot_a/boot_b, system_a/system_b... and, on Qualcomm-style GPTs, stores priority,
+//! active, retry count, successful and unbootable bits on the boot partitions. The pairs are
+//! grouped by name and the slot the bootloader picks is identified from those bits.
+
+use crate::extent::Scheme;
+use crate::Partitions;
+use prettytable::{Cell, Row, Table};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+pub const CHROMEOS_KERNEL_TYPE: &str = "fe3a2a5d-4f32-41a7-b725-accc3285a309";
+pub const CHROMEOS_ROOTFS_TYPE: &str = "3cb8e202-3b7e-47dd-8a3c-7ff2a13cfcec";
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Platform {
+    ChromeOS,
+    Android,
+}
+
+/// Boot state of one slot, from the type-specific attribute bits (48 to 63).
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BootAttributes {
+    pub priority: u8,
+    pub tries: u8, // Tries left (ChromeOS), retry count (Android)
+    pub successful: bool,
+    pub active: bool,     // Android only
+    pub unbootable: bool, // Android only
+}
+
+impl BootAttributes {
+    /// ChromeOS kernel: priority in bits 48-51, tries in 52-55, successful in 56.
+    pub fn chromeos(attributes: u64) -> BootAttributes {
+        BootAttributes {
+            priority: (attributes >> 48 & 0xF) as u8,
+            tries: (attributes >> 52 & 0xF) as u8,
+            successful: attributes >> 56 & 1 == 1,
+            active: false,
+            unbootable: false,
+        }
+    }
+
+    /// Android: priority in bits 48-49, active in 50, retry count in 51-53, successful in 54
+    /// and unbootable in 55.
+    pub fn android(attributes: u64) -> BootAttributes {
+        BootAttributes {
+            priority: (attributes >> 48 & 0x3) as u8,
+            tries: (attributes >> 51 & 0x7) as u8,
+            successful: attributes >> 54 & 1 == 1,
+            active: attributes >> 50 & 1 == 1,
+            unbootable: attributes >> 55 & 1 == 1,
+        }
+    }
+
+    /// Whether the bootloader may still pick this slot.
+    pub fn is_bootable(&self) -> bool {
+        self.priority > 0 && !self.unbootable && (self.successful || self.tries > 0)
+    }
+
+    pub fn flags(&self, platform: Platform) -> Vec<String> {
+        let mut flags = vec![
+            format!("Priority {}", self.priority),
+            format!("Tries {}", self.tries),
+        ];
+        if self.successful {
+            flags.push("Successful".to_string());
+        }
+        if platform == Platform::Android {
+            if self.active {
+                flags.push("Active".to_string());
+            }
+            if self.unbootable {
+                flags.push("Unbootable".to_string());
+            }
+        }
+        flags
+    }
+}
+
+/// The platform and slot a GPT partition belongs to, from its type and name: "KERN-A" gives
+/// ("KERN", "A") on ChromeOS, "boot_b" gives ("boot", "b") on Android.
+pub fn slot_of(type_guid: &str, name: &str) -> Option<(Platform, String, String)> {
+    let chromeos = type_guid.eq_ignore_ascii_case(CHROMEOS_KERNEL_TYPE)
+        || type_guid.eq_ignore_ascii_case(CHROMEOS_ROOTFS_TYPE);
+    let (platform, separator) = if chromeos {
+        (Platform::ChromeOS, '-')
+    } else {
+        (Platform::Android, '_')
+    };
+    let (base, slot) = name.rsplit_once(separator)?;
+    let is_slot = match platform {
+        Platform::ChromeOS => matches!(slot, "A" | "B"),
+        Platform::Android => matches!(slot, "a" | "b"),
+    };
+    if base.is_empty() || !is_slot {
+        return None;
+    }
+    Some((platform, base.to_string(), slot.to_string()))
+}
+
+/// One partition of an A/B pair.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct SlotMember {
+    pub slot: String,
+    pub partition_index: u64, // Index of the matching `PartitionExtent`
+    pub name: String,
+    pub attributes: u64,
+    pub boot: Option<BootAttributes>, // When the partition carries boot state
+}
+
+/// The partitions sharing a base name, one per slot.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct SlotGroup {
+    pub base_name: String,
+    pub members: Vec<SlotMember>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct AbSlots {
+    pub platform: Platform,
+    pub active_slot: Option<String>,
+    pub decided_by: Option<String>, // Base name of the group whose attributes tell the slot
+    pub groups: Vec<SlotGroup>,
+    pub issues: Vec<String>,
+}
+
+/// The slot the bootloader picks among `members`, with what looks wrong about them.
+fn pick_active(platform: Platform, members: &[SlotMember]) -> (Option<String>, Vec<String>) {
+    let mut issues = Vec::new();
+    let states: Vec<(&SlotMember, BootAttributes)> = members
+        .iter()
+        .filter_map(|member| member.boot.map(|boot| (member, boot)))
+        .collect();
+    // Highest priority first, the first slot in the table on ties.
+    let best = |candidates: Vec<&(&SlotMember, BootAttributes)>| {
+        candidates
+            .into_iter()
+            .rev()
+            .max_by_key(|(_, boot)| boot.priority)
+            .map(|(member, _)| member.slot.clone())
+    };
+
+    if platform == Platform::Android {
+        let active: Vec<_> = states.iter().filter(|(_, boot)| boot.active).collect();
+        match active.len() {
+            1 => {
+                let (member, boot) = *active[0];
+                if !boot.is_bootable() {
+                    issues.push(format!(
+                        "Active slot {} is not bootable (priority {}, retry count {}{})",
+                        member.slot,
+                        boot.priority,
+                        boot.tries,
+                        if boot.unbootable { ", unbootable" } else { "" }
+                    ));
+                }
+                return (Some(member.slot.clone()), issues);
+            }
+            0 => issues.push("No slot is marked active".to_string()),
+            _ => issues.push("Several slots are marked active".to_string()),
+        }
+    }
+
+    let bootable: Vec<_> = states
+        .iter()
+        .filter(|(_, boot)| boot.is_bootable())
+        .collect();
+    if bootable.is_empty() {
+        issues.push("No slot is bootable".to_string());
+        return (None, issues);
+    }
+    (best(bootable), issues)
+}
+
+impl Partitions {
+    /// Group the A/B partitions of the GPT by platform and base name, and identify the slot
+    /// the bootloader picks: from the ChromeOS kernel attributes, or from the Android boot
+    /// state bits of the first group carrying any.
+    pub fn ab_slots(&self) -> Vec<AbSlots> {
+        let gpt = match &self.gpt {
+            Some(gpt) => gpt,
+            None => return Vec::new(),
+        };
+        let extents = self
+            .extents()
+            .into_iter()
+            .filter(|extent| extent.scheme == Scheme::GPT);
+
+        let mut all: Vec<AbSlots> = Vec::new();
+        for (entry, extent) in gpt.partition_entries.iter().zip(extents) {
+            let name = entry.partition_name.trim_end_matches('\0');
+            let Some((platform, base_name, slot)) =
+                slot_of(&entry.partition_type_guid_string, name)
+            else {
+                continue;
+            };
+            let boot = match platform {
+                Platform::ChromeOS
+                    if entry
+                        .partition_type_guid_string
+                        .eq_ignore_ascii_case(CHROMEOS_KERNEL_TYPE) =>
+                {
+                    Some(BootAttributes::chromeos(entry.attributes))
+                }
+                Platform::Android if entry.attributes >> 48 & 0xFF != 0 => {
+                    Some(BootAttributes::android(entry.attributes))
+                }
+                _ => None,
+            };
+            let member = SlotMember {
+                slot,
+                partition_index: extent.index,
+                name: name.to_string(),
+                attributes: entry.attributes,
+                boot,
+            };
+
+            let slots = match all.iter().position(|slots| slots.platform == platform) {
+                Some(i) => &mut all[i],
+                None => {
+                    all.push(AbSlots {
+                        platform,
+                        active_slot: None,
+                        decided_by: None,
+                        groups: Vec::new(),
+                        issues: Vec::new(),
+                    });
+                    all.last_mut().unwrap()
+                }
+            };
+            match slots
+                .groups
+                .iter_mut()
+                .find(|group| group.base_name == base_name)
+            {
+                Some(group) => group.members.push(member),
+                None => slots.groups.push(SlotGroup {
+                    base_name,
+                    members: vec![member],
+                }),
+            }
+        }
+
+        for slots in &mut all {
+            for group in &slots.groups {
+                if group.members.len() < 2 {
+                    slots
+                        .issues
+                        .push(format!("{} has a single slot", group.members[0].name));
+                }
+            }
+            let deciding = slots
+                .groups
+                .iter()
+                .find(|group| group.members.iter().any(|member| member.boot.is_some()));
+            match deciding {
+                Some(group) => {
+                    let (active, issues) = pick_active(slots.platform, &group.members);
+                    slots.active_slot = active;
+                    slots.decided_by = Some(group.base_name.clone());
+                    slots.issues.extend(issues);
+                }
+                None => slots.issues.push(
+                    "No slot carries boot state attributes; the bootloader keeps it elsewhere"
+                        .to_string(),
+                ),
+            }
+        }
+        all
+    }
+}
+
+pub fn print_info(all: &[AbSlots]) -> String {
+    let mut s = String::new();
+    for slots in all {
+        let mut table = Table::new();
+        table.add_row(Row::new(vec![
+            Cell::new("Group"),
+            Cell::new("Slot"),
+            Cell::new("Partition"),
+            Cell::new("Boot state"),
+        ]));
+        for group in &slots.groups {
+            for member in &group.members {
+                table.add_row(Row::new(vec![
+                    Cell::new(&group.base_name),
+                    Cell::new(&member.slot),
+                    Cell::new(&format!("#{} {}", member.partition_index, member.name)),
+                    Cell::new(
+                        &member
+                            .boot
+                            .map(|boot| boot.flags(slots.platform).join(", "))
+                            .unwrap_or_default(),
+                    ),
+                ]));
+            }
+        }
+        s.push_str(&format!(
+            "\n{:?} A/B slots (active: {}):\n{}",
+            slots.platform,
+            slots.active_slot.as_deref().unwrap_or("unknown"),
+            table
+        ));
+        for issue in &slots.issues {
+            s.push_str(&format!("Warning: {}\n", issue));
+        }
+    }
+    s
+}
@@ -0,0 +1,388 @@
+//! BitLocker volumes: the volume header and the FVE (Full Volume Encryption) metadata.
+//!
+//! A BitLocker volume starts with a boot sector whose OEM name is "-FVE-FS-" (or "MSWIN4.1"
+//! for BitLocker To Go) and which points to three copies of the FVE metadata block. Each
+//! block holds a header followed by a list of entries: the volume master keys (VMK) with
+//! their protectors, the encrypted full volume encryption key (FVEK), the description...
+
+use crate::gpt::format_guid;
+use crate::reader::SectorReader;
+use byteorder::{ByteOrder, LittleEndian};
+use log::warn;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::io::SeekFrom;
+
+pub const SIGNATURE: &[u8; 8] = b"-FVE-FS-";
+/// Identifies the Windows 7 and later volume header, and BitLocker To Go.
+const BITLOCKER_GUID: &str = "4967d63b-2e29-4ad8-8399-f6a339e3d001";
+const FVE_BLOCK_HEADER_SIZE: usize = 64;
+const FVE_METADATA_HEADER_SIZE: usize = 48;
+const FVE_ENTRY_HEADER_SIZE: usize = 8;
+/// A VMK holds a stretch key, which holds the encrypted key: BitLocker nests no deeper,
+/// and deeper entries are left unparsed.
+const MAX_ENTRY_DEPTH: usize = 3;
+/// Each copy of the FVE metadata is given a 64 KiB area of the volume, which it cannot outgrow.
+const MAX_METADATA_SIZE: usize = 64 * 1024;
+/// Seconds between the FILETIME epoch (1601-01-01) and the Unix epoch.
+pub const FILETIME_UNIX_OFFSET: u64 = 11_644_473_600;
+
+pub const ENTRY_TYPE_PROPERTY: u16 = 0x0000;
+pub const ENTRY_TYPE_VMK: u16 = 0x0002;
+pub const ENTRY_TYPE_FVEK: u16 = 0x0003;
+pub const ENTRY_TYPE_DESCRIPTION: u16 = 0x0007;
+pub const VALUE_TYPE_KEY: u16 = 0x0001;
+pub const VALUE_TYPE_UNICODE_STRING: u16 = 0x0002;
+pub const VALUE_TYPE_STRETCH_KEY: u16 = 0x0003;
+pub const VALUE_TYPE_USE_KEY: u16 = 0x0004;
+pub const VALUE_TYPE_AES_CCM_ENCRYPTED_KEY: u16 = 0x0005;
+pub const VALUE_TYPE_VMK: u16 = 0x0008;
+pub const VALUE_TYPE_EXTERNAL_KEY: u16 = 0x0009;
+pub const PROTECTION_CLEAR_KEY: u16 = 0x0000;
+pub const PROTECTION_RECOVERY_PASSWORD: u16 = 0x0800;
+
+/// One metadata entry. Entries holding keys nest further entries in `properties`.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct FveEntry {
+    pub entry_type: u16,
+    pub value_type: u16,
+    pub version: u16,
+    pub description: String,
+    #[serde(skip)]
+    pub data: Vec<u8>, // Value, entry header excluded
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub properties: Vec<FveEntry>,
+}
+
+/// A volume master key protector.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct VolumeMasterKey {
+    pub key_id: String, // The identifier shown when BitLocker asks for the recovery key
+    pub last_modified: Option<String>,
+    pub protection_type: u16,
+    pub protection: String,
+}
+
+/// One of the three copies of the FVE metadata.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct FveMetadataBlock {
+    pub offset: u64, // In bytes from the volume start
+    pub version: u16,
+    pub encrypted_volume_size: u64,
+    pub volume_header_sectors: u32,
+    pub metadata_offsets: Vec<u64>,
+    pub volume_header_offset: u64, // Where the original boot sector was moved to
+    pub metadata_size: u32,        // Metadata header and entries
+    pub volume_guid: String,
+    pub next_nonce_counter: u32,
+    pub encryption_method: u16,
+    pub creation_time: u64, // FILETIME
+    pub entries: Vec<FveEntry>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct BitLockerInfo {
+    pub to_go: bool,                // BitLocker To Go, behind a FAT boot sector
+    pub metadata_offsets: Vec<u64>, // FVE metadata blocks, in bytes from the volume start
+    pub encryption_method: Option<String>,
+    pub volume_guid: Option<String>,
+    pub protectors: Vec<String>, // Protection type of every volume master key (VMK)
+    #[serde(default)]
+    pub creation_time: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>, // e.g. "DESKTOP-XYZ C: 2024-01-01"
+    #[serde(default)]
+    pub volume_master_keys: Vec<VolumeMasterKey>,
+    #[serde(default)]
+    pub blocks: Vec<FveMetadataBlock>, // The readable metadata blocks
+}
+
+impl BitLockerInfo {
+    /// Key IDs of the recovery password protectors: what to ask the key holder for.
+    pub fn recovery_key_ids(&self) -> Vec<&str> {
+        self.volume_master_keys
+            .iter()
+            .filter(|vmk| vmk.protection_type == PROTECTION_RECOVERY_PASSWORD)
+            .map(|vmk| vmk.key_id.as_str())
+            .collect()
+    }
+
+    /// The first readable metadata block.
+    pub fn metadata(&self) -> Option<&FveMetadataBlock> {
+        self.blocks.first()
+    }
+}
+
+pub fn encryption_method_description(method: u16) -> String {
+    match method {
+        0x0000 => "Not encrypted".to_string(),
+        0x8000 => "AES-128-CBC with Elephant diffuser".to_string(),
+        0x8001 => "AES-256-CBC with Elephant diffuser".to_string(),
+        0x8002 => "AES-128-CBC".to_string(),
+        0x8003 => "AES-256-CBC".to_string(),
+        0x8004 => "AES-128-XTS".to_string(),
+        0x8005 => "AES-256-XTS".to_string(),
+        _ => format!("Unknown (0x{:04x})", method),
+    }
+}
+
+pub fn protection_type_description(protection: u16) -> String {
+    match protection {
+        0x0000 => "Clear key (protection suspended)".to_string(),
+        0x0100 => "TPM".to_string(),
+        0x0200 => "Startup key".to_string(),
+        0x0500 => "TPM and PIN".to_string(),
+        0x0800 => "Recovery password".to_string(),
+        0x2000 => "Password".to_string(),
+        _ => format!("Unknown (0x{:04x})", protection),
+    }
+}
+
+pub fn entry_type_description(entry_type: u16) -> &'static str {
+    match entry_type {
+        0x0000 => "Property",
+        0x0002 => "Volume master key",
+        0x0003 => "Full volume encryption key",
+        0x0004 => "Validation",
+        0x0006 => "Startup key",
+        0x0007 => "Description",
+        0x000B => "Full volume encryption key backup",
+        0x000F => "Volume header block",
+        _ => "Unknown",
+    }
+}
+
+/// FILETIME (100 ns intervals since 1601-01-01) as an ISO 8601 UTC timestamp.
+pub fn filetime_to_string(filetime: u64) -> Option<String> {
+    let secs = (filetime / 10_000_000).checked_sub(FILETIME_UNIX_OFFSET)?;
+    let days = (secs / 86400) as i64;
+    let rem = secs % 86400;
+    // Civil date from days since 1970-01-01 (H. Hinnant's algorithm).
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    Some(format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    ))
+}
+
+fn guid_at(bytes: &[u8], offset: usize) -> String {
+    let mut guid = [0u8; 16];
+    guid.copy_from_slice(&bytes[offset..offset + 16]);
+    format_guid(&guid)
+}
+
+fn utf16_string(bytes: &[u8]) -> String {
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(LittleEndian::read_u16)
+        .take_while(|&unit| unit != 0)
+        .collect();
+    String::from_utf16_lossy(&units)
+}
+
+/// The three metadata block offsets from the volume header, if it is a BitLocker one.
+fn metadata_offsets(boot_sector: &[u8]) -> Option<(bool, Vec<u64>)> {
+    let offsets_at = |offset: usize| {
+        (0..3)
+            .map(|i| LittleEndian::read_u64(&boot_sector[offset + i * 8..]))
+            .collect::<Vec<u64>>()
+    };
+    if &boot_sector[3..11] == SIGNATURE {
+        if guid_at(boot_sector, 0xA0) == BITLOCKER_GUID {
+            return Some((false, offsets_at(0xB0)));
+        }
+        // Windows Vista: only the first block is given, in clusters.
+        let cluster_size =
+            LittleEndian::read_u16(&boot_sector[0x0B..]) as u64 * boot_sector[0x0D] as u64;
+        let first = LittleEndian::read_u64(&boot_sector[0x38..]).checked_mul(cluster_size)?;
+        return Some((false, vec![first]));
+    }
+    if &boot_sector[3..11] == b"MSWIN4.1" && guid_at(boot_sector, 0x1A0) == BITLOCKER_GUID {
+        return Some((true, offsets_at(0x1B0)));
+    }
+    None
+}
+
+/// Where the nested entries start in the value of an entry, for the value types having some.
+fn properties_offset(value_type: u16) -> Option<usize> {
+    match value_type {
+        VALUE_TYPE_VMK => Some(28), // GUID, FILETIME, unknown u16, protection type
+        VALUE_TYPE_STRETCH_KEY => Some(20), // Encryption method u32, salt
+        VALUE_TYPE_USE_KEY => Some(4), // Encryption method u32
+        VALUE_TYPE_EXTERNAL_KEY => Some(24), // GUID, FILETIME
+        _ => None,
+    }
+}
+
+/// Parse a list of entries, stopping at the first empty or truncated one.
+pub fn parse_entries(bytes: &[u8]) -> Vec<FveEntry> {
+    parse_entries_at(bytes, 1)
+}
+
+/// Parse a list of entries at nesting `depth`, the top-level list being at depth 1.
+fn parse_entries_at(bytes: &[u8], depth: usize) -> Vec<FveEntry> {
+    let mut entries = Vec::new();
+    let mut pos = 0;
+    while pos + FVE_ENTRY_HEADER_SIZE <= bytes.len() {
+        let size = LittleEndian::read_u16(&bytes[pos..]) as usize;
+        if size < FVE_ENTRY_HEADER_SIZE || pos + size > bytes.len() {
+            break;
+        }
+        let entry_type = LittleEndian::read_u16(&bytes[pos + 2..]);
+        let value_type = LittleEndian::read_u16(&bytes[pos + 4..]);
+        let data = &bytes[pos + FVE_ENTRY_HEADER_SIZE..pos + size];
+        let properties = match properties_offset(value_type) {
+            Some(offset) if offset <= data.len() && depth < MAX_ENTRY_DEPTH => {
+                parse_entries_at(&data[offset..], depth + 1)
+            }
+            _ => Vec::new(),
+        };
+        entries.push(FveEntry {
+            entry_type,
+            value_type,
+            version: LittleEndian::read_u16(&bytes[pos + 6..]),
+            description: entry_type_description(entry_type).to_string(),
+            data: data.to_vec(),
+            properties,
+        });
+        pos += size;
+    }
+    entries
+}
+
+impl FveEntry {
+    /// The protector of a VMK entry.
+    pub fn as_vmk(&self) -> Option<VolumeMasterKey> {
+        if self.entry_type != ENTRY_TYPE_VMK
+            || self.value_type != VALUE_TYPE_VMK
+            || self.data.len() < 28
+        {
+            return None;
+        }
+        let protection_type = LittleEndian::read_u16(&self.data[26..]);
+        Some(VolumeMasterKey {
+            key_id: guid_at(&self.data, 0),
+            last_modified: filetime_to_string(LittleEndian::read_u64(&self.data[16..])),
+            protection_type,
+            protection: protection_type_description(protection_type),
+        })
+    }
+}
+
+/// Read and parse the FVE metadata block at `block_offset` of the volume at `offset`.
+fn read_block<R: SectorReader + ?Sized>(
+    body: &mut R,
+    offset: u64,
+    block_offset: u64,
+) -> Option<FveMetadataBlock> {
+    let mut header = [0u8; FVE_BLOCK_HEADER_SIZE + FVE_METADATA_HEADER_SIZE];
+    body.seek(SeekFrom::Start(offset.checked_add(block_offset)?))
+        .ok()?;
+    body.read_exact(&mut header).ok()?;
+    if &header[0..8] != SIGNATURE {
+        return None;
+    }
+    let metadata = &header[FVE_BLOCK_HEADER_SIZE..];
+    let metadata_size = LittleEndian::read_u32(metadata);
+    let mut entries = Vec::new();
+    if (FVE_METADATA_HEADER_SIZE..=MAX_METADATA_SIZE).contains(&(metadata_size as usize)) {
+        let mut bytes = vec![0u8; metadata_size as usize - FVE_METADATA_HEADER_SIZE];
+        if body.read_exact(&mut bytes).is_ok() {
+            entries = parse_entries(&bytes);
+        }
+    }
+    Some(FveMetadataBlock {
+        offset: block_offset,
+        version: LittleEndian::read_u16(&header[10..]),
+        encrypted_volume_size: LittleEndian::read_u64(&header[16..]),
+        volume_header_sectors: LittleEndian::read_u32(&header[28..]),
+        metadata_offsets: (0..3)
+            .map(|i| LittleEndian::read_u64(&header[32 + i * 8..]))
+            .collect(),
+        volume_header_offset: LittleEndian::read_u64(&header[56..]),
+        metadata_size,
+        volume_guid: guid_at(metadata, 16),
+        next_nonce_counter: LittleEndian::read_u32(&metadata[32..]),
+        encryption_method: LittleEndian::read_u16(&metadata[36..]),
+        creation_time: LittleEndian::read_u64(&metadata[40..]),
+        entries,
+    })
+}
+
+/// Parse the BitLocker volume header at `offset` and its FVE metadata blocks.
+pub fn parse<R: SectorReader + ?Sized>(body: &mut R, offset: u64) -> Option<BitLockerInfo> {
+    let mut boot_sector = [0u8; 512];
+    body.seek(SeekFrom::Start(offset)).ok()?;
+    body.read_exact(&mut boot_sector).ok()?;
+    let (to_go, mut offsets) = metadata_offsets(&boot_sector)?;
+
+    let mut blocks: Vec<FveMetadataBlock> = Vec::new();
+    let mut i = 0;
+    while i < offsets.len() {
+        if let Some(block) = read_block(body, offset, offsets[i]) {
+            // Vista only gives the first block; the others are listed in its header.
+            if offsets.len() == 1 {
+                offsets.extend(block.metadata_offsets.iter().skip(1));
+            }
+            blocks.push(block);
+        } else {
+            warn!(
+                "No FVE metadata block at offset 0x{:x} of the BitLocker volume at 0x{:x}.",
+                offsets[i], offset
+            );
+        }
+        i += 1;
+    }
+    if let Some(first) = blocks.first() {
+        if blocks.iter().any(|block| {
+            block.volume_guid != first.volume_guid || block.metadata_size != first.metadata_size
+        }) {
+            warn!(
+                "The FVE metadata blocks of the BitLocker volume at 0x{:x} disagree.",
+                offset
+            );
+        }
+    }
+
+    let first = blocks.first();
+    let volume_master_keys: Vec<VolumeMasterKey> = first
+        .map(|block| block.entries.iter().filter_map(FveEntry::as_vmk).collect())
+        .unwrap_or_default();
+    Some(BitLockerInfo {
+        to_go,
+        metadata_offsets: offsets,
+        encryption_method: first
+            .map(|block| encryption_method_description(block.encryption_method)),
+        volume_guid: first.map(|block| block.volume_guid.clone()),
+        protectors: volume_master_keys
+            .iter()
+            .map(|vmk| vmk.protection.clone())
+            .collect(),
+        creation_time: first.and_then(|block| filetime_to_string(block.creation_time)),
+        description: first.and_then(|block| {
+            block
+                .entries
+                .iter()
+                .find(|entry| {
+                    entry.entry_type == ENTRY_TYPE_DESCRIPTION
+                        && entry.value_type == VALUE_TYPE_UNICODE_STRING
+                })
+                .map(|entry| utf16_string(&entry.data))
+        }),
+        volume_master_keys,
+        blocks,
+    })
+}
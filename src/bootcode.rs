@@ -0,0 +1,269 @@
+use capstone::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Address at which the BIOS loads the boot sector.
+pub const LOAD_ADDRESS: u32 = 0x7C00;
+/// Address most MBR boot codes relocate themselves to before chain-loading the VBR.
+pub const RELOCATION_ADDRESS: u32 = 0x0600;
+/// Minimum length of a printable run to be reported as a string.
+const MIN_STRING_LEN: usize = 4;
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct BootInstruction {
+    pub address: u32,  // Address of the instruction once loaded in memory
+    pub offset: usize, // Offset of the instruction inside the boot code
+    pub bytes: Vec<u8>,
+    pub mnemonic: String,
+    pub operands: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct BootString {
+    pub address: u32,
+    pub offset: usize,
+    pub value: String,
+}
+
+/// A contiguous range of the boot code that is never reached from the entry point.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct DataRegion {
+    pub address: u32,
+    pub offset: usize,
+    pub length: usize,
+}
+
+/// Recursive-descent disassembly of a boot sector code area.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct BootCode {
+    pub load_address: u32,
+    pub relocation_address: Option<u32>, // Set when the code copies itself and jumps to the copy
+    pub instructions: Vec<BootInstruction>,
+    pub data: Vec<DataRegion>,
+    pub strings: Vec<BootString>,
+}
+
+enum Flow {
+    Continue,
+    Branch(u32),
+    Jump(u32),
+    FarJump(u32),
+    Stop,
+}
+
+impl BootCode {
+    /// Disassemble the boot code starting at its entry point (first byte, loaded at 0x7C00),
+    /// following every branch that stays inside the code area.
+    /// Relocation to another address is followed when the code copies itself with
+    /// `rep movs` and far-jumps (or `retf`s) into the copy.
+    pub fn disassemble(code: &[u8]) -> BootCode {
+        let mut bootcode = BootCode {
+            load_address: LOAD_ADDRESS,
+            ..Default::default()
+        };
+        if code.iter().all(|b| *b == 0) {
+            return bootcode;
+        }
+
+        let cs = match Capstone::new()
+            .x86()
+            .mode(arch::x86::ArchMode::Mode16)
+            .build()
+        {
+            Ok(cs) => cs,
+            Err(_) => return bootcode,
+        };
+
+        let mut decoded: BTreeMap<usize, BootInstruction> = BTreeMap::new();
+        let mut covered = vec![false; code.len()];
+        let mut worklist: Vec<(usize, u32)> = vec![(0, LOAD_ADDRESS)];
+        // Destination register value seen before a `rep movs`, used to recognise relocation.
+        let mut copy_destination: Option<u32> = None;
+        let mut last_pushed: Option<u32> = None;
+
+        while let Some((start, base)) = worklist.pop() {
+            let mut offset = start;
+            while offset < code.len() && !covered[offset] {
+                let address = base + offset as u32;
+                let insns = match cs.disasm_count(&code[offset..], address as u64, 1) {
+                    Ok(insns) => insns,
+                    Err(_) => break,
+                };
+                let insn = match insns.iter().next() {
+                    Some(insn) => insn,
+                    None => break,
+                };
+                let length = insn.len();
+                // Overlapping decodes and zero padding (`add [bx+si], al`) mean we ran into data.
+                if covered[offset..offset + length].iter().any(|c| *c)
+                    || insn.bytes().iter().all(|b| *b == 0)
+                {
+                    break;
+                }
+                covered[offset..offset + length]
+                    .iter_mut()
+                    .for_each(|c| *c = true);
+
+                let mnemonic = insn.mnemonic().unwrap_or_default().to_string();
+                let operands = insn.op_str().unwrap_or_default().to_string();
+
+                let flow = match mnemonic.as_str() {
+                    "jmp" => parse_immediate(&operands).map_or(Flow::Stop, Flow::Jump),
+                    "ljmp" => parse_far_pointer(&operands).map_or(Flow::Stop, Flow::FarJump),
+                    "retf" => last_pushed.map_or(Flow::Stop, Flow::FarJump),
+                    "call" => parse_immediate(&operands).map_or(Flow::Continue, Flow::Branch),
+                    "ret" | "iret" => Flow::Stop,
+                    "int" if operands == "0x18" || operands == "0x19" => Flow::Stop,
+                    m if m.starts_with('j') || m.starts_with("loop") => {
+                        parse_immediate(&operands).map_or(Flow::Continue, Flow::Branch)
+                    }
+                    _ => Flow::Continue,
+                };
+
+                if mnemonic == "mov" {
+                    if let Some(value) = operands.strip_prefix("di, ") {
+                        copy_destination = parse_immediate(value);
+                    }
+                }
+                last_pushed = match mnemonic.as_str() {
+                    "push" => parse_immediate(&operands),
+                    _ => None,
+                };
+
+                decoded.insert(
+                    offset,
+                    BootInstruction {
+                        address,
+                        offset,
+                        bytes: insn.bytes().to_vec(),
+                        mnemonic,
+                        operands,
+                    },
+                );
+                offset += length;
+
+                let in_window = |target: u32, window: u32| {
+                    target >= window && ((target - window) as usize) < code.len()
+                };
+                match flow {
+                    Flow::Continue => {}
+                    Flow::Branch(target) => {
+                        if in_window(target, base) {
+                            worklist.push(((target - base) as usize, base));
+                        }
+                    }
+                    Flow::Jump(target) => {
+                        if in_window(target, base) {
+                            worklist.push(((target - base) as usize, base));
+                        }
+                        break;
+                    }
+                    Flow::FarJump(target) => {
+                        let destination = copy_destination.unwrap_or(RELOCATION_ADDRESS);
+                        if destination != base && in_window(target, destination) {
+                            bootcode.relocation_address = Some(destination);
+                            worklist.push(((target - destination) as usize, destination));
+                            break;
+                        }
+                        if in_window(target, base) {
+                            worklist.push(((target - base) as usize, base));
+                        }
+                        break;
+                    }
+                    Flow::Stop => break,
+                }
+            }
+        }
+
+        // Drop a relocation guess that was never backed by an actual `rep movs`.
+        if bootcode.relocation_address.is_some()
+            && !decoded.values().any(|i| i.mnemonic.starts_with("rep movs"))
+        {
+            bootcode.relocation_address = None;
+        }
+
+        let data_base = bootcode.relocation_address.unwrap_or(LOAD_ADDRESS);
+        let mut offset = 0;
+        while offset < code.len() {
+            if covered[offset] {
+                offset += 1;
+                continue;
+            }
+            let start = offset;
+            while offset < code.len() && !covered[offset] {
+                offset += 1;
+            }
+            bootcode.data.push(DataRegion {
+                address: data_base + start as u32,
+                offset: start,
+                length: offset - start,
+            });
+            bootcode
+                .strings
+                .extend(extract_strings(&code[start..offset], start, data_base));
+        }
+
+        bootcode.instructions = decoded.into_values().collect();
+        bootcode
+    }
+
+    /// Human readable listing: the reachable instructions followed by the embedded strings.
+    pub fn listing(&self) -> String {
+        let mut s = String::new();
+        if let Some(relocation) = self.relocation_address {
+            s.push_str(&format!(
+                "; loaded at 0x{:04x}, relocated to 0x{:04x}\n",
+                self.load_address, relocation
+            ));
+        }
+        for ins in &self.instructions {
+            let bytes: Vec<String> = ins.bytes.iter().map(|b| format!("{:02x}", b)).collect();
+            s.push_str(&format!(
+                "0x{:04x}: {:<24} {} {}\n",
+                ins.address,
+                bytes.join(" "),
+                ins.mnemonic,
+                ins.operands
+            ));
+        }
+        for string in &self.strings {
+            s.push_str(&format!("0x{:04x}: {:?}\n", string.address, string.value));
+        }
+        s.trim_end().to_string()
+    }
+}
+
+fn parse_immediate(operand: &str) -> Option<u32> {
+    let operand = operand.trim();
+    match operand.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => operand.parse().ok(),
+    }
+}
+
+/// Convert a `segment:offset` far pointer operand into a linear address.
+fn parse_far_pointer(operand: &str) -> Option<u32> {
+    let (segment, offset) = operand.split_once(':')?;
+    Some((parse_immediate(segment)? << 4) + parse_immediate(offset)?)
+}
+
+fn extract_strings(data: &[u8], region_offset: usize, base: u32) -> Vec<BootString> {
+    let mut strings = Vec::new();
+    let mut start = 0;
+    for i in 0..=data.len() {
+        let printable = i < data.len() && (0x20..0x7F).contains(&data[i]);
+        if printable {
+            continue;
+        }
+        if i - start >= MIN_STRING_LEN {
+            let offset = region_offset + start;
+            strings.push(BootString {
+                address: base + offset as u32,
+                offset,
+                value: String::from_utf8_lossy(&data[start..i]).trim().to_string(),
+            });
+        }
+        start = i + 1;
+    }
+    strings
+}
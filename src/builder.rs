@@ -0,0 +1,489 @@
+//! Synthetic raw disk images, composed from a declarative layout.
+//!
+//! Partition contents are left zeroed unless written with `write_at`; only the partition
+//! tables are generated. The images are meant for tests, fixtures and training, which is why
+//! the layout can also ask for deliberately broken structures (`Corruption`).
+
+use crate::gpt::{parse_guid, GPTHeader, GPTPartitionEntry, GPT};
+use crate::mbr::{MBRPartitionEntry, MBR};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs::File;
+use std::io::{Seek, SeekFrom, Write};
+use std::path::Path;
+
+const GPT_ENTRIES: u32 = 128;
+const GPT_ENTRY_SIZE: u32 = 128;
+
+/// (byte offset, bytes) pairs making up an image.
+type Structures = Vec<(u64, Vec<u8>)>;
+
+/// Damage applied to the generated tables.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Corruption {
+    BadHeaderCrc,         // The primary GPT header CRC does not match the header
+    BadPartitionArrayCrc, // The primary partition array CRC does not match the array
+    MissingPrimaryGpt,    // The primary GPT header is not written
+    MissingBackupGpt,     // The backup GPT header is not written
+    LoopingEbr,           // The last EBR links back to the first one
+    OverlappingEntries,   // The first partition is stretched over the start of the second
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MbrPartitionSpec {
+    pub partition_type: u8,
+    pub start_lba: u32,
+    pub size_sectors: u32,
+    #[serde(default)]
+    pub bootable: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GptPartitionSpec {
+    pub type_guid: String,
+    pub guid: String,
+    #[serde(default)]
+    pub name: String,
+    pub first_lba: u64,
+    pub last_lba: u64, // Inclusive
+    #[serde(default)]
+    pub attributes: u64,
+}
+
+/// Everything the builder needs, in a form that can be kept in a JSON file.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DiskLayout {
+    #[serde(default = "default_sector_size")]
+    pub sector_size: u64,
+    pub total_sectors: u64,
+    #[serde(default)]
+    pub boot_code: Vec<u8>,
+    #[serde(default)]
+    pub primaries: Vec<MbrPartitionSpec>,
+    #[serde(default)]
+    pub logicals: Vec<MbrPartitionSpec>, // Inside the first extended primary
+    pub disk_guid: Option<String>, // Set to write a GPT
+    #[serde(default)]
+    pub gpt_partitions: Vec<GptPartitionSpec>,
+    #[serde(default)]
+    pub corruptions: Vec<Corruption>,
+}
+
+fn default_sector_size() -> u64 {
+    512
+}
+
+/// Fluent construction of a `DiskLayout`, and its serialization to a raw image.
+///
+/// With a GPT and no primary partition the MBR is protective. With both, the MBR is hybrid:
+/// the 0xEE entry covers the GPT structures at the start of the disk and the primaries follow.
+/// The first EBR sits at the start of the extended partition, the others in the sector just
+/// before their logical partition.
+#[derive(Debug, Clone)]
+pub struct DiskImageBuilder {
+    layout: DiskLayout,
+    blobs: Vec<(u64, Vec<u8>)>, // (LBA, bytes) written over the generated image
+}
+
+fn chs(lba: u64) -> [u8; 3] {
+    let (heads, sectors) = (255u64, 63u64);
+    let cylinder = lba / (heads * sectors);
+    if cylinder > 1023 {
+        return [0xFE, 0xFF, 0xFF];
+    }
+    let head = (lba / sectors) % heads;
+    let sector = lba % sectors + 1;
+    [
+        head as u8,
+        (sector as u8) | (((cylinder >> 2) & 0xC0) as u8),
+        cylinder as u8,
+    ]
+}
+
+fn mbr_entry(
+    partition_type: u8,
+    start_lba: u32,
+    size_sectors: u32,
+    bootable: bool,
+    chs_base: u64,
+) -> MBRPartitionEntry {
+    let start = chs_base + start_lba as u64;
+    MBRPartitionEntry {
+        boot_indicator: if bootable { 0x80 } else { 0x00 },
+        start_chs: chs(start),
+        partition_type,
+        end_chs: chs(start + (size_sectors as u64).max(1) - 1),
+        start_lba,
+        size_sectors,
+        ..Default::default()
+    }
+}
+
+fn table(bootloader: &[u8], entries: Vec<MBRPartitionEntry>) -> MBR {
+    let mut mbr = MBR::from_bytes(&[0u8; 512]);
+    let code = bootloader.len().min(446);
+    mbr.bootloader[..code].copy_from_slice(&bootloader[..code]);
+    for (slot, entry) in mbr.partition_table.iter_mut().zip(entries) {
+        *slot = entry;
+    }
+    mbr.boot_signature = 0xAA55;
+    mbr
+}
+
+fn guid(value: &str) -> Result<[u8; 16], Box<dyn Error>> {
+    parse_guid(value).ok_or_else(|| format!("Invalid GUID '{}'", value).into())
+}
+
+impl DiskImageBuilder {
+    pub fn new(total_sectors: u64) -> DiskImageBuilder {
+        DiskImageBuilder::from_layout(DiskLayout {
+            sector_size: default_sector_size(),
+            total_sectors,
+            boot_code: Vec::new(),
+            primaries: Vec::new(),
+            logicals: Vec::new(),
+            disk_guid: None,
+            gpt_partitions: Vec::new(),
+            corruptions: Vec::new(),
+        })
+    }
+
+    pub fn from_layout(layout: DiskLayout) -> DiskImageBuilder {
+        DiskImageBuilder {
+            layout,
+            blobs: Vec::new(),
+        }
+    }
+
+    pub fn layout(&self) -> &DiskLayout {
+        &self.layout
+    }
+
+    pub fn sector_size(mut self, sector_size: u64) -> Self {
+        self.layout.sector_size = sector_size;
+        self
+    }
+
+    pub fn boot_code(mut self, code: &[u8]) -> Self {
+        self.layout.boot_code = code.to_vec();
+        self
+    }
+
+    pub fn primary(mut self, partition_type: u8, start_lba: u32, size_sectors: u32) -> Self {
+        self.layout.primaries.push(MbrPartitionSpec {
+            partition_type,
+            start_lba,
+            size_sectors,
+            bootable: false,
+        });
+        self
+    }
+
+    /// Mark the primary partition at `index` (in insertion order) as active.
+    pub fn bootable(mut self, index: usize) -> Self {
+        if let Some(primary) = self.layout.primaries.get_mut(index) {
+            primary.bootable = true;
+        }
+        self
+    }
+
+    /// Add an extended (0x0F) primary partition to hold the logical ones.
+    pub fn extended(self, start_lba: u32, size_sectors: u32) -> Self {
+        self.primary(0x0F, start_lba, size_sectors)
+    }
+
+    pub fn logical(mut self, partition_type: u8, start_lba: u32, size_sectors: u32) -> Self {
+        self.layout.logicals.push(MbrPartitionSpec {
+            partition_type,
+            start_lba,
+            size_sectors,
+            bootable: false,
+        });
+        self
+    }
+
+    /// Write a GPT (primary and backup) with this disk GUID.
+    pub fn gpt(mut self, disk_guid: &str) -> Self {
+        self.layout.disk_guid = Some(disk_guid.to_string());
+        self
+    }
+
+    pub fn gpt_partition(
+        self,
+        type_guid: &str,
+        guid: &str,
+        name: &str,
+        first_lba: u64,
+        last_lba: u64,
+    ) -> Self {
+        self.gpt_partition_spec(GptPartitionSpec {
+            type_guid: type_guid.to_string(),
+            guid: guid.to_string(),
+            name: name.to_string(),
+            first_lba,
+            last_lba,
+            attributes: 0,
+        })
+    }
+
+    pub fn gpt_partition_spec(mut self, spec: GptPartitionSpec) -> Self {
+        self.layout.gpt_partitions.push(spec);
+        self
+    }
+
+    pub fn corrupt(mut self, corruption: Corruption) -> Self {
+        self.layout.corruptions.push(corruption);
+        self
+    }
+
+    /// Write raw bytes at an LBA, over the generated tables if need be.
+    pub fn write_at(mut self, lba: u64, bytes: &[u8]) -> Self {
+        self.blobs.push((lba, bytes.to_vec()));
+        self
+    }
+
+    fn corrupted(&self, corruption: Corruption) -> bool {
+        self.layout.corruptions.contains(&corruption)
+    }
+
+    /// Every structure of the image as (byte offset, bytes), in write order.
+    fn structures(&self) -> Result<Structures, Box<dyn Error>> {
+        let layout = &self.layout;
+        let sector_size = layout.sector_size;
+        if sector_size < 512 || !sector_size.is_power_of_two() {
+            return Err(format!("Invalid sector size {}", sector_size).into());
+        }
+        let mut primaries = layout.primaries.clone();
+        let mut gpt_partitions = layout.gpt_partitions.clone();
+        if self.corrupted(Corruption::OverlappingEntries) {
+            if gpt_partitions.len() >= 2 {
+                gpt_partitions[0].last_lba = gpt_partitions[1].first_lba;
+            } else if primaries.len() >= 2 {
+                primaries[0].size_sectors = primaries[1]
+                    .start_lba
+                    .saturating_sub(primaries[0].start_lba)
+                    + 1;
+            } else {
+                return Err("Overlapping entries need at least two partitions".into());
+            }
+        }
+
+        for spec in primaries.iter().chain(&layout.logicals) {
+            if spec.start_lba as u64 + spec.size_sectors as u64 > layout.total_sectors {
+                return Err(
+                    format!("Partition at LBA {} ends past the disk", spec.start_lba).into(),
+                );
+            }
+        }
+
+        let mut structures = Vec::new();
+        let array_sectors = ((GPT_ENTRIES * GPT_ENTRY_SIZE) as u64).div_ceil(sector_size);
+        let first_usable = 2 + array_sectors;
+        // Both GPT copies and their entry arrays, around at least one usable sector.
+        if layout.disk_guid.is_some() && layout.total_sectors <= 2 * first_usable {
+            return Err("The disk is too small to hold a GPT".into());
+        }
+
+        // MBR: classic, protective or hybrid.
+        let mut entries = Vec::new();
+        if layout.disk_guid.is_some() {
+            let covered = if primaries.is_empty() {
+                (layout.total_sectors - 1).min(u32::MAX as u64)
+            } else {
+                first_usable - 1
+            };
+            let mut protective = mbr_entry(0xEE, 1, covered as u32, false, 0);
+            protective.start_chs = [0x00, 0x02, 0x00];
+            entries.push(protective);
+        }
+        for spec in &primaries {
+            entries.push(mbr_entry(
+                spec.partition_type,
+                spec.start_lba,
+                spec.size_sectors,
+                spec.bootable,
+                0,
+            ));
+        }
+        if entries.len() > 4 {
+            return Err(format!("{} entries do not fit in the MBR", entries.len()).into());
+        }
+        if !entries.is_empty() {
+            structures.push((0, table(&layout.boot_code, entries).to_bytes().to_vec()));
+        }
+
+        // EBR chain.
+        if !layout.logicals.is_empty() {
+            let extended = primaries
+                .iter()
+                .find(|p| matches!(p.partition_type, 0x05 | 0x0F | 0x85))
+                .ok_or("Logical partitions need an extended partition")?;
+            let ext_start = extended.start_lba;
+            let ext_end = ext_start as u64 + extended.size_sectors as u64;
+            let ebr_lbas: Vec<u32> = layout
+                .logicals
+                .iter()
+                .enumerate()
+                .map(|(i, spec)| {
+                    if i == 0 {
+                        ext_start
+                    } else {
+                        spec.start_lba.saturating_sub(1)
+                    }
+                })
+                .collect();
+            for (i, spec) in layout.logicals.iter().enumerate() {
+                let ebr_lba = ebr_lbas[i];
+                if spec.start_lba <= ebr_lba
+                    || ebr_lba < ext_start
+                    || spec.start_lba as u64 + spec.size_sectors as u64 > ext_end
+                {
+                    return Err(format!(
+                        "Logical partition at LBA {} does not fit in the extended partition",
+                        spec.start_lba
+                    )
+                    .into());
+                }
+                let mut entries = vec![mbr_entry(
+                    spec.partition_type,
+                    spec.start_lba - ebr_lba,
+                    spec.size_sectors,
+                    false,
+                    ebr_lba as u64,
+                )];
+                let next_ebr = match ebr_lbas.get(i + 1) {
+                    Some(next) => Some((*next, layout.logicals[i + 1].clone())),
+                    None if self.corrupted(Corruption::LoopingEbr) => {
+                        Some((ebr_lbas[0], layout.logicals[0].clone()))
+                    }
+                    None => None,
+                };
+                if let Some((next_lba, next)) = next_ebr {
+                    // From the next EBR to the end of its partition, which may be past 2^32.
+                    let next_size =
+                        next.start_lba as u64 + next.size_sectors as u64 - next_lba as u64;
+                    let next_size = u32::try_from(next_size).map_err(|_| {
+                        format!(
+                            "Logical partition at LBA {} is too large to be linked",
+                            next.start_lba
+                        )
+                    })?;
+                    entries.push(mbr_entry(
+                        0x05,
+                        next_lba - ext_start,
+                        next_size,
+                        false,
+                        ext_start as u64,
+                    ));
+                }
+                structures.push((
+                    ebr_lba as u64 * sector_size,
+                    table(&[], entries).to_bytes().to_vec(),
+                ));
+            }
+        }
+
+        // GPT, primary at LBA 1 and backup at the last LBA.
+        if let Some(disk_guid) = &layout.disk_guid {
+            let last_lba = layout.total_sectors - 1;
+            let mut gpt = GPT {
+                header: GPTHeader {
+                    signature: *b"EFI PART",
+                    revision: 0x0001_0000,
+                    header_size: 92,
+                    current_lba: 1,
+                    backup_lba: last_lba,
+                    first_usable_lba: first_usable,
+                    last_usable_lba: last_lba - array_sectors - 1,
+                    disk_guid: guid(disk_guid)?,
+                    partition_entry_lba: 2,
+                    num_partition_entries: GPT_ENTRIES,
+                    partition_entry_size: GPT_ENTRY_SIZE,
+                    ..Default::default()
+                },
+                partition_entries: Vec::new(),
+                ..Default::default()
+            };
+            if gpt_partitions.len() > GPT_ENTRIES as usize {
+                return Err(format!("More than {} GPT partitions", GPT_ENTRIES).into());
+            }
+            for (i, spec) in gpt_partitions.iter().enumerate() {
+                if spec.last_lba < spec.first_lba || spec.last_lba > last_lba {
+                    return Err(format!("Invalid GPT partition {}", i).into());
+                }
+                gpt.partition_entries.push(GPTPartitionEntry {
+                    id: Some(i as i64),
+                    partition_type_guid: guid(&spec.type_guid)?,
+                    partition_guid: guid(&spec.guid)?,
+                    starting_lba: spec.first_lba,
+                    ending_lba: spec.last_lba,
+                    attributes: spec.attributes,
+                    partition_name: spec.name.clone(),
+                    ..Default::default()
+                });
+            }
+            gpt.update_crc32();
+            let array = gpt.partition_array_to_bytes();
+
+            let mut backup = gpt.header.clone();
+            backup.current_lba = last_lba;
+            backup.backup_lba = 1;
+            backup.partition_entry_lba = last_lba - array_sectors;
+            backup.crc32 = backup.compute_crc32();
+
+            let mut primary = gpt.header;
+            if self.corrupted(Corruption::BadPartitionArrayCrc) {
+                primary.partition_array_crc32 ^= 0xFFFF_FFFF;
+                primary.crc32 = primary.compute_crc32();
+            }
+            if self.corrupted(Corruption::BadHeaderCrc) {
+                primary.crc32 ^= 0xFFFF_FFFF;
+            }
+
+            if !self.corrupted(Corruption::MissingPrimaryGpt) {
+                structures.push((sector_size, primary.to_bytes().to_vec()));
+            }
+            structures.push((2 * sector_size, array.clone()));
+            structures.push((backup.partition_entry_lba * sector_size, array));
+            if !self.corrupted(Corruption::MissingBackupGpt) {
+                structures.push((last_lba * sector_size, backup.to_bytes().to_vec()));
+            }
+        }
+
+        for (lba, bytes) in &self.blobs {
+            structures.push((lba * sector_size, bytes.clone()));
+        }
+        let image_size = layout.total_sectors * sector_size;
+        if let Some((offset, _)) = structures
+            .iter()
+            .find(|(offset, bytes)| offset + bytes.len() as u64 > image_size)
+        {
+            return Err(format!("Data written at offset {} ends past the disk", offset).into());
+        }
+        Ok(structures)
+    }
+
+    /// The whole image in memory.
+    pub fn build(&self) -> Result<Vec<u8>, Box<dyn Error>> {
+        let structures = self.structures()?;
+        let mut image = vec![0u8; (self.layout.total_sectors * self.layout.sector_size) as usize];
+        for (offset, bytes) in structures {
+            let offset = offset as usize;
+            image[offset..offset + bytes.len()].copy_from_slice(&bytes);
+        }
+        Ok(image)
+    }
+
+    /// Write the image to a file, sparse where the filesystem allows it.
+    pub fn write_to(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let structures = self.structures()?;
+        let mut file = File::create(path)?;
+        file.set_len(self.layout.total_sectors * self.layout.sector_size)?;
+        for (offset, bytes) in structures {
+            file.seek(SeekFrom::Start(offset))?;
+            file.write_all(&bytes)?;
+        }
+        file.sync_all()?;
+        Ok(())
+    }
+}
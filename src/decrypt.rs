@@ -0,0 +1,657 @@
+//! Decryption of BitLocker volumes.
+//!
+//! The full volume encryption key (FVEK) is either given as is, or unwrapped from the FVE
+//! metadata: a recovery password (or a clear key, when protection is suspended) decrypts a
+//! volume master key (VMK), which decrypts the FVEK. `BitLockerReader` then exposes the
+//! plaintext volume, the boot sector BitLocker moved away included, as a `SectorReader`.
+
+use crate::bitlocker::{
+    BitLockerInfo, FveEntry, ENTRY_TYPE_FVEK, PROTECTION_CLEAR_KEY, PROTECTION_RECOVERY_PASSWORD,
+    VALUE_TYPE_AES_CCM_ENCRYPTED_KEY, VALUE_TYPE_KEY, VALUE_TYPE_STRETCH_KEY,
+};
+use crate::encryption::Encryption;
+use crate::hash::to_hex;
+use crate::reader::SectorReader;
+use crate::Partitions;
+use aes::cipher::generic_array::GenericArray;
+use aes::cipher::{BlockDecrypt, BlockEncrypt, KeyInit};
+use aes::{Aes128, Aes256};
+use byteorder::{ByteOrder, LittleEndian};
+use ccm::aead::AeadInPlace;
+use ccm::consts::{U12, U16};
+use ccm::Ccm;
+use log::{info, warn};
+use sha2::digest::consts::U64;
+use sha2::{compress256, Digest, Sha256};
+use std::error::Error;
+use std::io::{self, Read, Seek, SeekFrom};
+
+type AesCcm = Ccm<Aes256, U16, U12>;
+
+/// SHA-256 rounds applied to a recovery password or user password.
+const STRETCH_ROUNDS: u64 = 0x100000;
+/// Every FVE metadata block is followed by reserved space up to this size.
+const FVE_METADATA_AREA_SIZE: u64 = 64 * 1024;
+const CCM_NONCE_SIZE: usize = 12;
+const CCM_TAG_SIZE: usize = 16;
+/// Key datum header: entry header, then encryption method and padding.
+const KEY_DATUM_HEADER_SIZE: usize = 12;
+
+/// What to unlock a BitLocker volume with.
+#[derive(Debug, Clone)]
+pub enum BitLockerKey {
+    /// The FVEK itself, as stored in the metadata (the tweak key follows for CBC with
+    /// diffuser and XTS).
+    Fvek(Vec<u8>),
+    /// The 48-digit recovery password, dashes optional.
+    RecoveryPassword(String),
+    /// The key left in the clear in the metadata while protection is suspended.
+    ClearKey,
+}
+
+/// An unwrapped full volume encryption key.
+#[derive(Debug, Clone)]
+pub struct Fvek {
+    pub method: u16, // Encryption method of the FVE metadata
+    pub key: Vec<u8>,
+}
+
+impl Fvek {
+    pub fn to_hex(&self) -> String {
+        to_hex(&self.key)
+    }
+}
+
+enum AesKey {
+    Aes128(Box<Aes128>),
+    Aes256(Box<Aes256>),
+}
+
+impl AesKey {
+    fn new(key: &[u8]) -> Result<AesKey, Box<dyn Error>> {
+        match key.len() {
+            16 => Ok(AesKey::Aes128(Box::new(Aes128::new_from_slice(key)?))),
+            32 => Ok(AesKey::Aes256(Box::new(Aes256::new_from_slice(key)?))),
+            len => Err(format!("Invalid AES key length: {} bytes", len).into()),
+        }
+    }
+
+    fn encrypt(&self, block: &mut [u8]) {
+        let block = GenericArray::from_mut_slice(block);
+        match self {
+            AesKey::Aes128(aes) => aes.encrypt_block(block),
+            AesKey::Aes256(aes) => aes.encrypt_block(block),
+        }
+    }
+
+    fn decrypt(&self, block: &mut [u8]) {
+        let block = GenericArray::from_mut_slice(block);
+        match self {
+            AesKey::Aes128(aes) => aes.decrypt_block(block),
+            AesKey::Aes256(aes) => aes.decrypt_block(block),
+        }
+    }
+
+    /// AES-ECB of a little-endian integer padded to one block.
+    fn encrypt_u64(&self, value: u64) -> [u8; 16] {
+        let mut block = [0u8; 16];
+        block[..8].copy_from_slice(&value.to_le_bytes());
+        self.encrypt(&mut block);
+        block
+    }
+}
+
+/// Per-sector encryption of a BitLocker volume. Sectors are identified by their byte offset
+/// in the volume.
+pub struct SectorCipher {
+    mode: Mode,
+    sector_size: u64,
+}
+
+enum Mode {
+    Cbc { fvek: AesKey },
+    CbcDiffuser { fvek: AesKey, tweak: AesKey },
+    Xts { data: AesKey, tweak: AesKey },
+}
+
+fn xor(block: &mut [u8], other: &[u8]) {
+    for (b, o) in block.iter_mut().zip(other) {
+        *b ^= o;
+    }
+}
+
+/// Multiply an XTS tweak by the primitive element of GF(2^128).
+fn next_tweak(tweak: &mut [u8; 16]) {
+    let carry = tweak[15] >> 7;
+    for i in (1..16).rev() {
+        tweak[i] = (tweak[i] << 1) | (tweak[i - 1] >> 7);
+    }
+    tweak[0] = (tweak[0] << 1) ^ (carry * 0x87);
+}
+
+const DIFFUSER_A_ROTATIONS: [u32; 4] = [9, 0, 13, 0];
+const DIFFUSER_B_ROTATIONS: [u32; 4] = [0, 10, 0, 25];
+
+fn to_words(sector: &[u8]) -> Vec<u32> {
+    sector.chunks_exact(4).map(LittleEndian::read_u32).collect()
+}
+
+fn from_words(words: &[u32], sector: &mut [u8]) {
+    for (chunk, word) in sector.chunks_exact_mut(4).zip(words) {
+        chunk.copy_from_slice(&word.to_le_bytes());
+    }
+}
+
+/// Elephant diffuser A (5 cycles) and B (3 cycles), in the decryption direction.
+fn diffuser_decrypt(sector: &mut [u8]) {
+    let mut d = to_words(sector);
+    let n = d.len();
+    for _ in 0..3 {
+        for i in 0..n {
+            d[i] = d[i].wrapping_add(
+                d[(i + 2) % n] ^ d[(i + 5) % n].rotate_left(DIFFUSER_B_ROTATIONS[i % 4]),
+            );
+        }
+    }
+    for _ in 0..5 {
+        for i in 0..n {
+            d[i] = d[i].wrapping_add(
+                d[(i + n - 2) % n] ^ d[(i + n - 5) % n].rotate_left(DIFFUSER_A_ROTATIONS[i % 4]),
+            );
+        }
+    }
+    from_words(&d, sector);
+}
+
+/// Inverse of `diffuser_decrypt`.
+fn diffuser_encrypt(sector: &mut [u8]) {
+    let mut d = to_words(sector);
+    let n = d.len();
+    for _ in 0..5 {
+        for i in (0..n).rev() {
+            d[i] = d[i].wrapping_sub(
+                d[(i + n - 2) % n] ^ d[(i + n - 5) % n].rotate_left(DIFFUSER_A_ROTATIONS[i % 4]),
+            );
+        }
+    }
+    for _ in 0..3 {
+        for i in (0..n).rev() {
+            d[i] = d[i].wrapping_sub(
+                d[(i + 2) % n] ^ d[(i + 5) % n].rotate_left(DIFFUSER_B_ROTATIONS[i % 4]),
+            );
+        }
+    }
+    from_words(&d, sector);
+}
+
+impl SectorCipher {
+    pub fn new(fvek: &Fvek, sector_size: u64) -> Result<SectorCipher, Box<dyn Error>> {
+        let key = &fvek.key;
+        let slice = |range: std::ops::Range<usize>| {
+            key.get(range)
+                .ok_or_else(|| format!("FVEK too short ({} bytes) for its method", key.len()))
+        };
+        let mode = match fvek.method {
+            0x8000 => Mode::CbcDiffuser {
+                fvek: AesKey::new(slice(0..16)?)?,
+                tweak: AesKey::new(slice(32..48)?)?,
+            },
+            0x8001 => Mode::CbcDiffuser {
+                fvek: AesKey::new(slice(0..32)?)?,
+                tweak: AesKey::new(slice(32..64)?)?,
+            },
+            0x8002 => Mode::Cbc {
+                fvek: AesKey::new(slice(0..16)?)?,
+            },
+            0x8003 => Mode::Cbc {
+                fvek: AesKey::new(slice(0..32)?)?,
+            },
+            0x8004 => Mode::Xts {
+                data: AesKey::new(slice(0..16)?)?,
+                tweak: AesKey::new(slice(16..32)?)?,
+            },
+            0x8005 => Mode::Xts {
+                data: AesKey::new(slice(0..32)?)?,
+                tweak: AesKey::new(slice(32..64)?)?,
+            },
+            method => return Err(format!("Unsupported encryption method 0x{:04x}", method).into()),
+        };
+        if sector_size == 0 || !sector_size.is_multiple_of(16) {
+            return Err(format!("Invalid sector size {}", sector_size).into());
+        }
+        Ok(SectorCipher { mode, sector_size })
+    }
+
+    /// The 32-byte key XORed over a sector before the diffuser.
+    fn sector_key(tweak: &AesKey, offset: u64) -> [u8; 32] {
+        let mut block = [0u8; 16];
+        block[..8].copy_from_slice(&offset.to_le_bytes());
+        let mut key = [0u8; 32];
+        key[..16].copy_from_slice(&block);
+        tweak.encrypt(&mut key[..16]);
+        block[15] = 0x80;
+        key[16..].copy_from_slice(&block);
+        tweak.encrypt(&mut key[16..]);
+        key
+    }
+
+    /// Decrypt in place the sector found at byte `offset` of the volume.
+    pub fn decrypt_sector(&self, offset: u64, sector: &mut [u8]) {
+        match &self.mode {
+            Mode::Cbc { fvek } => Self::cbc_decrypt(fvek, offset, sector),
+            Mode::CbcDiffuser { fvek, tweak } => {
+                Self::cbc_decrypt(fvek, offset, sector);
+                diffuser_decrypt(sector);
+                let key = Self::sector_key(tweak, offset);
+                for (i, byte) in sector.iter_mut().enumerate() {
+                    *byte ^= key[i % 32];
+                }
+            }
+            Mode::Xts { data, tweak } => {
+                let mut t = tweak.encrypt_u64(offset / self.sector_size);
+                for block in sector.chunks_exact_mut(16) {
+                    xor(block, &t);
+                    data.decrypt(block);
+                    xor(block, &t);
+                    next_tweak(&mut t);
+                }
+            }
+        }
+    }
+
+    /// Encrypt in place the sector to be stored at byte `offset` of the volume.
+    pub fn encrypt_sector(&self, offset: u64, sector: &mut [u8]) {
+        match &self.mode {
+            Mode::Cbc { fvek } => Self::cbc_encrypt(fvek, offset, sector),
+            Mode::CbcDiffuser { fvek, tweak } => {
+                let key = Self::sector_key(tweak, offset);
+                for (i, byte) in sector.iter_mut().enumerate() {
+                    *byte ^= key[i % 32];
+                }
+                diffuser_encrypt(sector);
+                Self::cbc_encrypt(fvek, offset, sector);
+            }
+            Mode::Xts { data, tweak } => {
+                let mut t = tweak.encrypt_u64(offset / self.sector_size);
+                for block in sector.chunks_exact_mut(16) {
+                    xor(block, &t);
+                    data.encrypt(block);
+                    xor(block, &t);
+                    next_tweak(&mut t);
+                }
+            }
+        }
+    }
+
+    /// AES-CBC with the IV derived from the sector offset.
+    fn cbc_decrypt(fvek: &AesKey, offset: u64, sector: &mut [u8]) {
+        let mut previous = fvek.encrypt_u64(offset);
+        for block in sector.chunks_exact_mut(16) {
+            let mut ciphertext = [0u8; 16];
+            ciphertext.copy_from_slice(block);
+            fvek.decrypt(block);
+            xor(block, &previous);
+            previous = ciphertext;
+        }
+    }
+
+    fn cbc_encrypt(fvek: &AesKey, offset: u64, sector: &mut [u8]) {
+        let mut previous = fvek.encrypt_u64(offset);
+        for block in sector.chunks_exact_mut(16) {
+            xor(block, &previous);
+            fvek.encrypt(block);
+            previous.copy_from_slice(block);
+        }
+    }
+}
+
+/// The 16-byte key encoded by a 48-digit recovery password: eight blocks of six digits,
+/// each a multiple of 11 encoding one little-endian 16-bit word.
+pub fn recovery_password_key(password: &str) -> Result<[u8; 16], Box<dyn Error>> {
+    let digits: String = password
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .collect();
+    if digits.len() != 48 || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return Err("A recovery password is made of 48 digits".into());
+    }
+    let mut key = [0u8; 16];
+    for i in 0..8 {
+        let block: u32 = digits[i * 6..i * 6 + 6].parse()?;
+        if !block.is_multiple_of(11) || block / 11 > 0xFFFF {
+            return Err(format!("Block {} of the recovery password is invalid", i + 1).into());
+        }
+        LittleEndian::write_u16(&mut key[i * 2..], (block / 11) as u16);
+    }
+    Ok(key)
+}
+
+/// SHA-256 initial hash value.
+const SHA256_IV: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+/// BitLocker's key stretching: `STRETCH_ROUNDS` of SHA-256 over the previous digest, the
+/// password hash, the salt and the round counter.
+///
+/// The 88-byte message always pads to the same two blocks, so they are built once and fed to
+/// the compression function directly.
+pub fn stretch_key(password_hash: &[u8; 32], salt: &[u8; 16]) -> [u8; 32] {
+    let mut blocks = [GenericArray::<u8, U64>::default(); 2];
+    blocks[0][32..64].copy_from_slice(password_hash);
+    blocks[1][..16].copy_from_slice(salt);
+    blocks[1][24] = 0x80;
+    blocks[1][56..].copy_from_slice(&(88u64 * 8).to_be_bytes());
+    let mut digest = [0u8; 32];
+    for round in 0..STRETCH_ROUNDS {
+        blocks[0][..32].copy_from_slice(&digest);
+        blocks[1][16..24].copy_from_slice(&round.to_le_bytes());
+        let mut state = SHA256_IV;
+        compress256(&mut state, &blocks);
+        for (chunk, word) in digest.chunks_exact_mut(4).zip(state) {
+            chunk.copy_from_slice(&word.to_be_bytes());
+        }
+    }
+    digest
+}
+
+/// Decrypt an AES-CCM encrypted key value (nonce, tag, ciphertext) and return the key it
+/// holds.
+fn decrypt_key_datum(key: &[u8], value: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    if value.len() < CCM_NONCE_SIZE + CCM_TAG_SIZE + KEY_DATUM_HEADER_SIZE {
+        return Err("Truncated AES-CCM encrypted key".into());
+    }
+    let (nonce, rest) = value.split_at(CCM_NONCE_SIZE);
+    let (tag, ciphertext) = rest.split_at(CCM_TAG_SIZE);
+    let mut datum = ciphertext.to_vec();
+    AesCcm::new_from_slice(key)?
+        .decrypt_in_place_detached(
+            GenericArray::from_slice(nonce),
+            &[],
+            &mut datum,
+            GenericArray::from_slice(tag),
+        )
+        .map_err(|_| "Wrong key: the AES-CCM authentication tag does not match")?;
+    let size = (LittleEndian::read_u16(&datum) as usize).min(datum.len());
+    if LittleEndian::read_u16(&datum[4..]) != VALUE_TYPE_KEY || size < KEY_DATUM_HEADER_SIZE {
+        return Err("The decrypted value is not a key".into());
+    }
+    Ok(datum[KEY_DATUM_HEADER_SIZE..size].to_vec())
+}
+
+fn property(entry: &FveEntry, value_type: u16) -> Option<&FveEntry> {
+    entry
+        .properties
+        .iter()
+        .find(|property| property.value_type == value_type)
+}
+
+/// Decrypt the VMK of one protector with the key protecting it.
+fn unlock_vmk(
+    vmk: &FveEntry,
+    key: &BitLockerKey,
+    password_hash: Option<&[u8; 32]>,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let encrypted =
+        property(vmk, VALUE_TYPE_AES_CCM_ENCRYPTED_KEY).ok_or("The VMK has no encrypted key")?;
+    let protecting_key = match key {
+        BitLockerKey::RecoveryPassword(_) => {
+            let stretch = property(vmk, VALUE_TYPE_STRETCH_KEY)
+                .filter(|stretch| stretch.data.len() >= 20)
+                .ok_or("The recovery password VMK has no salt")?;
+            let mut salt = [0u8; 16];
+            salt.copy_from_slice(&stretch.data[4..20]);
+            let password_hash = password_hash.ok_or("No recovery password hash")?;
+            stretch_key(password_hash, &salt).to_vec()
+        }
+        BitLockerKey::ClearKey => property(vmk, VALUE_TYPE_KEY)
+            .filter(|clear| clear.data.len() > 4)
+            .map(|clear| clear.data[4..].to_vec())
+            .ok_or("The clear key VMK has no key")?,
+        BitLockerKey::Fvek(_) => return Err("An FVEK does not protect a VMK".into()),
+    };
+    decrypt_key_datum(&protecting_key, &encrypted.data)
+}
+
+/// Get the FVEK of a BitLocker volume from one of its keys.
+pub fn unlock(info: &BitLockerInfo, key: &BitLockerKey) -> Result<Fvek, Box<dyn Error>> {
+    let metadata = info.metadata().ok_or("No readable FVE metadata block")?;
+    let method = metadata.encryption_method;
+    let (protection, password_hash) = match key {
+        BitLockerKey::Fvek(fvek) => {
+            return Ok(Fvek {
+                method,
+                key: fvek.clone(),
+            })
+        }
+        BitLockerKey::RecoveryPassword(password) => {
+            let hash: [u8; 32] = Sha256::digest(recovery_password_key(password)?).into();
+            (PROTECTION_RECOVERY_PASSWORD, Some(hash))
+        }
+        BitLockerKey::ClearKey => (PROTECTION_CLEAR_KEY, None),
+    };
+
+    let mut last_error: Box<dyn Error> = "No protector matches the key".into();
+    for (entry, vmk) in metadata
+        .entries
+        .iter()
+        .filter_map(|entry| entry.as_vmk().map(|vmk| (entry, vmk)))
+        .filter(|(_, vmk)| vmk.protection_type == protection)
+    {
+        match unlock_vmk(entry, key, password_hash.as_ref()) {
+            Ok(vmk_key) => {
+                info!("Unlocked the VMK {}.", vmk.key_id);
+                let fvek = metadata
+                    .entries
+                    .iter()
+                    .find(|entry| {
+                        entry.entry_type == ENTRY_TYPE_FVEK
+                            && entry.value_type == VALUE_TYPE_AES_CCM_ENCRYPTED_KEY
+                    })
+                    .ok_or("No encrypted FVEK in the metadata")?;
+                return Ok(Fvek {
+                    method,
+                    key: decrypt_key_datum(&vmk_key, &fvek.data)?,
+                });
+            }
+            Err(err) => last_error = err,
+        }
+    }
+    Err(last_error)
+}
+
+/// The plaintext of a BitLocker volume, read through another reader.
+///
+/// The first sectors are read from where BitLocker moved them, the FVE metadata areas read
+/// as zeros and data past the encrypted size (conversion in progress) is passed through.
+pub struct BitLockerReader<R> {
+    inner: R,
+    offset: u64, // Start of the volume in `inner`
+    size: u64,
+    position: u64,
+    cipher: SectorCipher,
+    sector_size: u64,
+    volume_header_offset: u64,
+    volume_header_size: u64,
+    encrypted_size: u64,
+    reserved: Vec<(u64, u64)>, // Ranges of the volume read as zeros
+}
+
+impl<R: SectorReader> BitLockerReader<R> {
+    /// The volume of `size` bytes at `offset` in `inner`, described by `info`.
+    pub fn new(
+        inner: R,
+        offset: u64,
+        size: u64,
+        info: &BitLockerInfo,
+        fvek: &Fvek,
+    ) -> Result<BitLockerReader<R>, Box<dyn Error>> {
+        let metadata = info.metadata().ok_or("No readable FVE metadata block")?;
+        if metadata.version < 2 {
+            return Err("Windows Vista BitLocker volumes are not supported".into());
+        }
+        let sector_size = inner.sector_size() as u64;
+        let volume_header_size = metadata.volume_header_sectors as u64 * sector_size;
+        let mut reserved: Vec<(u64, u64)> = info
+            .metadata_offsets
+            .iter()
+            .map(|&block| (block, FVE_METADATA_AREA_SIZE))
+            .collect();
+        reserved.push((metadata.volume_header_offset, volume_header_size));
+        Ok(BitLockerReader {
+            inner,
+            offset,
+            size,
+            position: 0,
+            cipher: SectorCipher::new(fvek, sector_size)?,
+            sector_size,
+            volume_header_offset: metadata.volume_header_offset,
+            volume_header_size,
+            encrypted_size: if metadata.encrypted_volume_size == 0 {
+                size
+            } else {
+                metadata.encrypted_volume_size
+            },
+            reserved,
+        })
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Read and decrypt the sectors stored in place from volume offset `start` on.
+    fn read_in_place(&mut self, start: u64, buf: &mut [u8]) -> io::Result<()> {
+        self.inner.seek(SeekFrom::Start(self.offset + start))?;
+        self.inner.read_exact(buf)?;
+        for (i, sector) in buf.chunks_exact_mut(self.sector_size as usize).enumerate() {
+            let offset = start + i as u64 * self.sector_size;
+            if self
+                .reserved
+                .iter()
+                .any(|&(first, len)| offset >= first && offset < first + len)
+            {
+                sector.fill(0);
+            } else if offset < self.encrypted_size {
+                self.cipher.decrypt_sector(offset, sector);
+            }
+        }
+        Ok(())
+    }
+
+    /// Read and decrypt the first sectors of the volume from where BitLocker moved them.
+    fn read_relocated(&mut self, start: u64, buf: &mut [u8]) -> io::Result<()> {
+        let physical = self.volume_header_offset + start;
+        self.inner.seek(SeekFrom::Start(self.offset + physical))?;
+        self.inner.read_exact(buf)?;
+        for (i, sector) in buf.chunks_exact_mut(self.sector_size as usize).enumerate() {
+            self.cipher
+                .decrypt_sector(physical + i as u64 * self.sector_size, sector);
+        }
+        Ok(())
+    }
+}
+
+impl<R: SectorReader> Read for BitLockerReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = (buf.len() as u64).min(self.size.saturating_sub(self.position));
+        if len == 0 {
+            return Ok(0);
+        }
+        let first = self.position / self.sector_size * self.sector_size;
+        let end = (self.position + len)
+            .div_ceil(self.sector_size)
+            .saturating_mul(self.sector_size)
+            .min(self.size / self.sector_size * self.sector_size);
+        if end <= first {
+            return Ok(0);
+        }
+        let mut sectors = vec![0u8; (end - first) as usize];
+        let header_end = self.volume_header_size.clamp(first, end);
+        let (relocated, in_place) = sectors.split_at_mut((header_end - first) as usize);
+        if !relocated.is_empty() {
+            self.read_relocated(first, relocated)?;
+        }
+        if !in_place.is_empty() {
+            self.read_in_place(header_end, in_place)?;
+        }
+        let skip = (self.position - first) as usize;
+        let len = (len as usize).min(sectors.len() - skip);
+        buf[..len].copy_from_slice(&sectors[skip..skip + len]);
+        self.position += len as u64;
+        Ok(len)
+    }
+}
+
+impl<R: SectorReader> Seek for BitLockerReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(n) => Some(n),
+            SeekFrom::End(n) => self.size.checked_add_signed(n),
+            SeekFrom::Current(n) => self.position.checked_add_signed(n),
+        };
+        match target {
+            Some(target) => {
+                self.position = target;
+                Ok(target)
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+}
+
+impl<R: SectorReader> SectorReader for BitLockerReader<R> {
+    fn sector_size(&self) -> u16 {
+        self.sector_size as u16
+    }
+
+    fn image_size(&mut self) -> io::Result<u64> {
+        Ok(self.size)
+    }
+}
+
+impl Partitions {
+    /// Try `key` on every BitLocker partition and record the FVEK of those it unlocks in
+    /// their `fvek` field, as hex. Returns how many were unlocked.
+    pub fn unlock_bitlocker(&mut self, key: &BitLockerKey) -> usize {
+        let mut unlocked = 0;
+        let mut try_unlock = |encryption: &Option<Encryption>, fvek: &mut Option<String>| {
+            if let Some(Encryption::BitLocker(info)) = encryption {
+                match unlock(info, key) {
+                    Ok(key) => {
+                        *fvek = Some(key.to_hex());
+                        unlocked += 1;
+                    }
+                    Err(err) => warn!(
+                        "Could not unlock the BitLocker volume {}: {}",
+                        info.volume_guid.as_deref().unwrap_or("?"),
+                        err
+                    ),
+                }
+            }
+        };
+        let mbr_entries = self
+            .mbr
+            .iter_mut()
+            .flat_map(|mbr| mbr.partition_table.iter_mut());
+        let ebr_entries = self
+            .ebr
+            .iter_mut()
+            .flatten()
+            .map(|record| &mut record.partition_table[0]);
+        for entry in mbr_entries.chain(ebr_entries) {
+            try_unlock(&entry.encryption, &mut entry.fvek);
+        }
+        for entry in self
+            .gpt
+            .iter_mut()
+            .flat_map(|gpt| &mut gpt.partition_entries)
+        {
+            try_unlock(&entry.encryption, &mut entry.fvek);
+        }
+        unlocked
+    }
+}
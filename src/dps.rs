@@ -0,0 +1,295 @@
+//! Discoverable Partitions Specification (DPS) classification of GPT partitions.
+//!
+//! The UAPI Group's DPS gives every Linux partition role (root, /usr, their dm-verity hash and
+//! signature partitions, /home, /srv, /var, /var/tmp, swap...) one type GUID per architecture,
+//! so that systemd mounts a disk without an fstab. The role and architecture of each type come
+//! from the partition type registry; the DPS also defines three attribute bits: 63 (no-auto,
+//! do not mount automatically), 60 (read-only) and 59 (grow the file system to the partition).
+
+use crate::extent::Scheme;
+use crate::types;
+use crate::Partitions;
+use prettytable::{Cell, Row, Table};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+pub const NO_AUTO: u64 = 1 << 63;
+pub const READ_ONLY: u64 = 1 << 60;
+pub const GROW_FS: u64 = 1 << 59;
+
+#[derive(
+    Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum DpsRole {
+    Root,
+    Usr,
+    RootVerity,
+    UsrVerity,
+    RootVeritySig,
+    UsrVeritySig,
+    Esp,
+    Xbootldr,
+    Swap,
+    Home,
+    Srv,
+    Var,
+    Tmp,
+    UserHome,
+}
+
+const ROLES: [(DpsRole, &str); 14] = [
+    (DpsRole::Root, "root"),
+    (DpsRole::Usr, "usr"),
+    (DpsRole::RootVerity, "root-verity"),
+    (DpsRole::UsrVerity, "usr-verity"),
+    (DpsRole::RootVeritySig, "root-verity-sig"),
+    (DpsRole::UsrVeritySig, "usr-verity-sig"),
+    (DpsRole::Esp, "esp"),
+    (DpsRole::Xbootldr, "xbootldr"),
+    (DpsRole::Swap, "swap"),
+    (DpsRole::Home, "home"),
+    (DpsRole::Srv, "srv"),
+    (DpsRole::Var, "var"),
+    (DpsRole::Tmp, "tmp"),
+    (DpsRole::UserHome, "user-home"),
+];
+
+impl DpsRole {
+    /// The role of a registry entry, e.g. "root-verity".
+    pub fn from_name(name: &str) -> Option<DpsRole> {
+        ROLES
+            .iter()
+            .find(|(_, n)| *n == name)
+            .map(|(role, _)| *role)
+    }
+
+    pub fn name(&self) -> &'static str {
+        ROLES.iter().find(|(role, _)| role == self).unwrap().1
+    }
+
+    /// Root, /usr and their verity partitions have one type per architecture.
+    pub fn is_per_architecture(&self) -> bool {
+        matches!(
+            self,
+            DpsRole::Root
+                | DpsRole::Usr
+                | DpsRole::RootVerity
+                | DpsRole::UsrVerity
+                | DpsRole::RootVeritySig
+                | DpsRole::UsrVeritySig
+        )
+    }
+}
+
+/// Architectures of the DPS, with their names in the specification.
+#[derive(
+    Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord,
+)]
+pub enum Architecture {
+    #[serde(rename = "alpha")]
+    Alpha,
+    #[serde(rename = "arc")]
+    Arc,
+    #[serde(rename = "arm")]
+    Arm,
+    #[serde(rename = "arm64")]
+    Arm64,
+    #[serde(rename = "ia64")]
+    Ia64,
+    #[serde(rename = "loongarch64")]
+    LoongArch64,
+    #[serde(rename = "mips")]
+    Mips,
+    #[serde(rename = "mips64")]
+    Mips64,
+    #[serde(rename = "mips-le")]
+    MipsLe,
+    #[serde(rename = "mips64-le")]
+    Mips64Le,
+    #[serde(rename = "parisc")]
+    Parisc,
+    #[serde(rename = "ppc")]
+    Ppc,
+    #[serde(rename = "ppc64")]
+    Ppc64,
+    #[serde(rename = "ppc64-le")]
+    Ppc64Le,
+    #[serde(rename = "riscv32")]
+    RiscV32,
+    #[serde(rename = "riscv64")]
+    RiscV64,
+    #[serde(rename = "s390")]
+    S390,
+    #[serde(rename = "s390x")]
+    S390x,
+    #[serde(rename = "tilegx")]
+    TileGx,
+    #[serde(rename = "x86")]
+    X86,
+    #[serde(rename = "x86-64")]
+    X86_64,
+}
+
+const ARCHITECTURES: [(Architecture, &str); 21] = [
+    (Architecture::Alpha, "alpha"),
+    (Architecture::Arc, "arc"),
+    (Architecture::Arm, "arm"),
+    (Architecture::Arm64, "arm64"),
+    (Architecture::Ia64, "ia64"),
+    (Architecture::LoongArch64, "loongarch64"),
+    (Architecture::Mips, "mips"),
+    (Architecture::Mips64, "mips64"),
+    (Architecture::MipsLe, "mips-le"),
+    (Architecture::Mips64Le, "mips64-le"),
+    (Architecture::Parisc, "parisc"),
+    (Architecture::Ppc, "ppc"),
+    (Architecture::Ppc64, "ppc64"),
+    (Architecture::Ppc64Le, "ppc64-le"),
+    (Architecture::RiscV32, "riscv32"),
+    (Architecture::RiscV64, "riscv64"),
+    (Architecture::S390, "s390"),
+    (Architecture::S390x, "s390x"),
+    (Architecture::TileGx, "tilegx"),
+    (Architecture::X86, "x86"),
+    (Architecture::X86_64, "x86-64"),
+];
+
+impl Architecture {
+    pub fn from_name(name: &str) -> Option<Architecture> {
+        ARCHITECTURES
+            .iter()
+            .find(|(_, n)| *n == name)
+            .map(|(arch, _)| *arch)
+    }
+
+    pub fn name(&self) -> &'static str {
+        ARCHITECTURES
+            .iter()
+            .find(|(arch, _)| arch == self)
+            .unwrap()
+            .1
+    }
+}
+
+/// The (role, architecture) pair of a DPS type.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DpsClass {
+    pub role: DpsRole,
+    pub arch: Option<Architecture>, // Only for the per-architecture roles
+}
+
+/// The DPS class of a GPT type GUID. The DPS types are the Linux types of the registry whose
+/// role is a DPS role, with an architecture exactly when the role is per-architecture, and
+/// the EFI System Partition.
+pub fn classify(type_guid: &str) -> Option<DpsClass> {
+    let entry = types::gpt_type(type_guid)?;
+    let role = DpsRole::from_name(entry.role.as_deref()?)?;
+    if role != DpsRole::Esp && entry.os.as_deref() != Some("Linux") {
+        return None;
+    }
+    let arch = match entry.arch.as_deref() {
+        Some(name) => Some(Architecture::from_name(name)?),
+        None => None,
+    };
+    if role.is_per_architecture() != arch.is_some() {
+        return None;
+    }
+    Some(DpsClass { role, arch })
+}
+
+/// The DPS attribute bits set in `attributes`.
+pub fn attribute_flags(attributes: u64) -> Vec<String> {
+    [
+        (GROW_FS, "Grow file system"),
+        (READ_ONLY, "Read-only"),
+        (NO_AUTO, "No auto-mount"),
+    ]
+    .iter()
+    .filter(|(bit, _)| attributes & bit != 0)
+    .map(|(_, flag)| flag.to_string())
+    .collect()
+}
+
+/// A GPT partition with a DPS type.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct DpsPartition {
+    pub partition_index: u64, // Index of the matching `PartitionExtent`
+    pub name: String,
+    pub role: DpsRole,
+    pub arch: Option<Architecture>,
+    pub no_auto: bool,
+    pub read_only: bool,
+    pub grow_fs: bool,
+}
+
+impl Partitions {
+    /// The GPT partitions with a DPS type, in table order.
+    pub fn dps_partitions(&self) -> Vec<DpsPartition> {
+        let gpt = match &self.gpt {
+            Some(gpt) => gpt,
+            None => return Vec::new(),
+        };
+        let extents = self
+            .extents()
+            .into_iter()
+            .filter(|extent| extent.scheme == Scheme::GPT);
+
+        let mut all = Vec::new();
+        for (entry, extent) in gpt.partition_entries.iter().zip(extents) {
+            let Some(class) = classify(&entry.partition_type_guid_string) else {
+                continue;
+            };
+            all.push(DpsPartition {
+                partition_index: extent.index,
+                name: entry.partition_name.trim_end_matches('\0').to_string(),
+                role: class.role,
+                arch: class.arch,
+                no_auto: entry.attributes & NO_AUTO != 0,
+                read_only: entry.attributes & READ_ONLY != 0,
+                grow_fs: entry.attributes & GROW_FS != 0,
+            });
+        }
+        all
+    }
+
+    /// The partition systemd mounts as root on `arch`: the first root partition of that
+    /// architecture not marked no-auto.
+    pub fn dps_root(&self, arch: Architecture) -> Option<DpsPartition> {
+        self.dps_partitions()
+            .into_iter()
+            .find(|p| p.role == DpsRole::Root && p.arch == Some(arch) && !p.no_auto)
+    }
+}
+
+pub fn print_info(all: &[DpsPartition]) -> String {
+    let mut table = Table::new();
+    table.add_row(Row::new(vec![
+        Cell::new("Partition"),
+        Cell::new("Role"),
+        Cell::new("Architecture"),
+        Cell::new("Flags"),
+    ]));
+    for partition in all {
+        let mut flags = Vec::new();
+        if partition.no_auto {
+            flags.push("no-auto");
+        }
+        if partition.read_only {
+            flags.push("read-only");
+        }
+        if partition.grow_fs {
+            flags.push("grow-fs");
+        }
+        table.add_row(Row::new(vec![
+            Cell::new(&format!(
+                "#{} {}",
+                partition.partition_index, partition.name
+            )),
+            Cell::new(partition.role.name()),
+            Cell::new(partition.arch.map(|arch| arch.name()).unwrap_or("")),
+            Cell::new(&flags.join(", ")),
+        ]));
+    }
+    format!("\nDiscoverable partitions:\n{}", table)
+}
@@ -1,26 +1,67 @@
 use crate::mbr::{MBRPartitionEntry, MBR};
-use exhume_body::Body;
+use crate::reader::SectorReader;
+use log::warn;
 use prettytable::{Cell, Row, Table};
-use std::io::{Read, Seek, SeekFrom};
+use std::collections::HashSet;
+use std::io::SeekFrom;
 
-pub fn parse_ebr(body: &mut Body, start_lba: u32, sector_size: usize) -> Vec<MBR> {
+/// Upper bound on the length of an EBR chain, far above anything a partitioning tool creates.
+const MAX_EBR_CHAIN: usize = 1024;
+
+/// Walk the EBR chain of the extended partition starting at `extended_start`, in chain order.
+/// The logical partition of each EBR is relative to that EBR, and the link to the next EBR is
+/// relative to the start of the extended partition; both are returned as absolute LBAs.
+/// The walk stops on an unreadable sector, a missing signature or a link back to a visited EBR.
+pub fn parse_ebr<R: SectorReader + ?Sized>(
+    body: &mut R,
+    extended_start: u32,
+    sector_size: usize,
+) -> Vec<MBR> {
     let mut ebr_found = Vec::new();
-    let ebr_absolute_lba = start_lba as usize * sector_size;
-    body.seek(SeekFrom::Start(ebr_absolute_lba as u64)).unwrap();
-    let mut ebr_data = vec![0u8; 512];
-    body.read_exact(&mut ebr_data).unwrap();
-    let mut ebr = MBR::from_bytes(&ebr_data);
-    let logical_partition = &mut ebr.partition_table[0];
-    if logical_partition.partition_type != 0x00 {
-        logical_partition.start_lba += start_lba;
-        logical_partition.first_byte_addr = logical_partition.start_lba as usize * sector_size;
-    }
-    let next_ebr_partition = &ebr.partition_table[1];
-    if next_ebr_partition.partition_type != 0x00 {
-        let next_ebr_start = next_ebr_partition.start_lba;
-        ebr_found.extend(parse_ebr(body, next_ebr_start, sector_size));
+    let mut visited = HashSet::new();
+    let mut next_lba = Some(extended_start);
+    while let Some(lba) = next_lba.take() {
+        if !visited.insert(lba) {
+            warn!("The EBR chain loops back to LBA {}, stopping there.", lba);
+            break;
+        }
+        if visited.len() > MAX_EBR_CHAIN {
+            warn!(
+                "The EBR chain is longer than {} records, stopping there.",
+                MAX_EBR_CHAIN
+            );
+            break;
+        }
+        let mut ebr_data = vec![0u8; 512];
+        let read = body
+            .seek(SeekFrom::Start(lba as u64 * sector_size as u64))
+            .and_then(|_| body.read_exact(&mut ebr_data));
+        if let Err(err) = read {
+            warn!("Could not read the EBR at LBA {}: {}", lba, err);
+            break;
+        }
+        let mut ebr = MBR::from_bytes_with_sector_size(&ebr_data, sector_size);
+        if ebr.boot_signature != 0xAA55 {
+            warn!("No EBR signature at LBA {}, stopping the chain there.", lba);
+            break;
+        }
+        ebr.table_lba = lba as u64;
+
+        let logical_partition = &mut ebr.partition_table[0];
+        if logical_partition.partition_type != 0x00 {
+            logical_partition.start_lba = logical_partition.start_lba.wrapping_add(lba);
+            logical_partition.first_byte_addr = logical_partition.start_lba as usize * sector_size;
+        }
+        let next_ebr_partition = &mut ebr.partition_table[1];
+        if next_ebr_partition.partition_type != 0x00 {
+            next_ebr_partition.start_lba =
+                next_ebr_partition.start_lba.wrapping_add(extended_start);
+            next_ebr_partition.first_byte_addr =
+                next_ebr_partition.start_lba as usize * sector_size;
+            next_lba = Some(next_ebr_partition.start_lba);
+        }
+        ebr_found.push(ebr);
     }
-    ebr_found.push(ebr.clone());
     ebr_found
 }
 
@@ -0,0 +1,200 @@
+//! Tell which partitions hold an encrypted volume, and which keys an examiner will need.
+//!
+//! BitLocker and LUKS are recognised by their headers. VeraCrypt and TrueCrypt volumes have
+//! none: their first sectors are indistinguishable from random data, so a partition whose
+//! start has near-maximal entropy and no known header is only flagged as possibly encrypted.
+
+use crate::bitlocker::{self, BitLockerInfo};
+use crate::entropy::shannon_entropy;
+use crate::extent::PartitionExtent;
+use crate::luks::{self, LuksHeader};
+use crate::reader::SectorReader;
+use crate::Partitions;
+use log::info;
+use prettytable::{Cell, Row, Table};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::io::SeekFrom;
+
+/// Bytes read at the start of a partition for the entropy check.
+const ENTROPY_SAMPLE_SIZE: u64 = 64 * 1024;
+/// Smaller samples cannot reach the threshold even when random.
+const MIN_ENTROPY_SAMPLE_SIZE: u64 = 16 * 1024;
+/// Bits per byte above which content is considered random.
+pub const ENTROPY_THRESHOLD: f64 = 7.95;
+/// Linux LUKS partition type, GPT and MBR.
+const LUKS_GPT_TYPE: &str = "ca7d7ccb-63ed-4c53-861c-1742536059cc";
+const LUKS_MBR_TYPE: u8 = 0xE8;
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+#[serde(tag = "kind")]
+pub enum Encryption {
+    BitLocker(BitLockerInfo),
+    Luks(LuksHeader),
+    /// No known header but random-looking content: VeraCrypt, TrueCrypt, plain dm-crypt...
+    PossiblyEncrypted {
+        entropy: f64,
+    },
+}
+
+impl Encryption {
+    pub fn description(&self) -> String {
+        match self {
+            Encryption::BitLocker(info) => {
+                let mut s = format!(
+                    "BitLocker{}, {}, protectors: {}",
+                    if info.to_go { " To Go" } else { "" },
+                    info.encryption_method
+                        .as_deref()
+                        .unwrap_or("unknown method"),
+                    if info.protectors.is_empty() {
+                        "unknown".to_string()
+                    } else {
+                        info.protectors.join(", ")
+                    }
+                );
+                if let Some(description) = &info.description {
+                    s.push_str(&format!("\nDescription: {}", description));
+                }
+                if let Some(created) = &info.creation_time {
+                    s.push_str(&format!("\nCreated: {}", created));
+                }
+                for key_id in info.recovery_key_ids() {
+                    s.push_str(&format!("\nRecovery key ID: {{{}}}", key_id.to_uppercase()));
+                }
+                s
+            }
+            Encryption::Luks(header) => {
+                let mut s = format!(
+                    "LUKS{} {}, UUID {}, {} active keyslot(s), data at offset 0x{:x}",
+                    header.version,
+                    header.cipher,
+                    header.uuid,
+                    header.keyslots.iter().filter(|slot| slot.active).count(),
+                    header.payload_offset
+                );
+                if let Some(luks2) = &header.luks2 {
+                    for token in &luks2.tokens {
+                        s.push_str(&format!("\nToken {}: {}", token.id, token.kind));
+                    }
+                    for issue in &luks2.issues {
+                        s.push_str(&format!("\nWarning: {}", issue));
+                    }
+                }
+                s
+            }
+            Encryption::PossiblyEncrypted { entropy } => format!(
+                "Possibly encrypted (VeraCrypt/TrueCrypt?), entropy {:.3} bits/byte",
+                entropy
+            ),
+        }
+    }
+}
+
+/// Look for an encrypted volume in the `size` bytes starting at `offset`. `luks_typed` tells
+/// that the partition type says LUKS, so a damaged LUKS2 primary header is worth looking past.
+pub fn detect<R: SectorReader + ?Sized>(
+    body: &mut R,
+    offset: u64,
+    size: u64,
+    luks_typed: bool,
+) -> Option<Encryption> {
+    if let Some(info) = bitlocker::parse(body, offset) {
+        return Some(Encryption::BitLocker(info));
+    }
+    if let Some(header) = luks::parse(body, offset, luks_typed) {
+        return Some(Encryption::Luks(header));
+    }
+    let sample_size = size.min(ENTROPY_SAMPLE_SIZE);
+    if sample_size < MIN_ENTROPY_SAMPLE_SIZE {
+        return None;
+    }
+    let mut sample = vec![0u8; sample_size as usize];
+    body.seek(SeekFrom::Start(offset)).ok()?;
+    body.read_exact(&mut sample).ok()?;
+    let entropy = shannon_entropy(&sample);
+    if entropy >= ENTROPY_THRESHOLD {
+        Some(Encryption::PossiblyEncrypted { entropy })
+    } else {
+        None
+    }
+}
+
+impl Partitions {
+    /// Check every partition of the tables for an encrypted volume and record what was found
+    /// in its `encryption` field.
+    pub fn detect_encryption<R: SectorReader + ?Sized>(&mut self, body: &mut R) {
+        let sector_size = self.sector_size;
+        let image_size = self.image_size;
+        let mut check = |start_lba: u64, size_sectors: u64, luks_typed: bool| {
+            let offset = start_lba * sector_size;
+            if start_lba == 0 || offset >= image_size {
+                return None;
+            }
+            let size = (size_sectors * sector_size).min(image_size - offset);
+            detect(body, offset, size, luks_typed)
+        };
+
+        let mbr_entries = self
+            .mbr
+            .iter_mut()
+            .flat_map(|mbr| mbr.partition_table.iter_mut());
+        let ebr_entries = self
+            .ebr
+            .iter_mut()
+            .flatten()
+            .map(|record| &mut record.partition_table[0]);
+        for entry in mbr_entries.chain(ebr_entries) {
+            if matches!(entry.partition_type, 0x00 | 0x05 | 0x0F | 0x85 | 0xEE) {
+                continue;
+            }
+            entry.encryption = check(
+                entry.start_lba as u64,
+                entry.size_sectors as u64,
+                entry.partition_type == LUKS_MBR_TYPE,
+            );
+            if let Some(encryption) = &entry.encryption {
+                info!(
+                    "MBR partition at LBA {}: {}",
+                    entry.start_lba,
+                    encryption.description()
+                );
+            }
+        }
+
+        if let Some(gpt) = &mut self.gpt {
+            for entry in &mut gpt.partition_entries {
+                let luks_typed = entry
+                    .partition_type_guid_string
+                    .eq_ignore_ascii_case(LUKS_GPT_TYPE);
+                entry.encryption = check(entry.starting_lba, entry.size_sectors, luks_typed);
+                if let Some(encryption) = &entry.encryption {
+                    info!(
+                        "GPT partition at LBA {}: {}",
+                        entry.starting_lba,
+                        encryption.description()
+                    );
+                }
+            }
+        }
+    }
+}
+
+pub fn print_info(extents: &[PartitionExtent]) -> String {
+    let mut table = Table::new();
+    table.add_row(Row::new(vec![
+        Cell::new("Partition"),
+        Cell::new("Start (LBA)"),
+        Cell::new("Encryption"),
+    ]));
+    for extent in extents {
+        if let Some(encryption) = &extent.encryption {
+            table.add_row(Row::new(vec![
+                Cell::new(&format!("#{} ({:?})", extent.index, extent.scheme)),
+                Cell::new(&format!("0x{:x}", extent.start_lba)),
+                Cell::new(&encryption.description()),
+            ]));
+        }
+    }
+    format!("\nEncrypted volumes:\n{}", table)
+}
@@ -0,0 +1,300 @@
+//! Byte entropy, to tell encrypted or compressed content from structured data.
+//!
+//! `compute_entropy` profiles every partition and unallocated gap block by block. Random
+//! content with no encryption header to explain it may be a hidden container or a wipe with
+//! random data; content that is nothing but zeros is a wipe.
+
+use crate::encryption::Encryption;
+use crate::layout::{Slot, SlotKind};
+use crate::reader::SectorReader;
+use crate::Partitions;
+use log::info;
+use prettytable::{Cell, Row, Table};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::io::SeekFrom;
+
+pub const DEFAULT_BLOCK_SIZE: u64 = 64 * 1024;
+/// Random blocks smaller than this do not reliably reach `HIGH_ENTROPY_THRESHOLD`.
+pub const MIN_BLOCK_SIZE: u64 = 4096;
+/// Bits per byte above which a block is considered random (4 KiB of random bytes average 7.955).
+pub const HIGH_ENTROPY_THRESHOLD: f64 = 7.9;
+/// Share of high-entropy blocks above which a whole slot is considered random.
+const HIGH_ENTROPY_SLOT_RATIO: f64 = 0.9;
+/// Shorter runs of high-entropy blocks are common (compressed files) and not reported.
+const MIN_REGION_SIZE: u64 = 1024 * 1024;
+/// Points of the downsampled profile of each slot.
+const PROFILE_POINTS: usize = 64;
+
+/// Shannon entropy of `data` in bits per byte: 0.0 for a constant buffer, close to 8.0 for
+/// uniformly random bytes.
+pub fn shannon_entropy(data: &[u8]) -> f64 {
+    if data.is_empty() {
+        return 0.0;
+    }
+    let mut counts = [0u64; 256];
+    for &byte in data {
+        counts[byte as usize] += 1;
+    }
+    let len = data.len() as f64;
+    counts
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = count as f64 / len;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntropyFinding {
+    Wiped,       // Every block is zero-filled
+    HighEntropy, // Random content with no encryption header: hidden container or random wipe
+}
+
+/// A run of consecutive high-entropy blocks.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq)]
+pub struct EntropyRegion {
+    pub first_byte_addr: u64,
+    pub size_bytes: u64,
+}
+
+/// Entropy profile of one slot of the disk layout.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct SlotEntropy {
+    pub slot: Slot,
+    pub first_byte_addr: u64,
+    pub size_bytes: u64,
+    pub block_size: u64,
+    pub blocks: u64,
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub zero_blocks: u64,
+    pub high_entropy_blocks: u64,
+    pub histogram: Vec<u64>, // Blocks per whole bit of entropy: [0, 1), [1, 2) ... [7, 8]
+    pub profile: Vec<f64>,   // Mean entropy of up to 64 equal runs of blocks, in disk order
+    pub high_entropy_regions: Vec<EntropyRegion>, // Runs of at least 1 MiB, unexplained only
+    pub finding: Option<EntropyFinding>,
+}
+
+/// Per-block entropy of `length` bytes starting at `offset`, with whether each block is all zeros.
+fn block_entropies<R: SectorReader + ?Sized>(
+    body: &mut R,
+    offset: u64,
+    length: u64,
+    block_size: u64,
+) -> Result<Vec<(f64, bool)>, Box<dyn Error>> {
+    let mut buf = vec![0u8; block_size as usize];
+    let mut blocks = Vec::with_capacity(length.div_ceil(block_size) as usize);
+    let mut remaining = length;
+    body.seek(SeekFrom::Start(offset))?;
+    while remaining > 0 {
+        let len = std::cmp::min(block_size, remaining) as usize;
+        body.read_exact(&mut buf[..len])?;
+        let zero = buf[..len].iter().all(|&b| b == 0);
+        blocks.push((shannon_entropy(&buf[..len]), zero));
+        remaining -= len as u64;
+    }
+    Ok(blocks)
+}
+
+fn round(value: f64) -> f64 {
+    (value * 1000.0).round() / 1000.0
+}
+
+/// Mean of `entropies` over at most `PROFILE_POINTS` equal runs.
+fn profile(entropies: &[f64]) -> Vec<f64> {
+    let points = entropies.len().min(PROFILE_POINTS);
+    (0..points)
+        .map(|i| {
+            let run = &entropies[i * entropies.len() / points..(i + 1) * entropies.len() / points];
+            round(run.iter().sum::<f64>() / run.len() as f64)
+        })
+        .collect()
+}
+
+/// Profile the `blocks` of a slot. `explained` is set when an encryption header accounts for
+/// random content.
+fn summarize(
+    slot: Slot,
+    first_byte_addr: u64,
+    size_bytes: u64,
+    block_size: u64,
+    blocks: &[(f64, bool)],
+    explained: bool,
+) -> SlotEntropy {
+    let entropies: Vec<f64> = blocks.iter().map(|(entropy, _)| *entropy).collect();
+    let mut high: Vec<bool> = entropies
+        .iter()
+        .map(|&entropy| entropy >= HIGH_ENTROPY_THRESHOLD)
+        .collect();
+    // A trailing partial block too small to look random follows the block before it.
+    let tail = size_bytes % block_size;
+    if tail > 0 && tail < MIN_BLOCK_SIZE && high.len() > 1 {
+        let last = high.len() - 1;
+        high[last] = high[last - 1];
+    }
+    let mut histogram = vec![0u64; 8];
+    for entropy in &entropies {
+        histogram[(*entropy as usize).min(7)] += 1;
+    }
+    let zero_blocks = blocks.iter().filter(|(_, zero)| *zero).count() as u64;
+    let high_entropy_blocks = high.iter().filter(|&&high| high).count() as u64;
+
+    let mut high_entropy_regions = Vec::new();
+    if !explained {
+        let mut i = 0;
+        while i < blocks.len() {
+            if !high[i] {
+                i += 1;
+                continue;
+            }
+            let start = i;
+            while i < blocks.len() && high[i] {
+                i += 1;
+            }
+            let region_start = start as u64 * block_size;
+            let region_size = (i as u64 * block_size).min(size_bytes) - region_start;
+            if region_size >= MIN_REGION_SIZE.min(size_bytes) {
+                high_entropy_regions.push(EntropyRegion {
+                    first_byte_addr: first_byte_addr + region_start,
+                    size_bytes: region_size,
+                });
+            }
+        }
+    }
+
+    let count = blocks.len() as u64;
+    let finding = if count > 0 && zero_blocks == count {
+        Some(EntropyFinding::Wiped)
+    } else if !explained
+        && count > 0
+        && high_entropy_blocks as f64 >= HIGH_ENTROPY_SLOT_RATIO * count as f64
+    {
+        Some(EntropyFinding::HighEntropy)
+    } else {
+        None
+    };
+    SlotEntropy {
+        slot,
+        first_byte_addr,
+        size_bytes,
+        block_size,
+        blocks: count,
+        min: if entropies.is_empty() {
+            0.0
+        } else {
+            round(entropies.iter().copied().fold(f64::INFINITY, f64::min))
+        },
+        max: round(entropies.iter().copied().fold(0.0, f64::max)),
+        mean: round(entropies.iter().sum::<f64>() / count.max(1) as f64),
+        zero_blocks,
+        high_entropy_blocks,
+        histogram,
+        profile: profile(&entropies),
+        high_entropy_regions,
+        finding,
+    }
+}
+
+impl Partitions {
+    /// Profile the entropy of every partition and unallocated gap, `block_size` bytes at a
+    /// time. Partition tables and extended containers are skipped. Random content is only
+    /// explained by the encryption headers of a discovery with `DiscoveryOptions::encryption`.
+    pub fn compute_entropy<R: SectorReader + ?Sized>(
+        &self,
+        body: &mut R,
+        block_size: u64,
+    ) -> Result<Vec<SlotEntropy>, Box<dyn Error>> {
+        if block_size < MIN_BLOCK_SIZE {
+            return Err(format!(
+                "The entropy block size must be at least {} bytes",
+                MIN_BLOCK_SIZE
+            )
+            .into());
+        }
+        let image_size = self.image_size;
+        let total_sectors = image_size / self.sector_size;
+        let extents = self.extents();
+        let mut results = Vec::new();
+        for slot in self.layout(total_sectors) {
+            if !matches!(slot.kind, SlotKind::Partition | SlotKind::Unallocated) {
+                continue;
+            }
+            let first_byte_addr = slot.start_lba.saturating_mul(self.sector_size);
+            let size_bytes = std::cmp::min(
+                slot.length.saturating_mul(self.sector_size),
+                image_size.saturating_sub(first_byte_addr),
+            );
+            let explained = slot
+                .partition_index
+                .and_then(|index| extents.iter().find(|extent| extent.index == index))
+                .is_some_and(|extent| {
+                    matches!(
+                        extent.encryption,
+                        Some(Encryption::BitLocker(_) | Encryption::Luks(_))
+                    )
+                });
+            info!(
+                "Profiling the entropy of {} (LBA {} to {})",
+                slot.description, slot.start_lba, slot.end_lba
+            );
+            let first_byte_addr = self.base_offset.saturating_add(first_byte_addr);
+            let blocks = block_entropies(body, first_byte_addr, size_bytes, block_size)?;
+            results.push(summarize(
+                slot,
+                first_byte_addr,
+                size_bytes,
+                block_size,
+                &blocks,
+                explained,
+            ));
+        }
+        Ok(results)
+    }
+}
+
+pub fn print_info(profiles: &[SlotEntropy]) -> String {
+    let mut table = Table::new();
+    table.add_row(Row::new(vec![
+        Cell::new("Slot"),
+        Cell::new("Start (LBA)"),
+        Cell::new("Description"),
+        Cell::new("Entropy (min/mean/max)"),
+        Cell::new("Zero / random blocks"),
+        Cell::new("Finding"),
+    ]));
+    for entry in profiles {
+        let mut finding = match entry.finding {
+            Some(EntropyFinding::Wiped) => "Zero-filled: wiped".to_string(),
+            Some(EntropyFinding::HighEntropy) => {
+                "Random with no encryption header: hidden container or random wipe?".to_string()
+            }
+            None => String::new(),
+        };
+        for region in &entry.high_entropy_regions {
+            finding.push_str(&format!(
+                "\nHigh entropy at 0x{:x} ({} bytes)",
+                region.first_byte_addr, region.size_bytes
+            ));
+        }
+        table.add_row(Row::new(vec![
+            Cell::new(&format!("{:?}", entry.slot.kind)),
+            Cell::new(&format!("0x{:x}", entry.slot.start_lba)),
+            Cell::new(&entry.slot.description),
+            Cell::new(&format!(
+                "{:.3} / {:.3} / {:.3}",
+                entry.min, entry.mean, entry.max
+            )),
+            Cell::new(&format!(
+                "{} / {} of {}",
+                entry.zero_blocks, entry.high_entropy_blocks, entry.blocks
+            )),
+            Cell::new(finding.trim_start()),
+        ]));
+    }
+    format!("\nEntropy:\n{}", table)
+}
@@ -0,0 +1,417 @@
+//! Inventory of the EFI System Partition (ESP).
+//!
+//! The firmware loads its boot applications from the FAT volume of the ESP, under `/EFI`.
+//! Every file there is listed; the `.efi` binaries are hashed and their PE headers read to tell
+//! the machine, the link timestamp and whether they carry an Authenticode signature. Known
+//! bootloaders are recognized by name. UEFI bootkits such as BlackLotus drop their loaders
+//! here, next to or in place of the legitimate ones.
+
+use crate::bitlocker::{filetime_to_string, FILETIME_UNIX_OFFSET};
+use crate::fat::{DirEntry, FatType, FatVolume};
+use crate::hash::{Hashes, MultiHasher};
+use crate::reader::SectorReader;
+use crate::Partitions;
+use byteorder::{ByteOrder, LittleEndian};
+use log::{info, warn};
+use prettytable::{Cell, Row, Table};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+pub const ESP_GPT_TYPE: &str = "c12a7328-f81f-11d2-ba4b-00a0c93ec93b";
+pub const ESP_MBR_TYPE: &str = "0xef";
+/// Directories deeper than this under `/EFI` are not walked.
+const MAX_DEPTH: usize = 8;
+/// Binaries larger than this are listed but neither hashed nor parsed.
+const MAX_BINARY_SIZE: u64 = 64 * 1024 * 1024;
+/// Index of the certificate table (Authenticode signature) in the PE data directories.
+const SECURITY_DIRECTORY: usize = 4;
+
+/// Headers of a PE image.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct PeInfo {
+    pub machine: String,
+    pub timestamp: Option<String>, // Link time; a hash rather than a time in reproducible builds
+    pub subsystem: String,
+    pub signed: bool, // Whether the certificate table holds an Authenticode signature
+    pub signature_size: u64, // Size of the certificate table in bytes
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct EspFile {
+    pub path: String,
+    pub size: u64,
+    pub modified: Option<String>, // FAT local time
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hashes: Option<Hashes>, // `.efi` binaries only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pe: Option<PeInfo>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bootloader: Option<String>, // Known bootloader the file name matches
+}
+
+/// Files of the FAT volume of one EFI System Partition.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct EspInventory {
+    pub partition_index: u64, // Index of the matching `PartitionExtent`
+    pub start_lba: u64,
+    pub fat_type: Option<FatType>,
+    pub label: Option<String>,
+    pub files: Vec<EspFile>, // Under `/EFI`
+    pub issues: Vec<String>,
+}
+
+pub fn machine_description(machine: u16) -> String {
+    match machine {
+        0x014C => "x86".to_string(),
+        0x8664 => "x64".to_string(),
+        0x01C2 => "ARM Thumb".to_string(),
+        0x01C4 => "ARMv7".to_string(),
+        0xAA64 => "ARM64".to_string(),
+        0x0EBC => "EFI Byte Code".to_string(),
+        0x5032 => "RISC-V 32".to_string(),
+        0x5064 => "RISC-V 64".to_string(),
+        0x6264 => "LoongArch 64".to_string(),
+        _ => format!("Unknown (0x{:04x})", machine),
+    }
+}
+
+pub fn subsystem_description(subsystem: u16) -> String {
+    match subsystem {
+        10 => "EFI application".to_string(),
+        11 => "EFI boot service driver".to_string(),
+        12 => "EFI runtime driver".to_string(),
+        13 => "EFI ROM".to_string(),
+        _ => format!("Unknown ({})", subsystem),
+    }
+}
+
+/// The known bootloader a file name belongs to. `path` is the full path of the file.
+pub fn known_bootloader(path: &str) -> Option<&'static str> {
+    let lower = path.to_lowercase();
+    let name = lower.rsplit('/').next()?;
+    let stem = name.strip_suffix(".efi")?;
+    let arch = ["x64", "ia32", "aa64", "arm", "riscv64", "loongarch64"];
+    let loader = |prefix: &str| {
+        stem.strip_prefix(prefix)
+            .is_some_and(|suffix| arch.contains(&suffix))
+    };
+    if stem == "bootmgfw" || stem == "bootmgr" {
+        Some("Windows Boot Manager")
+    } else if loader("shim") {
+        Some("shim")
+    } else if loader("grub") {
+        Some("GRUB")
+    } else if loader("mm") {
+        Some("MokManager")
+    } else if loader("fb") {
+        Some("shim fallback")
+    } else if loader("systemd-boot") {
+        Some("systemd-boot")
+    } else if loader("boot") && lower.starts_with("/efi/boot/") {
+        Some("Removable media loader")
+    } else {
+        None
+    }
+}
+
+/// Parse the DOS and PE headers of `image`.
+pub fn parse_pe(image: &[u8]) -> Result<PeInfo, String> {
+    if image.len() < 0x40 || &image[0..2] != b"MZ" {
+        return Err("No MZ header".to_string());
+    }
+    let pe = LittleEndian::read_u32(&image[0x3C..0x40]) as usize;
+    let coff = image.get(pe..pe + 24).ok_or("PE header past the end")?;
+    if &coff[0..4] != b"PE\0\0" {
+        return Err("No PE signature".to_string());
+    }
+    let machine = LittleEndian::read_u16(&coff[4..6]);
+    let timestamp = LittleEndian::read_u32(&coff[8..12]) as u64;
+    let optional_size = LittleEndian::read_u16(&coff[20..22]) as usize;
+    let optional = image
+        .get(pe + 24..pe + 24 + optional_size)
+        .ok_or("Optional header past the end")?;
+    if optional.len() < 2 {
+        return Err("No optional header".to_string());
+    }
+    let directories_at = match LittleEndian::read_u16(&optional[0..2]) {
+        0x10B => 96,  // PE32
+        0x20B => 112, // PE32+
+        magic => return Err(format!("Unknown optional header magic 0x{:x}", magic)),
+    };
+    if optional.len() < directories_at {
+        return Err("Truncated optional header".to_string());
+    }
+    let subsystem = LittleEndian::read_u16(&optional[68..70]);
+    let directories = LittleEndian::read_u32(&optional[directories_at - 4..directories_at]);
+
+    // The certificate table gives a file offset, not an RVA.
+    let security_at = directories_at + SECURITY_DIRECTORY * 8;
+    let (offset, size) = match optional.get(security_at..security_at + 8) {
+        Some(entry) if directories as usize > SECURITY_DIRECTORY => (
+            LittleEndian::read_u32(&entry[0..4]) as u64,
+            LittleEndian::read_u32(&entry[4..8]) as u64,
+        ),
+        _ => (0, 0),
+    };
+    let signed = size >= 8 && offset > 0 && offset + size <= image.len() as u64;
+
+    Ok(PeInfo {
+        machine: machine_description(machine),
+        timestamp: filetime_to_string((timestamp + FILETIME_UNIX_OFFSET) * 10_000_000),
+        subsystem: subsystem_description(subsystem),
+        signed,
+        signature_size: size,
+    })
+}
+
+/// List the files under `dir`, recursively, hashing and parsing the `.efi` binaries.
+fn walk<R: SectorReader + ?Sized>(
+    body: &mut R,
+    volume: &FatVolume,
+    dir: &DirEntry,
+    path: &str,
+    depth: usize,
+    inventory: &mut EspInventory,
+) {
+    let entries = match volume.read_dir(body, dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            inventory
+                .issues
+                .push(format!("Cannot read directory {}: {}", path, e));
+            return;
+        }
+    };
+    for entry in entries {
+        let path = format!("{}/{}", path, entry.name);
+        if entry.is_dir {
+            if depth >= MAX_DEPTH {
+                inventory.issues.push(format!(
+                    "{}: deeper than {} levels, not walked",
+                    path, MAX_DEPTH
+                ));
+            } else {
+                walk(body, volume, &entry, &path, depth + 1, inventory);
+            }
+            continue;
+        }
+
+        let mut file = EspFile {
+            path: path.clone(),
+            size: entry.size,
+            modified: entry.modified.clone(),
+            hashes: None,
+            pe: None,
+            bootloader: known_bootloader(&path).map(str::to_string),
+        };
+        if !entry.name.to_lowercase().ends_with(".efi") {
+            inventory.files.push(file);
+            continue;
+        }
+        if entry.size > MAX_BINARY_SIZE {
+            inventory.issues.push(format!(
+                "{}: {} bytes, too large to analyze",
+                path, entry.size
+            ));
+            inventory.files.push(file);
+            continue;
+        }
+        let image = match volume.read_file(body, &entry) {
+            Ok(image) => image,
+            Err(e) => {
+                inventory.issues.push(format!("{}: {}", path, e));
+                inventory.files.push(file);
+                continue;
+            }
+        };
+        if (image.len() as u64) < entry.size {
+            inventory.issues.push(format!(
+                "{}: cluster chain holds {} of {} bytes",
+                path,
+                image.len(),
+                entry.size
+            ));
+        }
+        let mut hasher = MultiHasher::new();
+        hasher.update(&image);
+        file.hashes = Some(hasher.finalize());
+        match parse_pe(&image) {
+            Ok(pe) => file.pe = Some(pe),
+            Err(e) => inventory
+                .issues
+                .push(format!("{}: not a PE image ({})", path, e)),
+        }
+        inventory.files.push(file);
+    }
+}
+
+/// Checks across the files of the inventory.
+fn check(inventory: &mut EspInventory) {
+    let mut issues = Vec::new();
+    for file in &inventory.files {
+        let (Some(bootloader), Some(pe)) = (&file.bootloader, &file.pe) else {
+            continue;
+        };
+        let signed_loader = matches!(
+            bootloader.as_str(),
+            "Windows Boot Manager" | "shim" | "MokManager" | "shim fallback"
+        );
+        if signed_loader && !pe.signed {
+            issues.push(format!(
+                "{}: {} without an Authenticode signature",
+                file.path, bootloader
+            ));
+        }
+        let under_microsoft = file.path.to_lowercase().starts_with("/efi/microsoft/");
+        if under_microsoft && bootloader != "Windows Boot Manager" {
+            issues.push(format!(
+                "{}: {} under /EFI/Microsoft, where BlackLotus drops its loader",
+                file.path, bootloader
+            ));
+        }
+    }
+    inventory.issues.extend(issues);
+}
+
+/// Open the FAT volume at `offset` and inventory its `/EFI` directory.
+pub fn inventory<R: SectorReader + ?Sized>(
+    body: &mut R,
+    offset: u64,
+    partition_index: u64,
+    start_lba: u64,
+) -> EspInventory {
+    let mut inventory = EspInventory {
+        partition_index,
+        start_lba,
+        fat_type: None,
+        label: None,
+        files: Vec::new(),
+        issues: Vec::new(),
+    };
+    let volume = match FatVolume::open(body, offset) {
+        Ok(volume) => volume,
+        Err(e) => {
+            inventory
+                .issues
+                .push(format!("No readable FAT file system: {}", e));
+            return inventory;
+        }
+    };
+    inventory.fat_type = Some(volume.fat_type);
+    inventory.label = volume.label.clone();
+
+    let root = match volume.root_dir(body) {
+        Ok(root) => root,
+        Err(e) => {
+            inventory
+                .issues
+                .push(format!("Cannot read the root directory: {}", e));
+            return inventory;
+        }
+    };
+    // BlackLotus also copies vulnerable Windows boot applications to /system32.
+    if root
+        .iter()
+        .any(|entry| entry.is_dir && entry.name.eq_ignore_ascii_case("system32"))
+    {
+        inventory.issues.push(
+            "/system32 directory at the root of the ESP, as created by BlackLotus".to_string(),
+        );
+    }
+    match root
+        .iter()
+        .find(|entry| entry.is_dir && entry.name.eq_ignore_ascii_case("EFI"))
+    {
+        Some(efi) => {
+            let path = format!("/{}", efi.name);
+            walk(body, &volume, efi, &path, 1, &mut inventory);
+        }
+        None => inventory.issues.push("No /EFI directory".to_string()),
+    }
+    check(&mut inventory);
+    inventory
+}
+
+impl Partitions {
+    /// Inventory the FAT volume of every EFI System Partition: GPT type
+    /// C12A7328-F81F-11D2-BA4B-00A0C93EC93B or MBR type 0xEF.
+    pub fn inventory_esp<R: SectorReader + ?Sized>(&self, body: &mut R) -> Vec<EspInventory> {
+        let mut inventories = Vec::new();
+        for extent in self.extents() {
+            let is_esp = extent.partition_type.eq_ignore_ascii_case(ESP_GPT_TYPE)
+                || extent.partition_type == ESP_MBR_TYPE;
+            // The body is read relative to the table, like the LBAs.
+            let offset = extent.first_byte_addr - self.base_offset;
+            if !is_esp || offset >= self.image_size {
+                continue;
+            }
+            let inventory = inventory(body, offset, extent.index, extent.start_lba);
+            info!(
+                "EFI System Partition #{}: {} files under /EFI",
+                extent.index,
+                inventory.files.len()
+            );
+            for issue in &inventory.issues {
+                warn!("EFI System Partition #{}: {}", extent.index, issue);
+            }
+            inventories.push(inventory);
+        }
+        inventories
+    }
+}
+
+pub fn print_info(inventories: &[EspInventory]) -> String {
+    let mut s = String::new();
+    for inventory in inventories {
+        let mut table = Table::new();
+        table.add_row(Row::new(vec![
+            Cell::new("Path"),
+            Cell::new("Size"),
+            Cell::new("Modified"),
+            Cell::new("Bootloader"),
+            Cell::new("PE"),
+            Cell::new("SHA-256"),
+        ]));
+        for file in &inventory.files {
+            let pe = file
+                .pe
+                .as_ref()
+                .map(|pe| {
+                    format!(
+                        "{}, {}\n{}",
+                        pe.machine,
+                        if pe.signed { "signed" } else { "unsigned" },
+                        pe.timestamp.as_deref().unwrap_or("")
+                    )
+                })
+                .unwrap_or_default();
+            table.add_row(Row::new(vec![
+                Cell::new(&file.path),
+                Cell::new(&file.size.to_string()),
+                Cell::new(file.modified.as_deref().unwrap_or("")),
+                Cell::new(file.bootloader.as_deref().unwrap_or("")),
+                Cell::new(&pe),
+                Cell::new(
+                    file.hashes
+                        .as_ref()
+                        .map(|hashes| hashes.sha256.as_str())
+                        .unwrap_or(""),
+                ),
+            ]));
+        }
+        s.push_str(&format!(
+            "\nEFI System Partition #{} (LBA {}, {}):\n{}",
+            inventory.partition_index,
+            inventory.start_lba,
+            inventory
+                .fat_type
+                .map(|fat_type| format!("{:?}", fat_type))
+                .unwrap_or_else(|| "no file system".to_string()),
+            table
+        ));
+        for issue in &inventory.issues {
+            s.push_str(&format!("Warning: {}\n", issue));
+        }
+    }
+    s
+}
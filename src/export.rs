@@ -0,0 +1,333 @@
+use crate::extent::{PartitionExtent, Scheme};
+use crate::hash::Hashes;
+use crate::layout::SlotKind;
+use crate::report::PartitionsReport;
+use crate::Partitions;
+use serde::Serialize;
+use std::error::Error;
+
+/// Turns discovered partitions into a machine-readable document.
+pub trait Exporter {
+    /// Name used to select the exporter (`--format-out`).
+    fn name(&self) -> &'static str;
+    fn export(&self, partitions: &Partitions, evidence: &str) -> Result<String, Box<dyn Error>>;
+}
+
+/// Every exporter shipped with the crate.
+pub fn exporters() -> Vec<Box<dyn Exporter>> {
+    vec![
+        Box::new(BodyfileExporter),
+        Box::new(CsvExporter),
+        Box::new(DfxmlExporter),
+        Box::new(JsonExporter),
+        Box::new(CompactJsonExporter),
+    ]
+}
+
+pub fn exporter_for(name: &str) -> Option<Box<dyn Exporter>> {
+    exporters().into_iter().find(|e| e.name() == name)
+}
+
+/// Digests of a partition, if `compute_hashes` was run.
+fn partition_hashes(partitions: &Partitions, extent: &PartitionExtent) -> Option<Hashes> {
+    partitions.hashes.as_ref().and_then(|hashes| {
+        hashes
+            .iter()
+            .find(|h| {
+                h.slot.kind == SlotKind::Partition && h.slot.partition_index == Some(extent.index)
+            })
+            .map(|h| h.hashes.clone())
+    })
+}
+
+/// The Sleuth Kit 3 body file read by `mactime`: one line per partition, and one per file
+/// of the EFI System Partition inventory with its modification time.
+pub struct BodyfileExporter;
+
+/// Seconds since the Unix epoch of a "YYYY-MM-DD HH:MM:SS" time, taken as UTC.
+fn epoch_seconds(datetime: &str) -> Option<i64> {
+    let field = |range: std::ops::Range<usize>| datetime.get(range)?.parse::<i64>().ok();
+    let (year, month, day) = (field(0..4)?, field(5..7)?, field(8..10)?);
+    let (hour, minute, second) = (field(11..13)?, field(14..16)?, field(17..19)?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    // Days from the civil date (proleptic Gregorian calendar, March-based years).
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146097 + day_of_era - 719468;
+    Some(days * 86400 + hour * 3600 + minute * 60 + second)
+}
+
+/// Body file fields are separated by `|`, and records by new lines.
+fn bodyfile_name(name: &str) -> String {
+    name.chars()
+        .map(|c| if c == '|' || c.is_control() { '_' } else { c })
+        .collect()
+}
+
+impl Exporter for BodyfileExporter {
+    fn name(&self) -> &'static str {
+        "bodyfile"
+    }
+
+    fn export(&self, partitions: &Partitions, evidence: &str) -> Result<String, Box<dyn Error>> {
+        // MD5|name|inode|mode_as_string|UID|GID|size|atime|mtime|ctime|crtime
+        let mut lines = String::new();
+        for extent in partitions.extents() {
+            let md5 = partition_hashes(partitions, &extent).map_or("0".to_string(), |h| h.md5);
+            let mut name = format!(
+                "{}:partition {} ({})",
+                evidence, extent.index, extent.description
+            );
+            if let Some(label) = extent.name.as_deref().filter(|label| !label.is_empty()) {
+                name.push_str(&format!(" \"{}\"", label));
+            }
+            lines.push_str(&format!(
+                "{}|{}|{}|-/----------|0|0|{}|0|0|0|0\n",
+                md5,
+                bodyfile_name(&name),
+                extent.index,
+                extent.size_bytes
+            ));
+        }
+        for esp in &partitions.esp {
+            for file in &esp.files {
+                let md5 = file.hashes.as_ref().map_or("0", |h| h.md5.as_str());
+                let name = format!(
+                    "{}:partition {}{}",
+                    evidence, esp.partition_index, file.path
+                );
+                let mtime = file
+                    .modified
+                    .as_deref()
+                    .and_then(epoch_seconds)
+                    .unwrap_or(0);
+                lines.push_str(&format!(
+                    "{}|{}|0|r/rrwxrwxrwx|0|0|{}|0|{}|0|0\n",
+                    md5,
+                    bodyfile_name(&name),
+                    file.size,
+                    mtime
+                ));
+            }
+        }
+        Ok(lines)
+    }
+}
+
+/// One row per partition, whatever the scheme.
+pub struct CsvExporter;
+
+#[derive(Serialize)]
+struct CsvRow<'a> {
+    evidence: &'a str,
+    index: u64,
+    scheme: Scheme,
+    entry_id: Option<u64>,
+    partition_type: &'a str,
+    description: &'a str,
+    guid: Option<&'a str>,
+    name: Option<&'a str>,
+    start_lba: u64,
+    end_lba: u64,
+    size_sectors: u64,
+    first_byte_addr: u64,
+    size_bytes: u64,
+    md5: Option<String>,
+    sha1: Option<String>,
+    sha256: Option<String>,
+}
+
+impl Exporter for CsvExporter {
+    fn name(&self) -> &'static str {
+        "csv"
+    }
+
+    fn export(&self, partitions: &Partitions, evidence: &str) -> Result<String, Box<dyn Error>> {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        for extent in partitions.extents() {
+            let hashes = partition_hashes(partitions, &extent);
+            writer.serialize(CsvRow {
+                evidence,
+                index: extent.index,
+                scheme: extent.scheme,
+                entry_id: extent.entry_id,
+                partition_type: &extent.partition_type,
+                description: &extent.description,
+                guid: extent.guid.as_deref(),
+                name: extent.name.as_deref(),
+                start_lba: extent.start_lba,
+                end_lba: extent.start_lba + extent.size_sectors.max(1) - 1,
+                size_sectors: extent.size_sectors,
+                first_byte_addr: extent.first_byte_addr,
+                size_bytes: extent.size_bytes,
+                md5: hashes.as_ref().map(|h| h.md5.clone()),
+                sha1: hashes.as_ref().map(|h| h.sha1.clone()),
+                sha256: hashes.map(|h| h.sha256),
+            })?;
+        }
+        Ok(String::from_utf8(writer.into_inner()?)?)
+    }
+}
+
+/// Digital Forensics XML: a `partitionsystem` per scheme with its `partition` elements,
+/// and one `volume` element per partition.
+pub struct DfxmlExporter;
+
+fn xml_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c if (c as u32) < 0x20 && !matches!(c, '\t' | '\n' | '\r') => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn dfxml_hashdigests(hashes: &Option<Hashes>, indent: &str) -> String {
+    match hashes {
+        Some(h) => format!(
+            "{i}<hashdigest type=\"md5\">{}</hashdigest>\n{i}<hashdigest type=\"sha1\">{}</hashdigest>\n{i}<hashdigest type=\"sha256\">{}</hashdigest>\n",
+            h.md5,
+            h.sha1,
+            h.sha256,
+            i = indent
+        ),
+        None => String::new(),
+    }
+}
+
+impl Exporter for DfxmlExporter {
+    fn name(&self) -> &'static str {
+        "dfxml"
+    }
+
+    fn export(&self, partitions: &Partitions, evidence: &str) -> Result<String, Box<dyn Error>> {
+        let extents = partitions.extents();
+        let mut x = String::new();
+        x.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        x.push_str("<dfxml version=\"1.2.0\" xmlns=\"http://www.forensicswiki.org/wiki/Category:Digital_Forensics_XML\" xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n");
+        x.push_str("  <metadata>\n    <dc:type>Partition Listing</dc:type>\n  </metadata>\n");
+        x.push_str(&format!(
+            "  <creator version=\"1.0\">\n    <program>{}</program>\n    <version>{}</version>\n  </creator>\n",
+            env!("CARGO_PKG_NAME"),
+            env!("CARGO_PKG_VERSION")
+        ));
+        x.push_str(&format!(
+            "  <source>\n    <image_filename>{}</image_filename>\n    <sectorsize>{}</sectorsize>\n    <image_size>{}</image_size>\n  </source>\n",
+            xml_escape(evidence),
+            partitions.sector_size,
+            partitions.image_size
+        ));
+
+        // Logical partitions belong to the same DOS partition system as the primary ones.
+        for (gpt, pstype) in [(false, "dos"), (true, "gpt")] {
+            let members: Vec<&PartitionExtent> = extents
+                .iter()
+                .filter(|e| (e.scheme == Scheme::GPT) == gpt)
+                .collect();
+            if members.is_empty() {
+                continue;
+            }
+            x.push_str(&format!(
+                "  <partitionsystem offset=\"{}\">\n",
+                partitions.base_offset
+            ));
+            x.push_str(&format!("    <pstype_str>{}</pstype_str>\n", pstype));
+            for extent in members {
+                x.push_str("    <partition>\n");
+                x.push_str(&format!(
+                    "      <partition_index>{}</partition_index>\n",
+                    extent.index
+                ));
+                x.push_str(&format!(
+                    "      <ptype_str>{}</ptype_str>\n",
+                    xml_escape(&extent.partition_type)
+                ));
+                x.push_str(&format!(
+                    "      <ftype_str>{}</ftype_str>\n",
+                    xml_escape(&extent.description)
+                ));
+                if let Some(guid) = &extent.guid {
+                    x.push_str(&format!("      <guid>{}</guid>\n", guid));
+                }
+                if let Some(name) = &extent.name {
+                    x.push_str(&format!(
+                        "      <partition_label>{}</partition_label>\n",
+                        xml_escape(name)
+                    ));
+                }
+                x.push_str(&format!(
+                    "      <byte_runs>\n        <byte_run img_offset=\"{}\" len=\"{}\"/>\n      </byte_runs>\n",
+                    extent.first_byte_addr, extent.size_bytes
+                ));
+                x.push_str(&dfxml_hashdigests(
+                    &partition_hashes(partitions, extent),
+                    "      ",
+                ));
+                x.push_str("    </partition>\n");
+            }
+            x.push_str("  </partitionsystem>\n");
+        }
+
+        for extent in &extents {
+            x.push_str(&format!(
+                "  <volume offset=\"{}\">\n",
+                extent.first_byte_addr
+            ));
+            x.push_str(&format!(
+                "    <partition_offset>{}</partition_offset>\n",
+                extent.first_byte_addr
+            ));
+            x.push_str(&format!(
+                "    <sector_size>{}</sector_size>\n",
+                partitions.sector_size
+            ));
+            x.push_str(&format!(
+                "    <byte_runs>\n      <byte_run img_offset=\"{}\" len=\"{}\"/>\n    </byte_runs>\n",
+                extent.first_byte_addr, extent.size_bytes
+            ));
+            x.push_str("  </volume>\n");
+        }
+        x.push_str("</dfxml>\n");
+        Ok(x)
+    }
+}
+
+/// The versioned report of `crate::report`, pretty-printed.
+pub struct JsonExporter;
+
+impl Exporter for JsonExporter {
+    fn name(&self) -> &'static str {
+        "json"
+    }
+
+    fn export(&self, partitions: &Partitions, evidence: &str) -> Result<String, Box<dyn Error>> {
+        let report = PartitionsReport::new(partitions, Some(evidence));
+        Ok(serde_json::to_string_pretty(&report)?)
+    }
+}
+
+/// The versioned report of `crate::report` on a single line.
+pub struct CompactJsonExporter;
+
+impl Exporter for CompactJsonExporter {
+    fn name(&self) -> &'static str {
+        "compact-json"
+    }
+
+    fn export(&self, partitions: &Partitions, evidence: &str) -> Result<String, Box<dyn Error>> {
+        let report = PartitionsReport::new(partitions, Some(evidence));
+        Ok(serde_json::to_string(&report)?)
+    }
+}
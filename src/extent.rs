@@ -0,0 +1,203 @@
+use crate::encryption::Encryption;
+use crate::Partitions;
+use log::warn;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scheme {
+    MBR,
+    EBR,
+    GPT,
+}
+
+/// Byte range of one used partition, whatever the scheme that describes it.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct PartitionExtent {
+    pub index: u64, // 1-based position across all schemes (MBR, then EBR, then GPT)
+    pub scheme: Scheme,
+    pub entry_id: Option<u64>, // Entry index inside its own table
+    pub partition_type: String,
+    pub description: String,
+    pub guid: Option<String>,
+    pub name: Option<String>,
+    pub start_lba: u64,
+    pub size_sectors: u64,
+    pub first_byte_addr: u64, // Address in the body, `base_offset` included
+    pub size_bytes: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encryption: Option<Encryption>,
+}
+
+impl PartitionExtent {
+    pub fn end_byte_addr(&self) -> u64 {
+        self.first_byte_addr.saturating_add(self.size_bytes)
+    }
+}
+
+/// How a partition is designated on the command line: its index, its GUID or its name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PartitionSelector {
+    Index(u64),
+    Guid(String),
+    Name(String),
+}
+
+impl PartitionSelector {
+    pub fn parse(selector: &str) -> PartitionSelector {
+        let selector = selector.trim();
+        if let Ok(index) = selector.parse::<u64>() {
+            return PartitionSelector::Index(index);
+        }
+        let stripped = selector.trim_start_matches('{').trim_end_matches('}');
+        let is_guid = stripped.len() == 36
+            && stripped.chars().enumerate().all(|(i, c)| match i {
+                8 | 13 | 18 | 23 => c == '-',
+                _ => c.is_ascii_hexdigit(),
+            });
+        if is_guid {
+            PartitionSelector::Guid(stripped.to_ascii_lowercase())
+        } else {
+            PartitionSelector::Name(selector.to_string())
+        }
+    }
+
+    pub fn matches(&self, extent: &PartitionExtent) -> bool {
+        match self {
+            PartitionSelector::Index(index) => extent.index == *index,
+            PartitionSelector::Guid(guid) => extent.guid.as_deref() == Some(guid.as_str()),
+            PartitionSelector::Name(name) => extent
+                .name
+                .as_deref()
+                .is_some_and(|n| n.eq_ignore_ascii_case(name)),
+        }
+    }
+}
+
+impl fmt::Display for PartitionSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PartitionSelector::Index(index) => write!(f, "partition #{}", index),
+            PartitionSelector::Guid(guid) => write!(f, "partition GUID {}", guid),
+            PartitionSelector::Name(name) => write!(f, "partition named {:?}", name),
+        }
+    }
+}
+
+impl Partitions {
+    /// Absolute byte address and size of `size_sectors` sectors from `start_lba`, or `None`
+    /// when the range ends past what 64 bits address.
+    fn byte_range(&self, start_lba: u64, size_sectors: u64) -> Option<(u64, u64)> {
+        let first_byte_addr = start_lba
+            .checked_mul(self.sector_size)?
+            .checked_add(self.base_offset)?;
+        let size_bytes = size_sectors.checked_mul(self.sector_size)?;
+        first_byte_addr.checked_add(size_bytes)?;
+        Some((first_byte_addr, size_bytes))
+    }
+
+    /// List every used partition with its absolute byte range.
+    /// Extended containers and the protective 0xEE entry are not partitions on their own
+    /// and are left out, and so are entries whose byte range overflows.
+    pub fn extents(&self) -> Vec<PartitionExtent> {
+        let mut extents = Vec::new();
+
+        if let Some(mbr) = &self.mbr {
+            for entry in &mbr.partition_table {
+                if matches!(entry.partition_type, 0x00 | 0x05 | 0x0F | 0x85 | 0xEE) {
+                    continue;
+                }
+                let Some((first_byte_addr, size_bytes)) =
+                    self.byte_range(entry.start_lba as u64, entry.size_sectors as u64)
+                else {
+                    warn!("Skipping an MBR entry whose byte range overflows.");
+                    continue;
+                };
+                extents.push(PartitionExtent {
+                    index: 0,
+                    scheme: Scheme::MBR,
+                    entry_id: entry.id.map(|id| id as u64),
+                    partition_type: format!("0x{:02x}", entry.partition_type),
+                    description: entry.description.clone(),
+                    guid: None,
+                    name: None,
+                    start_lba: entry.start_lba as u64,
+                    size_sectors: entry.size_sectors as u64,
+                    first_byte_addr,
+                    size_bytes,
+                    encryption: entry.encryption.clone(),
+                });
+            }
+        }
+
+        if let Some(ebr) = &self.ebr {
+            let mut logicals: Vec<PartitionExtent> = ebr
+                .iter()
+                .map(|record| &record.partition_table[0])
+                .filter(|entry| entry.partition_type != 0x00)
+                .filter_map(|entry| {
+                    let range = self.byte_range(entry.start_lba as u64, entry.size_sectors as u64);
+                    if range.is_none() {
+                        warn!("Skipping an EBR entry whose byte range overflows.");
+                    }
+                    Some((entry, range?))
+                })
+                .map(|(entry, (first_byte_addr, size_bytes))| PartitionExtent {
+                    index: 0,
+                    scheme: Scheme::EBR,
+                    entry_id: entry.id.map(|id| id as u64),
+                    partition_type: format!("0x{:02x}", entry.partition_type),
+                    description: entry.description.clone(),
+                    guid: None,
+                    name: None,
+                    start_lba: entry.start_lba as u64,
+                    size_sectors: entry.size_sectors as u64,
+                    first_byte_addr,
+                    size_bytes,
+                    encryption: entry.encryption.clone(),
+                })
+                .collect();
+            logicals.sort_by_key(|extent| extent.start_lba);
+            extents.extend(logicals);
+        }
+
+        if let Some(gpt) = &self.gpt {
+            for entry in &gpt.partition_entries {
+                let name = entry.partition_name.trim_end_matches('\0').to_string();
+                let Some((first_byte_addr, size_bytes)) =
+                    self.byte_range(entry.starting_lba, entry.size_sectors)
+                else {
+                    warn!(
+                        "Skipping GPT partition {}, whose byte range overflows.",
+                        entry.partition_guid_string
+                    );
+                    continue;
+                };
+                extents.push(PartitionExtent {
+                    index: 0,
+                    scheme: Scheme::GPT,
+                    entry_id: entry.id.map(|id| id as u64),
+                    partition_type: entry.partition_type_guid_string.clone(),
+                    description: entry.description.clone(),
+                    guid: Some(entry.partition_guid_string.clone()),
+                    name: if name.is_empty() { None } else { Some(name) },
+                    start_lba: entry.starting_lba,
+                    size_sectors: entry.size_sectors,
+                    first_byte_addr,
+                    size_bytes,
+                    encryption: entry.encryption.clone(),
+                });
+            }
+        }
+
+        for (i, extent) in extents.iter_mut().enumerate() {
+            extent.index = i as u64 + 1;
+        }
+        extents
+    }
+
+    pub fn select(&self, selector: &PartitionSelector) -> Option<PartitionExtent> {
+        self.extents().into_iter().find(|e| selector.matches(e))
+    }
+}
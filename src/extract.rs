@@ -0,0 +1,102 @@
+use crate::extent::PartitionExtent;
+use crate::hash::{Hashes, MultiHasher};
+use crate::reader::SectorReader;
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+#[derive(Debug, Default, Clone)]
+pub struct ExtractOptions {
+    pub resume: bool, // Continue an interrupted extraction instead of starting over
+    pub sparse: bool, // Do not write all-zero chunks, leave holes in the output instead
+    pub hash: bool,   // Compute MD5/SHA-1/SHA-256 of the extracted data
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct ExtractReport {
+    pub first_byte_addr: u64,
+    pub size_bytes: u64,
+    pub resumed_from: u64, // Bytes already present in the output when resuming
+    pub hashes: Option<Hashes>,
+}
+
+/// Stream the byte range of a partition out of the body into a raw (dd) image.
+/// `progress` is called with the number of bytes of the partition done so far.
+///
+/// When resuming with hashing enabled, the part already present in the output is
+/// read back from the output file to seed the digests.
+pub fn extract_partition<R: SectorReader + ?Sized>(
+    body: &mut R,
+    extent: &PartitionExtent,
+    output: &Path,
+    options: &ExtractOptions,
+    mut progress: impl FnMut(u64),
+) -> Result<ExtractReport, Box<dyn Error>> {
+    let size = extent.size_bytes;
+    let mut hasher = options.hash.then(MultiHasher::new);
+
+    let mut resumed_from = 0;
+    if options.resume && output.exists() {
+        resumed_from = output.metadata()?.len();
+        if resumed_from > size {
+            return Err(format!(
+                "Output is larger ({} bytes) than the partition ({} bytes), refusing to resume",
+                resumed_from, size
+            )
+            .into());
+        }
+        if let Some(hasher) = hasher.as_mut() {
+            let mut existing = File::open(output)?.take(resumed_from);
+            let mut buf = vec![0u8; CHUNK_SIZE];
+            loop {
+                let n = existing.read(&mut buf)?;
+                if n == 0 {
+                    break;
+                }
+                hasher.update(&buf[..n]);
+            }
+        }
+        info!("Resuming extraction at byte {} of {}", resumed_from, size);
+    }
+
+    let mut out = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(!options.resume)
+        .open(output)?;
+    out.seek(SeekFrom::Start(resumed_from))?;
+    body.seek(SeekFrom::Start(extent.first_byte_addr + resumed_from))?;
+
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    let mut done = resumed_from;
+    progress(done);
+    while done < size {
+        let len = std::cmp::min(CHUNK_SIZE as u64, size - done) as usize;
+        body.read_exact(&mut buf[..len])?;
+        if let Some(hasher) = hasher.as_mut() {
+            hasher.update(&buf[..len]);
+        }
+        if options.sparse && buf[..len].iter().all(|b| *b == 0) {
+            out.seek(SeekFrom::Current(len as i64))?;
+        } else {
+            out.write_all(&buf[..len])?;
+        }
+        done += len as u64;
+        progress(done);
+    }
+    // A sparse tail is only materialised by extending the file to its final length.
+    out.set_len(size)?;
+    out.flush()?;
+
+    Ok(ExtractReport {
+        first_byte_addr: extent.first_byte_addr,
+        size_bytes: size,
+        resumed_from,
+        hashes: hasher.map(MultiHasher::finalize),
+    })
+}
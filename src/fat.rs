@@ -0,0 +1,393 @@
+//! Minimal read-only FAT12/16/32 reader.
+//!
+//! Enough to walk the directories of a small volume such as the EFI System Partition and read
+//! its files: the boot sector (BIOS parameter block), the first FAT, and directory entries with
+//! their long file names. No write support, no repair, no recovery of deleted entries.
+
+use crate::reader::SectorReader;
+use byteorder::{ByteOrder, LittleEndian};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::error::Error;
+use std::io::SeekFrom;
+
+const DIR_ENTRY_SIZE: usize = 32;
+const ATTR_VOLUME_ID: u8 = 0x08;
+const ATTR_DIRECTORY: u8 = 0x10;
+const ATTR_LONG_NAME: u8 = 0x0F;
+const DELETED: u8 = 0xE5;
+/// A 32 MiB FAT32 maps 8 Mi clusters, 32 GiB with 4 KiB clusters: far more than any ESP.
+const MAX_FAT_SIZE: u64 = 32 * 1024 * 1024;
+/// Bytes of a directory read at most.
+const MAX_DIR_SIZE: u64 = 4 * 1024 * 1024;
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FatType {
+    Fat12,
+    Fat16,
+    Fat32,
+}
+
+/// One entry of a directory, its long file name resolved.
+#[derive(Debug, Clone)]
+pub struct DirEntry {
+    pub name: String,       // Long file name, or the 8.3 name when there is none
+    pub short_name: String, // 8.3 name, "NAME.EXT"
+    pub is_dir: bool,
+    pub size: u64,
+    pub first_cluster: u32,
+    pub modified: Option<String>, // Local time, "YYYY-MM-DD HH:MM:SS"
+}
+
+/// Geometry of a FAT volume and its first FAT. Byte offsets are relative to the body the
+/// volume was opened from.
+#[derive(Debug, Clone)]
+pub struct FatVolume {
+    pub fat_type: FatType,
+    pub label: Option<String>,
+    pub bytes_per_sector: u64,
+    pub cluster_size: u64,
+    pub cluster_count: u32,
+    offset: u64,
+    root_dir_offset: u64, // FAT12/16 fixed root directory
+    root_dir_size: u64,
+    root_cluster: u32, // FAT32 root directory
+    data_offset: u64,
+    fat: Vec<u8>,
+}
+
+/// DOS date and time as "YYYY-MM-DD HH:MM:SS", None when unset.
+pub fn dos_datetime(date: u16, time: u16) -> Option<String> {
+    if date == 0 {
+        return None;
+    }
+    Some(format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        1980 + (date >> 9),
+        (date >> 5) & 0x0F,
+        date & 0x1F,
+        time >> 11,
+        (time >> 5) & 0x3F,
+        (time & 0x1F) * 2
+    ))
+}
+
+/// Checksum of the 8.3 name that every long file name entry repeats.
+fn short_name_checksum(name: &[u8]) -> u8 {
+    name.iter().fold(0u8, |sum, &c| {
+        (sum >> 1).wrapping_add(sum << 7).wrapping_add(c)
+    })
+}
+
+fn short_name(entry: &[u8]) -> (String, String) {
+    let mut base = entry[0..8].to_vec();
+    if base[0] == 0x05 {
+        base[0] = DELETED; // 0xE5 as first character, escaped
+    }
+    let text = |bytes: &[u8], lower: bool| {
+        let s: String = bytes.iter().map(|&b| b as char).collect();
+        let s = s.trim_end().to_string();
+        if lower {
+            s.to_lowercase()
+        } else {
+            s
+        }
+    };
+    // Windows NT stores all-lowercase base names and extensions as flags.
+    let (base, ext) = (
+        text(&base, entry[12] & 0x08 != 0),
+        text(&entry[8..11], entry[12] & 0x10 != 0),
+    );
+    let raw = if ext.is_empty() {
+        base.to_uppercase()
+    } else {
+        format!("{}.{}", base, ext).to_uppercase()
+    };
+    let display = if ext.is_empty() {
+        base
+    } else {
+        format!("{}.{}", base, ext)
+    };
+    (raw, display)
+}
+
+/// Parse the entries of a directory. Long file names whose checksum does not match the 8.3
+/// entry that follows them are dropped, as a FAT driver would.
+pub fn parse_dir(bytes: &[u8]) -> Vec<DirEntry> {
+    let mut entries = Vec::new();
+    let mut lfn: Vec<(u8, Vec<u16>)> = Vec::new();
+    let mut lfn_checksum = 0u8;
+    for entry in bytes.chunks_exact(DIR_ENTRY_SIZE) {
+        match entry[0] {
+            0x00 => break,
+            DELETED => {
+                lfn.clear();
+                continue;
+            }
+            _ => {}
+        }
+        let attributes = entry[11];
+        if attributes & 0x3F == ATTR_LONG_NAME {
+            if entry[0] & 0x40 != 0 {
+                lfn.clear();
+            }
+            lfn_checksum = entry[13];
+            let units = [1..11, 14..26, 28..32]
+                .into_iter()
+                .flat_map(|range| entry[range].chunks_exact(2).map(LittleEndian::read_u16))
+                .collect();
+            lfn.push((entry[0] & 0x1F, units));
+            continue;
+        }
+        if attributes & ATTR_VOLUME_ID != 0 {
+            lfn.clear();
+            continue;
+        }
+
+        let (raw, display) = short_name(entry);
+        let mut name = display;
+        if !lfn.is_empty() && lfn_checksum == short_name_checksum(&entry[0..11]) {
+            lfn.sort_by_key(|(order, _)| *order);
+            let units: Vec<u16> = lfn
+                .iter()
+                .flat_map(|(_, units)| units.iter().copied())
+                .take_while(|&unit| unit != 0x0000)
+                .collect();
+            name = String::from_utf16_lossy(&units);
+        }
+        lfn.clear();
+        if name == "." || name == ".." {
+            continue;
+        }
+        let hi = LittleEndian::read_u16(&entry[20..22]) as u32;
+        let lo = LittleEndian::read_u16(&entry[26..28]) as u32;
+        entries.push(DirEntry {
+            name,
+            short_name: raw,
+            is_dir: attributes & ATTR_DIRECTORY != 0,
+            size: LittleEndian::read_u32(&entry[28..32]) as u64,
+            first_cluster: hi << 16 | lo,
+            modified: dos_datetime(
+                LittleEndian::read_u16(&entry[24..26]),
+                LittleEndian::read_u16(&entry[22..24]),
+            ),
+        });
+    }
+    entries
+}
+
+fn label(bytes: &[u8]) -> Option<String> {
+    let label: String = bytes.iter().map(|&b| b as char).collect();
+    let label = label.trim_end();
+    if label.is_empty() || label == "NO NAME" {
+        None
+    } else {
+        Some(label.to_string())
+    }
+}
+
+impl FatVolume {
+    /// Read the boot sector and the first FAT of the volume at `offset`.
+    pub fn open<R: SectorReader + ?Sized>(
+        body: &mut R,
+        offset: u64,
+    ) -> Result<FatVolume, Box<dyn Error>> {
+        let mut boot = [0u8; 512];
+        body.seek(SeekFrom::Start(offset))?;
+        body.read_exact(&mut boot)?;
+        if !matches!(boot[0], 0xEB | 0xE9) || boot[510..512] != [0x55, 0xAA] {
+            return Err("No FAT boot sector".into());
+        }
+        let bytes_per_sector = LittleEndian::read_u16(&boot[11..13]) as u64;
+        let sectors_per_cluster = boot[13] as u64;
+        let reserved = LittleEndian::read_u16(&boot[14..16]) as u64;
+        let fats = boot[16] as u64;
+        let root_entries = LittleEndian::read_u16(&boot[17..19]) as u64;
+        let total = match LittleEndian::read_u16(&boot[19..21]) as u64 {
+            0 => LittleEndian::read_u32(&boot[32..36]) as u64,
+            total => total,
+        };
+        let fat_sectors = match LittleEndian::read_u16(&boot[22..24]) as u64 {
+            0 => LittleEndian::read_u32(&boot[36..40]) as u64,
+            sectors => sectors,
+        };
+        if !bytes_per_sector.is_power_of_two()
+            || !(512..=4096).contains(&bytes_per_sector)
+            || !sectors_per_cluster.is_power_of_two()
+            || reserved == 0
+            || fats == 0
+            || fat_sectors == 0
+        {
+            return Err("Invalid BIOS parameter block".into());
+        }
+
+        let root_dir_sectors = (root_entries * DIR_ENTRY_SIZE as u64).div_ceil(bytes_per_sector);
+        let meta_sectors = reserved + fats * fat_sectors + root_dir_sectors;
+        if total <= meta_sectors {
+            return Err("FAT volume without a data area".into());
+        }
+        let cluster_count = (total - meta_sectors) / sectors_per_cluster;
+        let fat_type = if cluster_count < 4085 {
+            FatType::Fat12
+        } else if cluster_count < 65525 {
+            FatType::Fat16
+        } else {
+            FatType::Fat32
+        };
+        let (label_at, signature_at) = match fat_type {
+            FatType::Fat32 => (71, 66),
+            _ => (43, 38),
+        };
+        let label = if boot[signature_at] == 0x29 {
+            label(&boot[label_at..label_at + 11])
+        } else {
+            None
+        };
+
+        let fat_size = fat_sectors * bytes_per_sector;
+        if fat_size > MAX_FAT_SIZE {
+            return Err(format!("FAT of {} bytes is too large", fat_size).into());
+        }
+        let mut fat = vec![0u8; fat_size as usize];
+        body.seek(SeekFrom::Start(offset + reserved * bytes_per_sector))?;
+        body.read_exact(&mut fat)?;
+
+        let root_dir_offset = offset + (reserved + fats * fat_sectors) * bytes_per_sector;
+        Ok(FatVolume {
+            fat_type,
+            label,
+            bytes_per_sector,
+            cluster_size: sectors_per_cluster * bytes_per_sector,
+            cluster_count: cluster_count as u32,
+            offset,
+            root_dir_offset,
+            root_dir_size: root_dir_sectors * bytes_per_sector,
+            root_cluster: LittleEndian::read_u32(&boot[44..48]),
+            data_offset: root_dir_offset + root_dir_sectors * bytes_per_sector,
+            fat,
+        })
+    }
+
+    /// Byte offset of the volume in the body.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// The cluster following `cluster` in its chain, None at the end of the chain.
+    fn next_cluster(&self, cluster: u32) -> Option<u32> {
+        let fat = &self.fat;
+        let next = match self.fat_type {
+            FatType::Fat12 => {
+                let at = cluster as usize * 3 / 2;
+                let pair = LittleEndian::read_u16(fat.get(at..at + 2)?);
+                let value = if cluster & 1 == 1 {
+                    pair >> 4
+                } else {
+                    pair & 0x0FFF
+                };
+                if value >= 0xFF8 {
+                    return None;
+                }
+                value as u32
+            }
+            FatType::Fat16 => {
+                let at = cluster as usize * 2;
+                let value = LittleEndian::read_u16(fat.get(at..at + 2)?);
+                if value >= 0xFFF8 {
+                    return None;
+                }
+                value as u32
+            }
+            FatType::Fat32 => {
+                let at = cluster as usize * 4;
+                let value = LittleEndian::read_u32(fat.get(at..at + 4)?) & 0x0FFF_FFFF;
+                if value >= 0x0FFF_FFF8 {
+                    return None;
+                }
+                value
+            }
+        };
+        Some(next).filter(|&next| self.is_data_cluster(next))
+    }
+
+    fn is_data_cluster(&self, cluster: u32) -> bool {
+        cluster >= 2 && cluster - 2 < self.cluster_count
+    }
+
+    /// The clusters of the chain starting at `first`, cut short when it comes back to a
+    /// cluster already visited. Lazy, so that callers stop reading where they need.
+    pub fn chain(&self, first: u32) -> impl Iterator<Item = u32> + '_ {
+        let mut visited = HashSet::new();
+        let mut cluster = Some(first).filter(|&c| self.is_data_cluster(c));
+        std::iter::from_fn(move || {
+            let current = cluster.filter(|&c| visited.insert(c))?;
+            cluster = self.next_cluster(current);
+            Some(current)
+        })
+    }
+
+    /// Read up to `length` bytes of the chain starting at `first`.
+    fn read_chain<R: SectorReader + ?Sized>(
+        &self,
+        body: &mut R,
+        first: u32,
+        length: u64,
+    ) -> Result<Vec<u8>, Box<dyn Error>> {
+        let mut bytes = Vec::new();
+        let mut cluster = vec![0u8; self.cluster_size as usize];
+        for number in self.chain(first) {
+            if bytes.len() as u64 >= length {
+                break;
+            }
+            let at = self.data_offset + (number as u64 - 2) * self.cluster_size;
+            body.seek(SeekFrom::Start(at))?;
+            body.read_exact(&mut cluster)?;
+            let take = (length - bytes.len() as u64).min(self.cluster_size) as usize;
+            bytes.extend_from_slice(&cluster[..take]);
+        }
+        Ok(bytes)
+    }
+
+    /// Entries of the root directory.
+    pub fn root_dir<R: SectorReader + ?Sized>(
+        &self,
+        body: &mut R,
+    ) -> Result<Vec<DirEntry>, Box<dyn Error>> {
+        let bytes = match self.fat_type {
+            FatType::Fat32 => self.read_chain(body, self.root_cluster, MAX_DIR_SIZE)?,
+            _ => {
+                let mut bytes = vec![0u8; self.root_dir_size as usize];
+                body.seek(SeekFrom::Start(self.root_dir_offset))?;
+                body.read_exact(&mut bytes)?;
+                bytes
+            }
+        };
+        Ok(parse_dir(&bytes))
+    }
+
+    /// Entries of the subdirectory `dir`.
+    pub fn read_dir<R: SectorReader + ?Sized>(
+        &self,
+        body: &mut R,
+        dir: &DirEntry,
+    ) -> Result<Vec<DirEntry>, Box<dyn Error>> {
+        if !dir.is_dir {
+            return Err(format!("{} is not a directory", dir.name).into());
+        }
+        Ok(parse_dir(&self.read_chain(
+            body,
+            dir.first_cluster,
+            MAX_DIR_SIZE,
+        )?))
+    }
+
+    /// Content of the file `file`, as far as its cluster chain goes.
+    pub fn read_file<R: SectorReader + ?Sized>(
+        &self,
+        body: &mut R,
+        file: &DirEntry,
+    ) -> Result<Vec<u8>, Box<dyn Error>> {
+        self.read_chain(body, file.first_cluster, file.size)
+    }
+}
@@ -0,0 +1,447 @@
+//! GRUB 2 (i386-pc) boot.img and core.img.
+//!
+//! boot.img, in the MBR, loads the first sector of core.img from the LBA patched at 0x5C.
+//! That sector (diskboot.img) holds the block list of the rest of core.img: the LZMA
+//! decompressor followed by the compressed kernel, whose modules, embedded configuration and
+//! prefix are appended after it. core.img is embedded in the MBR gap, or in the BIOS boot
+//! partition of GPT disks. A core.img that was modified or moved is a known persistence
+//! technique, hence the checks of the block list against the embedding area.
+
+use crate::hash::to_hex;
+use crate::reader::SectorReader;
+use crate::Partitions;
+use byteorder::{ByteOrder, LittleEndian};
+use log::{info, warn};
+use lzma_rs::decompress::{Options, UnpackedSize};
+use prettytable::{Cell, Row, Table};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::{Cursor, SeekFrom};
+
+/// Short jump over the BIOS parameter block that starts boot.img.
+const BOOT_JUMP: [u8; 2] = [0xEB, 0x63];
+const BOOT_KERNEL_SECTOR: usize = 0x5C;
+const BOOT_DRIVE: usize = 0x64;
+/// First (highest) block list entry of diskboot.img; entries are 12 bytes and grow downwards.
+const BLOCKLIST_START: usize = 0x1F4;
+const BLOCKLIST_ENTRY_SIZE: usize = 12;
+const MAX_BLOCKS: usize = 16;
+/// Offsets in the decompressor header (lzma_decompress.img), second sector of core.img.
+const COMPRESSED_SIZE: usize = 0x08;
+const UNCOMPRESSED_SIZE: usize = 0x0C;
+const REED_SOLOMON_REDUNDANCY: usize = 0x10;
+/// The BIOS loads core.img into the 640 KiB of conventional memory; a block list
+/// claiming several MiB is not GRUB's.
+const MAX_CORE_SIZE: u64 = 4 * 1024 * 1024;
+/// Decompressed kernels with their modules stay within a few MiB; this also caps the
+/// memory of the LZMA decoder.
+const MAX_KERNEL_SIZE: u64 = 16 * 1024 * 1024;
+/// Magic of the module area appended to the kernel ("mimg").
+const MODULE_INFO_MAGIC: u32 = 0x676D_696D;
+const MODULE_INFO_SIZE: usize = 12;
+const MODULE_HEADER_SIZE: usize = 8;
+const BIOS_BOOT_TYPE: &str = "21686148-6449-6e6f-744e-656564454649";
+
+/// One run of sectors of core.img, as listed in diskboot.img.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq)]
+pub struct GrubBlock {
+    pub start_lba: u64,
+    pub sectors: u64,
+    pub segment: u64, // Real-mode segment the sectors are loaded to
+}
+
+/// An object appended to the kernel: module, memdisk, embedded config, prefix, key...
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct GrubObject {
+    pub kind: String,
+    pub size: u64,
+    pub name: Option<String>, // Module name, prefix or configuration
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct GrubInfo {
+    pub kernel_sector: u64, // LBA of core.img, as patched in boot.img
+    pub boot_drive: u64,    // 0xFF: the drive the BIOS booted from
+    pub location: String,   // Embedding area core.img was found in
+    pub blocklist: Vec<GrubBlock>,
+    pub core_size: u64, // Bytes of core.img covered by the block list, diskboot.img included
+    pub sha256: Option<String>, // Of those bytes, to compare with a known-good core.img
+    pub compressed_size: Option<u64>,
+    pub uncompressed_size: Option<u64>,
+    pub reed_solomon_redundancy: Option<u64>,
+    pub modules: Vec<String>,
+    pub prefix: Option<String>,
+    pub config: Option<String>, // Embedded configuration script
+    pub objects: Vec<GrubObject>,
+    pub issues: Vec<String>,
+}
+
+/// Whether `code` (the MBR boot code area) is GRUB 2 boot.img.
+pub fn is_boot_img(code: &[u8]) -> bool {
+    code.len() > BOOT_DRIVE
+        && code[0..2] == BOOT_JUMP
+        && code.windows(5).any(|window| window == b"GRUB ")
+}
+
+/// The block list of diskboot.img, if `sector` is one.
+pub fn parse_blocklist(sector: &[u8]) -> Option<Vec<GrubBlock>> {
+    if sector.len() < 512 || !sector.windows(7).any(|window| window == b"Loading") {
+        return None;
+    }
+    let mut blocks = Vec::new();
+    for i in 0..MAX_BLOCKS {
+        let entry = &sector[BLOCKLIST_START - i * BLOCKLIST_ENTRY_SIZE..];
+        let sectors = LittleEndian::read_u16(&entry[8..]) as u64;
+        if sectors == 0 {
+            break;
+        }
+        blocks.push(GrubBlock {
+            start_lba: LittleEndian::read_u64(entry),
+            sectors,
+            segment: LittleEndian::read_u16(&entry[10..]) as u64,
+        });
+    }
+    if blocks.is_empty() {
+        None
+    } else {
+        Some(blocks)
+    }
+}
+
+/// Decompress the raw LZMA stream of the kernel (no header; lc=3, lp=0, pb=2).
+fn decompress(compressed: &[u8], size: u64) -> Option<Vec<u8>> {
+    let mut stream = vec![0x5D];
+    // A dictionary as large as the output is always large enough.
+    stream.extend((size.max(4096) as u32).to_le_bytes());
+    stream.extend_from_slice(compressed);
+    let options = Options {
+        unpacked_size: UnpackedSize::UseProvided(Some(size)),
+        memlimit: Some(MAX_KERNEL_SIZE as usize),
+        allow_incomplete: false,
+    };
+    let mut kernel = Vec::new();
+    lzma_rs::lzma_decompress_with_options(&mut Cursor::new(stream), &mut kernel, &options).ok()?;
+    Some(kernel)
+}
+
+/// The objects of the module area appended to `kernel`.
+fn module_area(kernel: &[u8]) -> Option<&[u8]> {
+    (0..kernel.len().saturating_sub(MODULE_INFO_SIZE)).find_map(|pos| {
+        let info = &kernel[pos..];
+        let size = LittleEndian::read_u32(&info[8..]) as usize;
+        let valid = LittleEndian::read_u32(info) == MODULE_INFO_MAGIC
+            && LittleEndian::read_u32(&info[4..]) as usize == MODULE_INFO_SIZE
+            && size >= MODULE_INFO_SIZE
+            && size <= info.len();
+        valid.then(|| &info[MODULE_INFO_SIZE..size])
+    })
+}
+
+/// Name of a module: the `.modname` section of its ELF32 image.
+fn module_name(elf: &[u8]) -> Option<String> {
+    if elf.len() < 52 || &elf[0..4] != b"\x7fELF" || elf[4] != 1 {
+        return None;
+    }
+    let shoff = LittleEndian::read_u32(&elf[0x20..]) as usize;
+    let shentsize = LittleEndian::read_u16(&elf[0x2E..]) as usize;
+    let shnum = LittleEndian::read_u16(&elf[0x30..]) as usize;
+    let shstrndx = LittleEndian::read_u16(&elf[0x32..]) as usize;
+    if shentsize < 40 {
+        return None;
+    }
+    let section = |i: usize| -> Option<(usize, &[u8])> {
+        let header = elf.get(shoff + i * shentsize..shoff + (i + 1) * shentsize)?;
+        let offset = LittleEndian::read_u32(&header[16..]) as usize;
+        let size = LittleEndian::read_u32(&header[20..]) as usize;
+        Some((
+            LittleEndian::read_u32(header) as usize,
+            elf.get(offset..offset.checked_add(size)?)?,
+        ))
+    };
+    let (_, names) = section(shstrndx)?;
+    (0..shnum).find_map(|i| {
+        let (name, data) = section(i)?;
+        (names.get(name..)?.starts_with(b".modname\0")).then(|| c_string(data))
+    })
+}
+
+fn c_string(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).to_string()
+}
+
+fn parse_objects(area: &[u8]) -> Vec<GrubObject> {
+    let mut objects = Vec::new();
+    let mut pos = 0;
+    while pos + MODULE_HEADER_SIZE <= area.len() {
+        let kind = LittleEndian::read_u32(&area[pos..]);
+        let size = LittleEndian::read_u32(&area[pos + 4..]) as usize;
+        if size < MODULE_HEADER_SIZE || pos + size > area.len() {
+            break;
+        }
+        let data = &area[pos + MODULE_HEADER_SIZE..pos + size];
+        let (kind, name) = match kind {
+            0 => ("module", module_name(data)),
+            1 => ("memdisk", None),
+            2 => ("config", Some(c_string(data))),
+            3 => ("prefix", Some(c_string(data))),
+            4 => ("pubkey", None),
+            5 => ("dtb", None),
+            6 => ("disable_shim_lock", None),
+            _ => ("unknown", None),
+        };
+        objects.push(GrubObject {
+            kind: kind.to_string(),
+            size: data.len() as u64,
+            name,
+        });
+        pos += size;
+    }
+    objects
+}
+
+fn read_sectors<R: SectorReader + ?Sized>(
+    body: &mut R,
+    lba: u64,
+    sectors: u64,
+    sector_size: u64,
+) -> Option<Vec<u8>> {
+    let mut bytes = vec![0u8; (sectors * sector_size) as usize];
+    body.seek(SeekFrom::Start(lba.checked_mul(sector_size)?))
+        .ok()?;
+    body.read_exact(&mut bytes).ok()?;
+    Some(bytes)
+}
+
+impl Partitions {
+    /// Where core.img may be embedded: the MBR gap of MBR disks, and the BIOS boot partitions
+    /// of GPT disks, as (description, first LBA, last LBA).
+    fn embedding_areas(&self) -> Vec<(String, u64, u64)> {
+        let mut areas = Vec::new();
+        if let Some(gpt) = &self.gpt {
+            for entry in &gpt.partition_entries {
+                if entry.partition_type_guid_string == BIOS_BOOT_TYPE {
+                    areas.push((
+                        format!("BIOS boot partition #{}", entry.id.unwrap_or_default()),
+                        entry.starting_lba,
+                        entry.ending_lba,
+                    ));
+                }
+            }
+        } else if let Some(mbr) = &self.mbr {
+            let first_partition = mbr
+                .partition_table
+                .iter()
+                .filter(|entry| entry.partition_type != 0x00 && entry.start_lba > 0)
+                .map(|entry| entry.start_lba as u64)
+                .min();
+            if let Some(first_partition) = first_partition.filter(|&lba| lba > 1) {
+                areas.push(("MBR gap".to_string(), 1, first_partition - 1));
+            }
+        }
+        areas
+    }
+
+    /// Locate and parse core.img when the MBR boot code is GRUB 2 boot.img.
+    pub fn analyze_grub<R: SectorReader + ?Sized>(&self, body: &mut R) -> Option<GrubInfo> {
+        let code = &self.mbr.as_ref()?.bootloader;
+        if !is_boot_img(code) {
+            return None;
+        }
+        let sector_size = self.sector_size;
+        let kernel_sector = LittleEndian::read_u64(&code[BOOT_KERNEL_SECTOR..]);
+        info!("GRUB boot.img found, core.img at LBA {}.", kernel_sector);
+        let mut issues = Vec::new();
+
+        let areas = self.embedding_areas();
+        let area = areas
+            .iter()
+            .find(|(_, first, last)| (*first..=*last).contains(&kernel_sector));
+        let location = match area {
+            Some((description, _, _)) => description.clone(),
+            None => {
+                issues.push(format!(
+                    "boot.img loads core.img from LBA {}, outside of any embedding area",
+                    kernel_sector
+                ));
+                "Outside the embedding area".to_string()
+            }
+        };
+
+        let mut info = GrubInfo {
+            kernel_sector,
+            boot_drive: code[BOOT_DRIVE] as u64,
+            location,
+            blocklist: Vec::new(),
+            core_size: 0,
+            sha256: None,
+            compressed_size: None,
+            uncompressed_size: None,
+            reed_solomon_redundancy: None,
+            modules: Vec::new(),
+            prefix: None,
+            config: None,
+            objects: Vec::new(),
+            issues: Vec::new(),
+        };
+        let diskboot = read_sectors(body, kernel_sector, 1, sector_size);
+        let blocklist = diskboot.as_deref().and_then(parse_blocklist);
+        let (diskboot, blocklist) = match (diskboot, blocklist) {
+            (Some(diskboot), Some(blocklist)) => (diskboot, blocklist),
+            _ => {
+                issues.push(format!(
+                    "No diskboot.img block list at LBA {}, where boot.img points",
+                    kernel_sector
+                ));
+                info.issues = issues;
+                return Some(info);
+            }
+        };
+        if blocklist[0].start_lba != kernel_sector + 1 {
+            issues.push(format!(
+                "The block list starts at LBA {}, not right after diskboot.img (LBA {})",
+                blocklist[0].start_lba,
+                kernel_sector + 1
+            ));
+        }
+        if let Some((description, first, last)) = area {
+            for block in &blocklist {
+                if block.start_lba < *first
+                    || block.start_lba.saturating_add(block.sectors - 1) > *last
+                {
+                    issues.push(format!(
+                        "Block list entry at LBA {} ({} sectors) is outside the {}",
+                        block.start_lba, block.sectors, description
+                    ));
+                }
+            }
+        }
+
+        let mut core = diskboot;
+        for block in &blocklist {
+            if core.len() as u64 + block.sectors * sector_size > MAX_CORE_SIZE {
+                issues.push("The block list covers more than 4 MiB, not reading it".to_string());
+                break;
+            }
+            match read_sectors(body, block.start_lba, block.sectors, sector_size) {
+                Some(bytes) => core.extend(bytes),
+                None => {
+                    issues.push(format!("Could not read LBA {}", block.start_lba));
+                    break;
+                }
+            }
+        }
+        info.blocklist = blocklist;
+        info.core_size = core.len() as u64;
+        info.sha256 = Some(to_hex(&Sha256::digest(&core)));
+        self.parse_kernel(&core, &mut info, &mut issues);
+
+        for issue in &issues {
+            warn!("GRUB core.img: {}", issue);
+        }
+        info.issues = issues;
+        Some(info)
+    }
+
+    /// Decompress the kernel of `core` and list what is appended to it.
+    fn parse_kernel(&self, core: &[u8], info: &mut GrubInfo, issues: &mut Vec<String>) {
+        let header = match core.get(512..512 + 0x20) {
+            Some(header) => header,
+            None => return,
+        };
+        let compressed_size = LittleEndian::read_u32(&header[COMPRESSED_SIZE..]) as u64;
+        let uncompressed_size = LittleEndian::read_u32(&header[UNCOMPRESSED_SIZE..]) as u64;
+        let redundancy = LittleEndian::read_u32(&header[REED_SOLOMON_REDUNDANCY..]) as u64;
+        info.compressed_size = Some(compressed_size);
+        info.uncompressed_size = Some(uncompressed_size);
+        info.reed_solomon_redundancy = Some(redundancy);
+
+        // The compressed kernel ends core.img, before the Reed-Solomon redundancy; without
+        // redundancy, core.img may be padded up to a whole sector.
+        let end = (core.len() as u64).checked_sub(redundancy);
+        let start = end.and_then(|end| end.checked_sub(compressed_size));
+        let (start, end) = match start.zip(end) {
+            Some((start, end))
+                if start >= 512
+                    && compressed_size > 0
+                    && uncompressed_size > 0
+                    && uncompressed_size <= MAX_KERNEL_SIZE =>
+            {
+                (start as usize, end as usize)
+            }
+            _ => {
+                issues.push(format!(
+                    "Compressed kernel of {} bytes does not fit in core.img ({} bytes)",
+                    compressed_size,
+                    core.len()
+                ));
+                return;
+            }
+        };
+        let padding = if redundancy == 0 { 511 } else { 0 };
+        let kernel = (0..=padding.min(start - 512))
+            .map(|shift| start - shift)
+            .filter(|&start| core.get(start) == Some(&0x00)) // Range coder streams start with a zero byte
+            .find_map(|start| {
+                let kernel = decompress(
+                    core.get(start..start + compressed_size as usize)?,
+                    uncompressed_size,
+                )?;
+                module_area(&kernel).is_some().then_some(kernel)
+            });
+        let kernel = match kernel {
+            Some(kernel) => kernel,
+            None => {
+                issues.push(format!(
+                    "Could not decompress the kernel ({} bytes before offset {})",
+                    compressed_size, end
+                ));
+                return;
+            }
+        };
+        info.objects = parse_objects(module_area(&kernel).unwrap_or_default());
+        for object in &info.objects {
+            match (object.kind.as_str(), &object.name) {
+                ("module", Some(name)) => info.modules.push(name.clone()),
+                ("prefix", Some(prefix)) => info.prefix = Some(prefix.clone()),
+                ("config", Some(config)) => info.config = Some(config.clone()),
+                _ => {}
+            }
+        }
+    }
+}
+
+pub fn print_info(grub: &GrubInfo) -> String {
+    let mut table = Table::new();
+    let mut row = |name: &str, value: String| {
+        table.add_row(Row::new(vec![Cell::new(name), Cell::new(&value)]));
+    };
+    row(
+        "core.img",
+        format!("LBA {} ({})", grub.kernel_sector, grub.location),
+    );
+    row(
+        "Block list",
+        grub.blocklist
+            .iter()
+            .map(|block| format!("LBA {} ({} sectors)", block.start_lba, block.sectors))
+            .collect::<Vec<_>>()
+            .join("\n"),
+    );
+    row("Size (bytes)", grub.core_size.to_string());
+    if let Some(sha256) = &grub.sha256 {
+        row("SHA-256", sha256.clone());
+    }
+    if let Some(prefix) = &grub.prefix {
+        row("Prefix", prefix.clone());
+    }
+    row("Modules", grub.modules.join(", "));
+    if let Some(config) = &grub.config {
+        row("Embedded config", config.clone());
+    }
+    for issue in &grub.issues {
+        row("Warning", issue.clone());
+    }
+    format!("\nGRUB core.img:\n{}", table)
+}
@@ -0,0 +1,143 @@
+use crate::layout::{Slot, SlotKind};
+use crate::reader::SectorReader;
+use crate::Partitions;
+use log::info;
+use md5::Md5;
+use prettytable::{Cell, Row, Table};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use std::error::Error;
+use std::io::SeekFrom;
+
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+/// MD5, SHA-1 and SHA-256 digests of a byte range, as lowercase hex strings.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Default, Clone, PartialEq, Eq)]
+pub struct Hashes {
+    pub md5: String,
+    pub sha1: String,
+    pub sha256: String,
+}
+
+/// Feed data once and get the three digests out.
+#[derive(Default, Clone)]
+pub struct MultiHasher {
+    md5: Md5,
+    sha1: Sha1,
+    sha256: Sha256,
+}
+
+impl MultiHasher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        self.md5.update(data);
+        self.sha1.update(data);
+        self.sha256.update(data);
+    }
+
+    pub fn finalize(self) -> Hashes {
+        Hashes {
+            md5: to_hex(&self.md5.finalize()),
+            sha1: to_hex(&self.sha1.finalize()),
+            sha256: to_hex(&self.sha256.finalize()),
+        }
+    }
+}
+
+/// Digests of one slot of the disk layout.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct SlotHash {
+    pub slot: Slot,
+    pub first_byte_addr: u64,
+    pub size_bytes: u64,
+    pub hashes: Hashes,
+}
+
+/// Stream `length` bytes starting at `offset` through the three hashers.
+pub fn hash_range<R: SectorReader + ?Sized>(
+    body: &mut R,
+    offset: u64,
+    length: u64,
+) -> Result<Hashes, Box<dyn Error>> {
+    let mut hasher = MultiHasher::new();
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    let mut remaining = length;
+    body.seek(SeekFrom::Start(offset))?;
+    while remaining > 0 {
+        let len = std::cmp::min(CHUNK_SIZE as u64, remaining) as usize;
+        body.read_exact(&mut buf[..len])?;
+        hasher.update(&buf[..len]);
+        remaining -= len as u64;
+    }
+    Ok(hasher.finalize())
+}
+
+impl Partitions {
+    /// Hash every partition, every unallocated gap and the partition table sectors.
+    /// Extended containers are skipped as their content is covered by the other slots.
+    pub fn compute_hashes<R: SectorReader + ?Sized>(
+        &self,
+        body: &mut R,
+    ) -> Result<Vec<SlotHash>, Box<dyn Error>> {
+        let image_size = self.image_size;
+        let total_sectors = image_size / self.sector_size;
+        let mut results = Vec::new();
+        for slot in self.layout(total_sectors) {
+            if slot.kind == SlotKind::Container {
+                continue;
+            }
+            let first_byte_addr = slot.start_lba.saturating_mul(self.sector_size);
+            // Partitions may claim more than what was acquired; hash what is there.
+            let size_bytes = std::cmp::min(
+                slot.length.saturating_mul(self.sector_size),
+                image_size.saturating_sub(first_byte_addr),
+            );
+            info!(
+                "Hashing {} (LBA {} to {})",
+                slot.description, slot.start_lba, slot.end_lba
+            );
+            let first_byte_addr = self.base_offset.saturating_add(first_byte_addr);
+            let hashes = hash_range(body, first_byte_addr, size_bytes)?;
+            results.push(SlotHash {
+                slot,
+                first_byte_addr,
+                size_bytes,
+                hashes,
+            });
+        }
+        Ok(results)
+    }
+}
+
+pub fn print_info(hashes: &[SlotHash]) -> String {
+    let mut table = Table::new();
+    table.add_row(Row::new(vec![
+        Cell::new("Slot"),
+        Cell::new("Start (LBA)"),
+        Cell::new("End (LBA)"),
+        Cell::new("Description"),
+        Cell::new("Hashes"),
+    ]));
+    for entry in hashes {
+        table.add_row(Row::new(vec![
+            Cell::new(&format!("{:?}", entry.slot.kind)),
+            Cell::new(&format!("0x{:x}", entry.slot.start_lba)),
+            Cell::new(&format!("0x{:x}", entry.slot.end_lba)),
+            Cell::new(&entry.slot.description),
+            Cell::new(&format!(
+                "MD5: {}\nSHA-1: {}\nSHA-256: {}",
+                entry.hashes.md5, entry.hashes.sha1, entry.hashes.sha256
+            )),
+        ]));
+    }
+    table.to_string()
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
@@ -0,0 +1,263 @@
+//! Non-zero bytes where the partition structures leave space that should stay zero.
+//!
+//! Header slack, reserved fields, unused table entries and the sectors around the tables are
+//! classic hiding spots: nothing reads them, so whatever is written there survives untouched.
+
+use crate::reader::SectorReader;
+use crate::Partitions;
+use log::warn;
+use prettytable::{Cell, Row, Table};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::io::SeekFrom;
+
+const GPT_SIGNATURE: &[u8; 8] = b"EFI PART";
+const GPT_HEADER_SIZE: u64 = 92;
+/// The GPT entry array is given at least this much room, whatever the entry count.
+const GPT_MIN_ARRAY_SIZE: u64 = 16384;
+/// Bytes of an area checked at most.
+const MAX_AREA_SIZE: u64 = 8 * 1024 * 1024;
+/// Bytes shown in the hexdump of an area, from its first non-zero row.
+const HEXDUMP_SIZE: usize = 256;
+
+/// A reserved area holding non-zero bytes.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct HiddenData {
+    pub location: String,
+    pub first_byte_addr: u64, // Start of the area, relative to `base_offset` like the LBAs
+    pub size_bytes: u64,
+    pub nonzero_bytes: u64,
+    pub first_nonzero: u64, // Offset of the first non-zero byte in the area
+    pub hexdump: String,    // Offsets relative to the area
+}
+
+/// Classic hexdump of `bytes`, with offsets starting at `base`.
+pub fn hexdump(bytes: &[u8], base: u64) -> String {
+    bytes
+        .chunks(16)
+        .enumerate()
+        .map(|(i, row)| {
+            let hex: Vec<String> = row.iter().map(|b| format!("{:02x}", b)).collect();
+            let ascii: String = row
+                .iter()
+                .map(|&b| {
+                    if b.is_ascii_graphic() || b == b' ' {
+                        b as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect();
+            format!(
+                "{:08x}  {:<47}  |{}|",
+                base + i as u64 * 16,
+                hex.join(" "),
+                ascii
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The finding for `bytes`, read at `first_byte_addr`, if any of them is non-zero.
+fn check(location: String, first_byte_addr: u64, bytes: &[u8]) -> Option<HiddenData> {
+    let first_nonzero = bytes.iter().position(|&b| b != 0)?;
+    let row = first_nonzero - first_nonzero % 16;
+    let end = (row + HEXDUMP_SIZE).min(bytes.len());
+    Some(HiddenData {
+        location,
+        first_byte_addr,
+        size_bytes: bytes.len() as u64,
+        nonzero_bytes: bytes.iter().filter(|&&b| b != 0).count() as u64,
+        first_nonzero: first_nonzero as u64,
+        hexdump: hexdump(&bytes[row..end], row as u64),
+    })
+}
+
+fn read_at<R: SectorReader + ?Sized>(body: &mut R, offset: u64, length: u64) -> Option<Vec<u8>> {
+    let mut bytes = vec![0u8; length as usize];
+    body.seek(SeekFrom::Start(offset)).ok()?;
+    body.read_exact(&mut bytes).ok()?;
+    Some(bytes)
+}
+
+impl Partitions {
+    /// Check the spare bytes of the partition structures that should be zero: GPT header slack
+    /// and reserved field, unused GPT entries and the sectors around the entry array, the MBR
+    /// gap of MBR-only disks, and the unused entries of every EBR.
+    pub fn find_hidden_data<R: SectorReader + ?Sized>(&self, body: &mut R) -> Vec<HiddenData> {
+        let sector_size = self.sector_size;
+        let mut found = Vec::new();
+
+        if let Some(gpt) = &self.gpt {
+            let header = &gpt.header;
+            let mut header_lbas = vec![header.current_lba, header.backup_lba];
+            header_lbas.dedup();
+            for lba in header_lbas {
+                let Some(offset) = lba.checked_mul(sector_size) else {
+                    continue;
+                };
+                let sector = match read_at(body, offset, sector_size) {
+                    Some(sector) if &sector[0..8] == GPT_SIGNATURE => sector,
+                    _ => continue,
+                };
+                found.extend(check(
+                    format!("GPT header reserved field (LBA {})", lba),
+                    offset + 20,
+                    &sector[20..24],
+                ));
+                let header_size = u32::from_le_bytes(sector[12..16].try_into().unwrap()) as u64;
+                let header_size = header_size.clamp(GPT_HEADER_SIZE, sector_size);
+                found.extend(check(
+                    format!("GPT header slack (LBA {})", lba),
+                    offset + header_size,
+                    &sector[header_size as usize..],
+                ));
+            }
+
+            let entry_size = header.partition_entry_size as u64;
+            let used_size = header.num_partition_entries as u64 * entry_size;
+            let array_size = used_size.max(GPT_MIN_ARRAY_SIZE).div_ceil(sector_size) * sector_size;
+            let array_offset = header.partition_entry_lba.checked_mul(sector_size);
+            let array = match array_offset {
+                Some(array_offset) if array_size <= MAX_AREA_SIZE => {
+                    read_at(body, array_offset, array_size).map(|array| (array_offset, array))
+                }
+                _ => None,
+            };
+            if let Some((array_offset, array)) = array {
+                if entry_size >= 128 {
+                    for (i, entry) in array[..used_size as usize]
+                        .chunks(entry_size as usize)
+                        .enumerate()
+                    {
+                        if entry[0..16].iter().all(|&b| b == 0) {
+                            found.extend(check(
+                                format!("Unused GPT entry #{}", i),
+                                array_offset + i as u64 * entry_size,
+                                entry,
+                            ));
+                        }
+                    }
+                }
+                found.extend(check(
+                    format!(
+                        "GPT entry array past num_partition_entries ({})",
+                        header.num_partition_entries
+                    ),
+                    array_offset + used_size,
+                    &array[used_size as usize..],
+                ));
+            }
+
+            // Sectors of the primary GPT around the entry array, before the first usable LBA.
+            if header.current_lba < header.backup_lba {
+                let array_end = header
+                    .partition_entry_lba
+                    .saturating_add(array_size / sector_size);
+                let gaps = [
+                    (header.current_lba + 1, header.partition_entry_lba),
+                    (array_end, header.first_usable_lba),
+                ];
+                for (start, end) in gaps {
+                    if start >= end {
+                        continue;
+                    }
+                    let (Some(offset), Some(length)) = (
+                        start.checked_mul(sector_size),
+                        (end - start).checked_mul(sector_size),
+                    ) else {
+                        continue;
+                    };
+                    if length > MAX_AREA_SIZE {
+                        continue;
+                    }
+                    if let Some(bytes) = read_at(body, offset, length) {
+                        found.extend(check(
+                            format!("GPT reserved sectors (LBA {} to {})", start, end - 1),
+                            offset,
+                            &bytes,
+                        ));
+                    }
+                }
+            }
+        }
+
+        let mbr_only = self
+            .mbr
+            .as_ref()
+            .filter(|mbr| !mbr.is_pmbr() && self.gpt.is_none());
+        if let Some(mbr) = mbr_only {
+            let first_partition = mbr
+                .partition_table
+                .iter()
+                .filter(|entry| entry.partition_type != 0x00 && entry.start_lba > 0)
+                .map(|entry| entry.start_lba as u64)
+                .min();
+            if let Some(first_partition) = first_partition.filter(|&lba| lba > 1) {
+                let length = ((first_partition - 1) * sector_size).min(MAX_AREA_SIZE);
+                if let Some(bytes) = read_at(body, sector_size, length) {
+                    found.extend(check(
+                        format!("MBR gap (LBA 1 to {})", first_partition - 1),
+                        sector_size,
+                        &bytes,
+                    ));
+                }
+            }
+        }
+
+        for (i, record) in self.ebr.iter().flatten().enumerate() {
+            let offset = record.table_lba * sector_size;
+            let sector = match read_at(body, offset, 512) {
+                Some(sector) => sector,
+                None => continue,
+            };
+            for (slot, entry) in record.partition_table.iter().enumerate() {
+                // The first two entries are the logical partition and the link to the next EBR.
+                if slot < 2 && entry.partition_type != 0x00 {
+                    continue;
+                }
+                let start = 446 + slot * 16;
+                found.extend(check(
+                    format!(
+                        "Unused entry {} of EBR #{} (LBA {})",
+                        slot + 1,
+                        i + 1,
+                        record.table_lba
+                    ),
+                    offset + start as u64,
+                    &sector[start..start + 16],
+                ));
+            }
+        }
+
+        for hidden in &found {
+            warn!(
+                "{} bytes of data in {}, at byte 0x{:x}",
+                hidden.nonzero_bytes, hidden.location, hidden.first_byte_addr
+            );
+        }
+        found
+    }
+}
+
+pub fn print_info(hidden_data: &[HiddenData]) -> String {
+    let mut table = Table::new();
+    table.add_row(Row::new(vec![
+        Cell::new("Location"),
+        Cell::new("First Byte Addr"),
+        Cell::new("Size (bytes)"),
+        Cell::new("Non-zero bytes"),
+        Cell::new("Hexdump"),
+    ]));
+    for hidden in hidden_data {
+        table.add_row(Row::new(vec![
+            Cell::new(&hidden.location),
+            Cell::new(&format!("0x{:x}", hidden.first_byte_addr)),
+            Cell::new(&hidden.size_bytes.to_string()),
+            Cell::new(&hidden.nonzero_bytes.to_string()),
+            Cell::new(&hidden.hexdump),
+        ]));
+    }
+    format!("\nData in reserved areas:\n{}", table)
+}
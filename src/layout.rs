@@ -0,0 +1,173 @@
+use crate::extent::Scheme;
+use crate::Partitions;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlotKind {
+    Table,       // Sectors holding a partition table (MBR, EBR, GPT header or entry array)
+    Container,   // Extended partition holding the EBR chain
+    Unallocated, // Sectors not covered by any table or partition
+    Partition,
+}
+
+/// One contiguous range of sectors of the disk, as laid out by the partition tables.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct Slot {
+    pub kind: SlotKind,
+    pub scheme: Option<Scheme>,
+    pub partition_index: Option<u64>, // Index of the matching `PartitionExtent`
+    pub start_lba: u64,
+    pub end_lba: u64, // Inclusive
+    pub length: u64,  // In sectors
+    pub description: String,
+}
+
+impl Slot {
+    fn new(kind: SlotKind, scheme: Option<Scheme>, start: u64, length: u64, desc: &str) -> Slot {
+        Slot {
+            kind,
+            scheme,
+            partition_index: None,
+            start_lba: start,
+            end_lba: start + length.max(1) - 1,
+            length,
+            description: desc.to_string(),
+        }
+    }
+}
+
+impl Partitions {
+    /// Lay the whole disk out as sorted slots: partition tables, extended containers,
+    /// partitions, and the unallocated gaps left between them.
+    /// `total_sectors` is the size of the body in sectors.
+    pub fn layout(&self, total_sectors: u64) -> Vec<Slot> {
+        let mut slots: Vec<Slot> = Vec::new();
+
+        if let Some(mbr) = &self.mbr {
+            let desc = if mbr.is_pmbr() {
+                "Safety Table"
+            } else {
+                "Primary Table (#0)"
+            };
+            slots.push(Slot::new(SlotKind::Table, Some(Scheme::MBR), 0, 1, desc));
+            for entry in &mbr.partition_table {
+                if matches!(entry.partition_type, 0x05 | 0x0F | 0x85) {
+                    slots.push(Slot::new(
+                        SlotKind::Container,
+                        Some(Scheme::MBR),
+                        entry.start_lba as u64,
+                        entry.size_sectors as u64,
+                        &format!("{} (0x{:02x})", entry.description, entry.partition_type),
+                    ));
+                }
+            }
+        }
+
+        if let Some(ebr) = &self.ebr {
+            for (i, record) in ebr.iter().enumerate() {
+                slots.push(Slot::new(
+                    SlotKind::Table,
+                    Some(Scheme::EBR),
+                    record.table_lba,
+                    1,
+                    &format!("Extended Table (#{})", i + 1),
+                ));
+            }
+        }
+
+        if let Some(gpt) = &self.gpt {
+            let header = &gpt.header;
+            let entry_bytes =
+                header.num_partition_entries as u64 * header.partition_entry_size as u64;
+            let array_sectors = entry_bytes.div_ceil(self.sector_size.max(1));
+            let is_backup = header.current_lba > header.backup_lba;
+            let (primary_lba, backup_lba) = if is_backup {
+                (header.backup_lba, header.current_lba)
+            } else {
+                (header.current_lba, header.backup_lba)
+            };
+            slots.push(Slot::new(
+                SlotKind::Table,
+                Some(Scheme::GPT),
+                primary_lba,
+                1,
+                "GPT Header",
+            ));
+            slots.push(Slot::new(
+                SlotKind::Table,
+                Some(Scheme::GPT),
+                if is_backup {
+                    primary_lba + 1
+                } else {
+                    header.partition_entry_lba
+                },
+                array_sectors,
+                "Partition Table",
+            ));
+            if backup_lba > primary_lba + array_sectors {
+                slots.push(Slot::new(
+                    SlotKind::Table,
+                    Some(Scheme::GPT),
+                    if is_backup {
+                        header.partition_entry_lba
+                    } else {
+                        backup_lba.saturating_sub(array_sectors)
+                    },
+                    array_sectors,
+                    "Backup Partition Table",
+                ));
+                slots.push(Slot::new(
+                    SlotKind::Table,
+                    Some(Scheme::GPT),
+                    backup_lba,
+                    1,
+                    "Backup GPT Header",
+                ));
+            }
+        }
+
+        for extent in self.extents() {
+            let mut slot = Slot::new(
+                SlotKind::Partition,
+                Some(extent.scheme),
+                extent.start_lba,
+                extent.size_sectors,
+                &extent.description,
+            );
+            slot.partition_index = Some(extent.index);
+            slots.push(slot);
+        }
+
+        slots.retain(|slot| slot.length > 0 && slot.start_lba < total_sectors.max(1));
+        slots.sort_by_key(|slot| (slot.start_lba, slot.kind == SlotKind::Partition));
+
+        // Gaps are whatever is covered neither by a table nor by a partition.
+        let mut gaps = Vec::new();
+        let mut next_free = 0u64;
+        for slot in slots.iter().filter(|s| s.kind != SlotKind::Container) {
+            if slot.start_lba > next_free {
+                gaps.push(Slot::new(
+                    SlotKind::Unallocated,
+                    None,
+                    next_free,
+                    slot.start_lba - next_free,
+                    "Unallocated",
+                ));
+            }
+            next_free = next_free.max(slot.end_lba + 1);
+        }
+        if total_sectors > next_free {
+            gaps.push(Slot::new(
+                SlotKind::Unallocated,
+                None,
+                next_free,
+                total_sectors - next_free,
+                "Unallocated",
+            ));
+        }
+        slots.extend(gaps);
+        slots.sort_by_key(|slot| (slot.start_lba, slot.kind == SlotKind::Partition));
+        slots
+    }
+}
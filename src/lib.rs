@@ -1,24 +1,121 @@
+pub mod abslot;
+pub mod bitlocker;
+#[cfg(feature = "disassembly")]
+pub mod bootcode;
+pub mod builder;
+pub mod decrypt;
+pub mod dps;
 pub mod ebr;
+pub mod encryption;
+pub mod entropy;
+pub mod esp;
+pub mod export;
+pub mod extent;
+pub mod extract;
+pub mod fat;
 pub mod gpt;
+pub mod grub;
+pub mod hash;
+pub mod hidden;
+pub mod layout;
+pub mod luks;
 pub mod mbr;
+pub mod mmls;
+pub mod nested;
+pub mod reader;
+pub mod repair;
+pub mod report;
+pub mod types;
 
-use exhume_body::Body;
+#[cfg(feature = "disassembly")]
+use bootcode::BootCode;
+use entropy::SlotEntropy;
+use esp::EspInventory;
 use gpt::{GPTHeader, GPTPartitionEntry, GPT};
+use grub::GrubInfo;
+use hash::SlotHash;
+use hidden::HiddenData;
 use log::{error, info, warn};
 use mbr::MBR;
+use nested::{DiscoveryOptions, NestedPartitions};
+use reader::{SectorReader, SubReader};
 use serde::{Deserialize, Serialize};
 use std::error::Error;
-use std::io::{Read, Seek, SeekFrom};
+use std::io::SeekFrom;
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct Partitions {
     pub mbr: Option<MBR>,
     pub ebr: Option<Vec<MBR>>,
     pub gpt: Option<GPT>,
+    #[serde(default = "default_sector_size")]
+    pub sector_size: u64,
+    #[serde(default)]
+    pub image_size: u64, // Logical size of the body in bytes, from `base_offset` on
+    #[serde(default)]
+    pub base_offset: u64, // Byte offset in the body the tables were discovered at
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hashes: Option<Vec<SlotHash>>, // Filled by `compute_hashes` on request
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entropy: Option<Vec<SlotEntropy>>, // Filled by `compute_entropy` on request
+    #[cfg(feature = "disassembly")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bootcode: Option<BootCode>, // MBR boot code, filled by `disassemble_bootloader` on request
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hidden_data: Vec<HiddenData>, // Non-zero bytes in reserved areas of the tables
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grub: Option<GrubInfo>, // core.img, when the MBR boot code is GRUB boot.img
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub esp: Vec<EspInventory>, // Files of the EFI System Partitions
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub nested: Vec<NestedPartitions>, // Tables found inside partitions, with `DiscoveryOptions::nested`
+}
+
+fn default_sector_size() -> u64 {
+    512
 }
 
 impl Partitions {
-    pub fn new(body: &mut Body) -> Result<Partitions, Box<dyn Error>> {
+    pub fn new<R: SectorReader + ?Sized>(body: &mut R) -> Result<Partitions, Box<dyn Error>> {
+        Partitions::with_options(body, &DiscoveryOptions::default())
+    }
+
+    pub fn with_options<R: SectorReader + ?Sized>(
+        body: &mut R,
+        options: &DiscoveryOptions,
+    ) -> Result<Partitions, Box<dyn Error>> {
+        let mut partitions = if options.offset == 0 {
+            Partitions::discover(body, options)?
+        } else {
+            let size = body.image_size()?;
+            if options.offset >= size {
+                return Err(format!(
+                    "Offset {} is past the end of the body ({} bytes)",
+                    options.offset, size
+                )
+                .into());
+            }
+            info!(
+                "Scanning for partition tables at byte offset {}.",
+                options.offset
+            );
+            let mut window = SubReader::new(body, options.offset, size - options.offset);
+            let mut partitions = Partitions::discover(&mut window, options)?;
+            partitions.base_offset = options.offset;
+            partitions
+        };
+        if options.nested {
+            partitions.nested = nested::discover_nested(body, &partitions, options);
+        }
+        Ok(partitions)
+    }
+
+    /// The partition tables found at the start (and, for the backup GPT, the end) of the body,
+    /// then what the passes turned on in `options` find in the partitions.
+    fn discover<R: SectorReader + ?Sized>(
+        body: &mut R,
+        options: &DiscoveryOptions,
+    ) -> Result<Partitions, Box<dyn Error>> {
         let mbr_record = match discover_mbr_partitions(body) {
             Ok(mbr) => Some(mbr),
             Err(msg) => {
@@ -39,46 +136,136 @@ impl Partitions {
             }
         };
 
-        Ok(Partitions {
+        let mut partitions = Partitions {
             mbr: mbr_record,
             ebr: ebr_record,
             gpt: gpt_record,
-        })
+            sector_size: body.sector_size() as u64,
+            image_size: body.image_size()?,
+            base_offset: 0,
+            hashes: None,
+            entropy: None,
+            #[cfg(feature = "disassembly")]
+            bootcode: None,
+            hidden_data: Vec::new(),
+            grub: None,
+            esp: Vec::new(),
+            nested: Vec::new(),
+        };
+        if options.encryption {
+            partitions.detect_encryption(body);
+        }
+        if options.hidden_data {
+            partitions.hidden_data = partitions.find_hidden_data(body);
+        }
+        if options.grub {
+            partitions.grub = partitions.analyze_grub(body);
+        }
+        if options.esp {
+            partitions.esp = partitions.inventory_esp(body);
+        }
+        Ok(partitions)
+    }
+
+    /// The disassembly of the MBR boot code: `bootcode` when it was filled, so it is only
+    /// disassembled once.
+    #[cfg(feature = "disassembly")]
+    fn bootloader_listing(&self, mbr: &MBR) -> String {
+        match &self.bootcode {
+            Some(bootcode) => bootcode.listing(),
+            None => mbr.disassemble_bootloader().listing(),
+        }
+    }
+
+    #[cfg(not(feature = "disassembly"))]
+    fn bootloader_listing(&self, _mbr: &MBR) -> String {
+        String::from("Disassembly unavailable (built without the `disassembly` feature)")
     }
 
     pub fn print_info(&self, bootloader: bool) -> String {
         let mut s = String::new();
 
         if let Some(mbr) = &self.mbr {
-            s.push_str(&mbr.print_info(&bootloader));
+            let listing = bootloader.then(|| self.bootloader_listing(mbr));
+            s.push_str(&mbr.print_info(listing.as_deref(), self.base_offset));
         }
         s.push('\n');
 
         if let Some(ebr) = &self.ebr {
             if !ebr.is_empty() {
                 for ebr_entry in ebr {
-                    s.push_str(&ebr_entry.print_info(&bootloader))
+                    s.push_str(&ebr_entry.print_info(None, self.base_offset))
                 }
             }
         }
 
         if let Some(gpt) = &self.gpt {
-            s.push_str(&gpt.print_info());
+            s.push_str(&gpt.print_info(self.base_offset));
+        }
+
+        let encrypted: Vec<_> = self
+            .extents()
+            .into_iter()
+            .filter(|extent| extent.encryption.is_some())
+            .collect();
+        if !encrypted.is_empty() {
+            s.push_str(&encryption::print_info(&encrypted));
+        }
+
+        let ab_slots = self.ab_slots();
+        if !ab_slots.is_empty() {
+            s.push_str(&abslot::print_info(&ab_slots));
+        }
+
+        let dps = self.dps_partitions();
+        if !dps.is_empty() {
+            s.push_str(&dps::print_info(&dps));
+        }
+
+        if let Some(grub) = &self.grub {
+            s.push_str(&grub::print_info(grub));
+        }
+
+        if !self.esp.is_empty() {
+            s.push_str(&esp::print_info(&self.esp));
+        }
+
+        if !self.hidden_data.is_empty() {
+            s.push_str(&hidden::print_info(&self.hidden_data));
+        }
+
+        if let Some(hashes) = &self.hashes {
+            s.push_str(&hash::print_info(hashes));
+        }
+
+        if let Some(entropy) = &self.entropy {
+            s.push_str(&entropy::print_info(entropy));
+        }
+
+        for nested in &self.nested {
+            s.push_str(&format!(
+                "\nNested partition table in partition #{} (depth {}, offset 0x{:x}):\n",
+                nested.parent_index, nested.depth, nested.partitions.base_offset
+            ));
+            s.push_str(&nested.partitions.print_info(bootloader));
         }
 
         s
     }
 }
 
-fn discover_any_gpt(body: &mut Body) -> Result<GPT, Box<dyn Error>> {
+fn discover_any_gpt<R: SectorReader + ?Sized>(body: &mut R) -> Result<GPT, Box<dyn Error>> {
     discover_gpt_partitions(body, false) // primary @ LBA 1
         .or_else(|_| discover_gpt_partitions(body, true)) // backup @ last LBA
 }
 
-fn discover_mbr_partitions(body: &mut Body) -> Result<mbr::MBR, Box<dyn Error>> {
-    let mut bootsector: Vec<u8> = vec![0; body.get_sector_size() as usize];
-    body.read_exact(&mut bootsector).unwrap();
-    let main_mbr = mbr::MBR::from_bytes(&bootsector);
+fn discover_mbr_partitions<R: SectorReader + ?Sized>(
+    body: &mut R,
+) -> Result<mbr::MBR, Box<dyn Error>> {
+    let mut bootsector: Vec<u8> = vec![0; body.sector_size() as usize];
+    body.seek(SeekFrom::Start(0))?;
+    body.read_exact(&mut bootsector)?;
+    let main_mbr = mbr::MBR::from_bytes_with_sector_size(&bootsector, bootsector.len());
     if main_mbr.is_mbr() {
         info!("Detected an MBR partition scheme.");
         if main_mbr.is_pmbr() {
@@ -91,8 +278,11 @@ fn discover_mbr_partitions(body: &mut Body) -> Result<mbr::MBR, Box<dyn Error>>
     }
 }
 
-pub fn read_gpt_header_at(body: &mut Body, lba: u64) -> Result<GPTHeader, Box<dyn Error>> {
-    let sector_size = body.get_sector_size() as u64;
+pub fn read_gpt_header_at<R: SectorReader + ?Sized>(
+    body: &mut R,
+    lba: u64,
+) -> Result<GPTHeader, Box<dyn Error>> {
+    let sector_size = body.sector_size() as u64;
     let mut hdr_bytes = [0u8; 92];
     body.seek(SeekFrom::Start(lba * sector_size))?;
     body.read_exact(&mut hdr_bytes)?;
@@ -105,13 +295,17 @@ pub fn read_gpt_header_at(body: &mut Body, lba: u64) -> Result<GPTHeader, Box<dy
     }
 }
 
-fn discover_ebr_partitions(body: &mut Body, main_mbr: &mbr::MBR) -> Vec<MBR> {
+fn discover_ebr_partitions<R: SectorReader + ?Sized>(
+    body: &mut R,
+    main_mbr: &mbr::MBR,
+) -> Vec<MBR> {
     let mut all_partitions: Vec<MBR> = Vec::new();
     for p in &main_mbr.partition_table {
         match p.partition_type {
             0x05 | 0x0F | 0x85 => {
                 info!("Extended Boot Record (EBR) partition discovered.");
-                let extended_partitions = ebr::parse_ebr(body, p.start_lba, p.sector_size);
+                let extended_partitions =
+                    ebr::parse_ebr(body, p.start_lba, body.sector_size() as usize);
                 all_partitions.extend(extended_partitions);
             }
             _ => {}
@@ -122,8 +316,11 @@ fn discover_ebr_partitions(body: &mut Body, main_mbr: &mbr::MBR) -> Vec<MBR> {
 
 /// Read the GPT (primary or backup) and all of its partition-table entries.
 /// Parse the primary header at LBA 1 or parse the backup header at the last LBA of the image.
-fn discover_gpt_partitions(body: &mut Body, backup: bool) -> Result<GPT, Box<dyn Error>> {
-    let sector_size = body.get_sector_size() as u64;
+fn discover_gpt_partitions<R: SectorReader + ?Sized>(
+    body: &mut R,
+    backup: bool,
+) -> Result<GPT, Box<dyn Error>> {
+    let sector_size = body.sector_size() as u64;
 
     // Primary GPT header is always at LBA 1
     // Backup GPT header is always at the last LBA of the disk / image
@@ -146,6 +343,12 @@ fn discover_gpt_partitions(body: &mut Body, backup: bool) -> Result<GPT, Box<dyn
     if !gpt.is_gpt() {
         return Err("Invalid Signature".into());
     }
+    if !gpt.header.is_crc32_valid() {
+        warn!(
+            "The GPT header at LBA {} fails its CRC32 check.",
+            target_lba
+        );
+    }
 
     info!(
         "Discovered a {} GPT header at LBA {}",
@@ -153,9 +356,12 @@ fn discover_gpt_partitions(body: &mut Body, backup: bool) -> Result<GPT, Box<dyn
         target_lba
     );
 
-    body.seek(SeekFrom::Start(
-        gpt.header.partition_entry_lba * sector_size,
-    ))?;
+    let array_offset = gpt
+        .header
+        .partition_entry_lba
+        .checked_mul(sector_size)
+        .ok_or("Partition entry array out of range")?;
+    body.seek(SeekFrom::Start(array_offset))?;
 
     let num_entries = gpt.header.num_partition_entries as usize;
     let entry_size = gpt.header.partition_entry_size as usize;
@@ -163,17 +369,30 @@ fn discover_gpt_partitions(body: &mut Body, backup: bool) -> Result<GPT, Box<dyn
 
     gpt.partition_entries = Vec::with_capacity(num_entries);
 
+    let mut array_crc32 = crc32fast::Hasher::new();
     for i in 0..num_entries {
         body.read_exact(&mut entry_buf)?;
+        array_crc32.update(&entry_buf);
         let mut entry = GPTPartitionEntry::from_bytes(&entry_buf);
         entry.id = Some(i as i64);
-        entry.first_byte_addr = entry.starting_lba * body.get_sector_size() as u64;
-        entry.size_sectors = entry.ending_lba - entry.starting_lba + 1;
+        entry.first_byte_addr = entry.starting_lba.saturating_mul(body.sector_size() as u64);
+        // An entry ending before it starts is empty.
+        entry.size_sectors = entry
+            .ending_lba
+            .saturating_add(1)
+            .saturating_sub(entry.starting_lba);
         // Skip unused (all-zero) entries to keep the output tidy
         if entry.partition_type_guid != [0u8; 16] {
             gpt.partition_entries.push(entry);
         }
     }
+    gpt.partition_array_crc32_read = Some(array_crc32.finalize());
+    if !gpt.is_partition_array_crc32_valid() {
+        warn!(
+            "The GPT partition array at LBA {} fails its CRC32 check.",
+            gpt.header.partition_entry_lba
+        );
+    }
 
     Ok(gpt)
 }
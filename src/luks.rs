@@ -0,0 +1,447 @@
+//! LUKS (Linux Unified Key Setup) headers, versions 1 and 2.
+//!
+//! LUKS1 keeps everything in a fixed 592-byte big-endian header. LUKS2 starts with a
+//! 4096-byte binary header followed by a JSON area describing segments, keyslots, tokens and
+//! digests; the binary header and JSON area are stored twice, and each copy is checksummed.
+
+use crate::hash::to_hex;
+use crate::reader::SectorReader;
+use byteorder::{BigEndian, ByteOrder};
+use log::warn;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha512};
+use std::io::SeekFrom;
+
+pub const MAGIC: &[u8; 6] = b"LUKS\xba\xbe";
+/// Magic of the LUKS2 secondary header.
+pub const SECONDARY_MAGIC: &[u8; 6] = b"SKUL\xba\xbe";
+const LUKS1_HEADER_SIZE: usize = 592;
+const LUKS1_KEYSLOTS: usize = 8;
+const LUKS1_KEYSLOT_ACTIVE: u32 = 0x00AC_71F3;
+const LUKS2_BINARY_HEADER_SIZE: usize = 4096;
+const LUKS2_CHECKSUM_OFFSET: usize = 448;
+const LUKS2_CHECKSUM_SIZE: usize = 64;
+/// Where the secondary header may be, one for each allowed header size.
+const LUKS2_SECONDARY_OFFSETS: [u64; 9] = [
+    0x4000, 0x8000, 0x10000, 0x20000, 0x40000, 0x80000, 0x100000, 0x200000, 0x400000,
+];
+/// The largest header LUKS2 allows, binary header and JSON area included.
+const MAX_HEADER_SIZE: u64 = LUKS2_SECONDARY_OFFSETS[LUKS2_SECONDARY_OFFSETS.len() - 1];
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Default)]
+pub struct LuksKeyslot {
+    pub id: u64,
+    pub active: bool,
+    pub kdf: Option<String>,     // "pbkdf2", "argon2i", "argon2id"
+    pub iterations: Option<u64>, // PBKDF2 iterations
+    pub key_size: Option<u64>,   // In bytes
+    #[serde(default)]
+    pub kdf_hash: Option<String>, // PBKDF2 hash
+    #[serde(default)]
+    pub time: Option<u64>, // Argon2 iterations
+    #[serde(default)]
+    pub memory: Option<u64>, // Argon2 memory, in KiB
+    #[serde(default)]
+    pub cpus: Option<u64>, // Argon2 parallelism
+    #[serde(default)]
+    pub area_encryption: Option<String>, // Cipher of the key material
+    #[serde(default)]
+    pub area_offset: Option<u64>, // Key material, in bytes from the header
+    #[serde(default)]
+    pub area_size: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct Luks2Segment {
+    pub id: u64,
+    pub kind: String,      // "crypt" or "linear"
+    pub offset: u64,       // In bytes from the header
+    pub size: Option<u64>, // None when "dynamic": up to the end of the device
+    pub encryption: Option<String>,
+    pub sector_size: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct Luks2Token {
+    pub id: u64,
+    pub kind: String, // e.g. "luks2-keyring", "systemd-tpm2", "systemd-fido2"
+    pub keyslots: Vec<u64>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct Luks2Digest {
+    pub id: u64,
+    pub kind: String,
+    pub hash: Option<String>,
+    pub iterations: Option<u64>,
+    pub keyslots: Vec<u64>,
+    pub segments: Vec<u64>,
+}
+
+/// One of the two copies of the LUKS2 binary header and JSON area.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct Luks2HeaderCopy {
+    pub offset: u64, // In bytes from the primary header
+    pub seqid: u64,  // Incremented on every metadata update
+    pub header_size: u64,
+    pub label: Option<String>,
+    pub subsystem: Option<String>,
+    pub uuid: String,
+    pub checksum_algorithm: String,
+    pub checksum: String,
+    pub checksum_valid: bool,
+    #[serde(skip)]
+    pub json: Value,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct Luks2Metadata {
+    pub headers: Vec<Luks2HeaderCopy>, // Primary then secondary, when found
+    pub segments: Vec<Luks2Segment>,
+    pub tokens: Vec<Luks2Token>,
+    pub digests: Vec<Luks2Digest>,
+    pub json_size: Option<u64>,
+    pub keyslots_size: Option<u64>,
+    pub issues: Vec<String>, // Bad checksums and disagreements between the two copies
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct LuksHeader {
+    pub version: u16,
+    pub uuid: String,
+    pub label: Option<String>, // LUKS2 only
+    pub cipher: String,        // e.g. "aes-xts-plain64"
+    pub hash: Option<String>,  // Digest hash (LUKS1) or first keyslot hash (LUKS2)
+    pub key_bytes: Option<u64>,
+    pub payload_offset: u64, // Start of the encrypted data, in bytes from the header
+    pub keyslots: Vec<LuksKeyslot>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub luks2: Option<Luks2Metadata>,
+}
+
+/// NUL-terminated ASCII field.
+fn c_string(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).to_string()
+}
+
+fn optional_string(bytes: &[u8]) -> Option<String> {
+    let s = c_string(bytes);
+    if s.is_empty() {
+        None
+    } else {
+        Some(s)
+    }
+}
+
+fn parse_luks1(header: &[u8]) -> LuksHeader {
+    let cipher_name = c_string(&header[8..40]);
+    let cipher_mode = c_string(&header[40..72]);
+    let keyslots = (0..LUKS1_KEYSLOTS)
+        .map(|i| {
+            let slot = &header[208 + i * 48..208 + (i + 1) * 48];
+            LuksKeyslot {
+                id: i as u64,
+                active: BigEndian::read_u32(slot) == LUKS1_KEYSLOT_ACTIVE,
+                kdf: Some("pbkdf2".to_string()),
+                iterations: Some(BigEndian::read_u32(&slot[4..]) as u64),
+                area_offset: Some(BigEndian::read_u32(&slot[40..]) as u64 * 512),
+                ..Default::default()
+            }
+        })
+        .collect();
+    LuksHeader {
+        version: 1,
+        uuid: c_string(&header[168..208]),
+        label: None,
+        cipher: format!("{}-{}", cipher_name, cipher_mode),
+        hash: Some(c_string(&header[72..104])),
+        key_bytes: Some(BigEndian::read_u32(&header[108..]) as u64),
+        payload_offset: BigEndian::read_u32(&header[104..]) as u64 * 512,
+        keyslots,
+        luks2: None,
+    }
+}
+
+/// Integers are stored as JSON strings in LUKS2 metadata.
+fn json_u64(value: &Value) -> Option<u64> {
+    match value {
+        Value::String(s) => s.parse().ok(),
+        Value::Number(n) => n.as_u64(),
+        _ => None,
+    }
+}
+
+fn json_string(value: &Value) -> Option<String> {
+    value.as_str().map(str::to_string)
+}
+
+/// Ids listed as strings, e.g. `"keyslots": ["0", "1"]`.
+fn json_ids(value: &Value) -> Vec<u64> {
+    value
+        .as_array()
+        .map(|ids| ids.iter().filter_map(json_u64).collect())
+        .unwrap_or_default()
+}
+
+/// The members of a JSON object keyed by numeric id, sorted by id.
+fn json_objects(value: &Value) -> Vec<(u64, &Value)> {
+    let mut objects: Vec<(u64, &Value)> = value
+        .as_object()
+        .map(|map| {
+            map.iter()
+                .filter_map(|(id, object)| Some((id.parse().ok()?, object)))
+                .collect()
+        })
+        .unwrap_or_default();
+    objects.sort_by_key(|(id, _)| *id);
+    objects
+}
+
+/// Checksum of a binary header (checksum field zeroed) followed by its JSON area.
+fn checksum(algorithm: &str, binary: &[u8], json: &[u8]) -> Option<Vec<u8>> {
+    let mut binary = binary.to_vec();
+    binary[LUKS2_CHECKSUM_OFFSET..LUKS2_CHECKSUM_OFFSET + LUKS2_CHECKSUM_SIZE].fill(0);
+    let digest = match algorithm {
+        "sha1" => Sha1::new()
+            .chain_update(&binary)
+            .chain_update(json)
+            .finalize()
+            .to_vec(),
+        "sha256" => Sha256::new()
+            .chain_update(&binary)
+            .chain_update(json)
+            .finalize()
+            .to_vec(),
+        "sha512" => Sha512::new()
+            .chain_update(&binary)
+            .chain_update(json)
+            .finalize()
+            .to_vec(),
+        _ => return None,
+    };
+    Some(digest)
+}
+
+/// Read the LUKS2 header copy found `at` bytes from the primary header at `offset`.
+fn read_copy<R: SectorReader + ?Sized>(
+    body: &mut R,
+    offset: u64,
+    at: u64,
+    magic: &[u8; 6],
+) -> Option<Luks2HeaderCopy> {
+    let mut binary = vec![0u8; LUKS2_BINARY_HEADER_SIZE];
+    body.seek(SeekFrom::Start(offset + at)).ok()?;
+    body.read_exact(&mut binary).ok()?;
+    if &binary[0..6] != magic || BigEndian::read_u16(&binary[6..]) != 2 {
+        return None;
+    }
+    let header_size = BigEndian::read_u64(&binary[8..]);
+    if header_size <= LUKS2_BINARY_HEADER_SIZE as u64 || header_size > MAX_HEADER_SIZE {
+        return None;
+    }
+    let mut json_area = vec![0u8; header_size as usize - LUKS2_BINARY_HEADER_SIZE];
+    body.read_exact(&mut json_area).ok()?;
+
+    let checksum_algorithm = c_string(&binary[72..104]);
+    let stored = &binary[LUKS2_CHECKSUM_OFFSET..LUKS2_CHECKSUM_OFFSET + LUKS2_CHECKSUM_SIZE];
+    let (checksum, checksum_valid) = match checksum(&checksum_algorithm, &binary, &json_area) {
+        Some(digest) => (
+            to_hex(&stored[..digest.len()]),
+            stored[..digest.len()] == digest,
+        ),
+        None => (to_hex(stored), false),
+    };
+    let end = json_area
+        .iter()
+        .position(|&b| b == 0)
+        .unwrap_or(json_area.len());
+    Some(Luks2HeaderCopy {
+        offset: at,
+        seqid: BigEndian::read_u64(&binary[16..]),
+        header_size,
+        label: optional_string(&binary[24..72]),
+        subsystem: optional_string(&binary[208..256]),
+        uuid: c_string(&binary[168..208]),
+        checksum_algorithm,
+        checksum,
+        checksum_valid,
+        json: serde_json::from_slice(&json_area[..end]).unwrap_or(Value::Null),
+    })
+}
+
+/// Both copies of the LUKS2 header. The secondary one is looked for right after the primary,
+/// or at every possible location when the primary is missing.
+fn read_copies<R: SectorReader + ?Sized>(
+    body: &mut R,
+    offset: u64,
+) -> (Option<Luks2HeaderCopy>, Option<Luks2HeaderCopy>) {
+    let primary = read_copy(body, offset, 0, MAGIC);
+    let secondary = match &primary {
+        Some(primary) => read_copy(body, offset, primary.header_size, SECONDARY_MAGIC),
+        None => LUKS2_SECONDARY_OFFSETS
+            .iter()
+            .find_map(|&at| read_copy(body, offset, at, SECONDARY_MAGIC)),
+    };
+    (primary, secondary)
+}
+
+fn issues(primary: Option<&Luks2HeaderCopy>, secondary: Option<&Luks2HeaderCopy>) -> Vec<String> {
+    let mut issues = Vec::new();
+    match (primary, secondary) {
+        (None, _) => issues.push("Primary header missing".to_string()),
+        (_, None) => issues.push("Secondary header missing".to_string()),
+        _ => {}
+    }
+    for copy in primary.iter().chain(secondary.iter()) {
+        if !copy.checksum_valid {
+            issues.push(format!(
+                "Header at offset 0x{:x} fails its {} checksum",
+                copy.offset, copy.checksum_algorithm
+            ));
+        }
+        if copy.json.is_null() {
+            issues.push(format!(
+                "Header at offset 0x{:x} has unreadable JSON metadata",
+                copy.offset
+            ));
+        }
+    }
+    if let (Some(primary), Some(secondary)) = (primary, secondary) {
+        if primary.seqid != secondary.seqid {
+            issues.push(format!(
+                "Sequence ids differ: {} (primary) and {} (secondary)",
+                primary.seqid, secondary.seqid
+            ));
+        }
+        if primary.uuid != secondary.uuid {
+            issues.push("UUIDs differ between the primary and secondary headers".to_string());
+        }
+        if primary.json != secondary.json {
+            issues.push("JSON metadata differs between the primary and secondary headers".into());
+        }
+    }
+    issues
+}
+
+fn parse_keyslot(id: u64, slot: &Value) -> LuksKeyslot {
+    let kdf = &slot["kdf"];
+    let area = &slot["area"];
+    LuksKeyslot {
+        id,
+        active: true,
+        kdf: json_string(&kdf["type"]),
+        iterations: json_u64(&kdf["iterations"]),
+        key_size: json_u64(&slot["key_size"]),
+        kdf_hash: json_string(&kdf["hash"]),
+        time: json_u64(&kdf["time"]),
+        memory: json_u64(&kdf["memory"]),
+        cpus: json_u64(&kdf["cpus"]),
+        area_encryption: json_string(&area["encryption"]),
+        area_offset: json_u64(&area["offset"]),
+        area_size: json_u64(&area["size"]),
+    }
+}
+
+fn parse_luks2(
+    copy: &Luks2HeaderCopy,
+    primary: Option<Luks2HeaderCopy>,
+    secondary: Option<Luks2HeaderCopy>,
+) -> LuksHeader {
+    let json = &copy.json;
+    let keyslots: Vec<LuksKeyslot> = json_objects(&json["keyslots"])
+        .into_iter()
+        .map(|(id, slot)| parse_keyslot(id, slot))
+        .collect();
+    let segments: Vec<Luks2Segment> = json_objects(&json["segments"])
+        .into_iter()
+        .map(|(id, segment)| Luks2Segment {
+            id,
+            kind: json_string(&segment["type"]).unwrap_or_default(),
+            offset: json_u64(&segment["offset"]).unwrap_or(0),
+            size: json_u64(&segment["size"]),
+            encryption: json_string(&segment["encryption"]),
+            sector_size: json_u64(&segment["sector_size"]),
+        })
+        .collect();
+    let tokens = json_objects(&json["tokens"])
+        .into_iter()
+        .map(|(id, token)| Luks2Token {
+            id,
+            kind: json_string(&token["type"]).unwrap_or_default(),
+            keyslots: json_ids(&token["keyslots"]),
+        })
+        .collect();
+    let digests = json_objects(&json["digests"])
+        .into_iter()
+        .map(|(id, digest)| Luks2Digest {
+            id,
+            kind: json_string(&digest["type"]).unwrap_or_default(),
+            hash: json_string(&digest["hash"]),
+            iterations: json_u64(&digest["iterations"]),
+            keyslots: json_ids(&digest["keyslots"]),
+            segments: json_ids(&digest["segments"]),
+        })
+        .collect();
+    let data = segments.iter().find(|segment| segment.kind == "crypt");
+
+    let issues = issues(primary.as_ref(), secondary.as_ref());
+    for issue in &issues {
+        warn!("LUKS2 volume {}: {}", copy.uuid, issue);
+    }
+    LuksHeader {
+        version: 2,
+        uuid: copy.uuid.clone(),
+        label: copy.label.clone(),
+        cipher: data
+            .and_then(|segment| segment.encryption.clone())
+            .unwrap_or_else(|| "unknown".to_string()),
+        hash: keyslots.iter().find_map(|slot| slot.kdf_hash.clone()),
+        key_bytes: keyslots.iter().find_map(|slot| slot.key_size),
+        payload_offset: data
+            .or(segments.first())
+            .map_or(0, |segment| segment.offset),
+        keyslots,
+        luks2: Some(Luks2Metadata {
+            headers: primary.into_iter().chain(secondary).collect(),
+            segments,
+            tokens,
+            digests,
+            json_size: json_u64(&json["config"]["json_size"]),
+            keyslots_size: json_u64(&json["config"]["keyslots_size"]),
+            issues,
+        }),
+    }
+}
+
+/// Parse the LUKS header at `offset`, if there is one. With `typed`, the partition is known
+/// to hold LUKS: a LUKS2 volume whose primary header was overwritten is still found through
+/// its secondary header.
+pub fn parse<R: SectorReader + ?Sized>(
+    body: &mut R,
+    offset: u64,
+    typed: bool,
+) -> Option<LuksHeader> {
+    let mut header = [0u8; LUKS1_HEADER_SIZE];
+    body.seek(SeekFrom::Start(offset)).ok()?;
+    body.read_exact(&mut header).ok()?;
+    let has_magic = &header[0..6] == MAGIC;
+    if has_magic && BigEndian::read_u16(&header[6..]) == 1 {
+        return Some(parse_luks1(&header));
+    }
+    if !has_magic && !typed {
+        return None;
+    }
+    let (primary, secondary) = read_copies(body, offset);
+    // Like cryptsetup, trust the valid copy with the highest sequence id.
+    let copy = [&primary, &secondary]
+        .into_iter()
+        .flatten()
+        .filter(|copy| !copy.json.is_null())
+        .max_by_key(|copy| (copy.checksum_valid, copy.seqid))?
+        .clone();
+    Some(parse_luks2(&copy, primary, secondary))
+}
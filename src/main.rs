@@ -1,50 +1,469 @@
 use clap::*;
 use exhume_body::Body;
+use exhume_partitions::builder::{DiskImageBuilder, DiskLayout};
+use exhume_partitions::decrypt::{unlock, BitLockerKey, BitLockerReader};
+use exhume_partitions::encryption::Encryption;
+use exhume_partitions::export::{exporter_for, exporters};
+use exhume_partitions::extent::{PartitionExtent, PartitionSelector};
+use exhume_partitions::extract::{extract_partition, ExtractOptions};
+use exhume_partitions::mmls::Units;
+use exhume_partitions::nested::DiscoveryOptions;
+use exhume_partitions::reader::skip_later_segments;
+use exhume_partitions::repair::plan_gpt_repair;
+use exhume_partitions::report::{self, PartitionsReport};
+use exhume_partitions::types;
 use exhume_partitions::Partitions;
-use log::{debug, error};
+use indicatif::{ProgressBar, ProgressStyle};
+use log::{debug, error, info, warn};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::HashSet;
 use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
 
-fn process_file(
-    file_path: &str,
-    format: &str,
-    json: bool,
-    output: Option<&String>,
+/// Settings shared by every evidence file of a run.
+struct RunOptions<'a> {
+    format: &'a str,
+    format_out: &'a str, // table, raw-json, mmls or the name of an exporter
+    units: Units,
     bootloader: bool,
-) {
-    let mut body = Body::new(file_path.to_string(), format);
-    debug!("Created Body from '{}'.", file_path);
-    debug!("Discovering partitions.");
-    match Partitions::new(&mut body) {
+    hash: bool,
+    entropy: Option<u64>, // Block size of the entropy profiles, when requested
+    discovery: DiscoveryOptions,
+}
+
+/// One line of a batch report.
+#[derive(Serialize)]
+struct EvidenceReport<'a> {
+    evidence: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    partitions: Option<PartitionsReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<&'a str>,
+}
+
+fn discover(file_path: &str, options: &RunOptions) -> Result<Partitions, String> {
+    // Body::new exits the whole process on unreadable evidence, check it first.
+    fs::File::open(file_path).map_err(|err| format!("Could not open the evidence: {}", err))?;
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let mut body = Body::new(file_path.to_string(), options.format);
+        debug!("Created Body from '{}'.", file_path);
+        debug!("Discovering partitions.");
+        let mut partitions = Partitions::with_options(&mut body, &options.discovery)
+            .map_err(|err| format!("Could not discover partitions: {:?}", err))?;
+        #[cfg(feature = "disassembly")]
+        if options.bootloader {
+            partitions.bootcode = partitions
+                .mbr
+                .as_ref()
+                .map(|mbr| mbr.disassemble_bootloader());
+        }
+        if options.hash {
+            match partitions.compute_hashes(&mut body) {
+                Ok(hashes) => partitions.hashes = Some(hashes),
+                Err(err) => error!("Could not hash the partitions: {:?}", err),
+            }
+        }
+        if let Some(block_size) = options.entropy {
+            match partitions.compute_entropy(&mut body, block_size) {
+                Ok(entropy) => partitions.entropy = Some(entropy),
+                Err(err) => error!("Could not profile the entropy: {:?}", err),
+            }
+        }
+        Ok(partitions)
+    }));
+    result.unwrap_or_else(|payload| {
+        let reason = payload
+            .downcast_ref::<String>()
+            .map(String::as_str)
+            .or_else(|| payload.downcast_ref::<&str>().copied())
+            .unwrap_or("unknown reason");
+        Err(format!("The parser panicked on this evidence: {}", reason))
+    })
+}
+
+fn render(partitions: &Partitions, evidence: &str, options: &RunOptions) -> String {
+    match options.format_out {
+        "raw-json" => serde_json::to_string_pretty(partitions).unwrap(),
+        "mmls" => partitions.print_mmls(options.units),
+        "table" => partitions.print_info(options.bootloader),
+        name => match exporter_for(name).map(|exporter| exporter.export(partitions, evidence)) {
+            Some(Ok(document)) => document,
+            Some(Err(err)) => {
+                error!("Could not export to {}: {:?}", name, err);
+                String::new()
+            }
+            None => {
+                error!("Unknown output format '{}'", name);
+                String::new()
+            }
+        },
+    }
+}
+
+fn process_file(file_path: &str, options: &RunOptions, output: Option<&String>) {
+    match discover(file_path, options) {
         Ok(partitions) => {
-            let output_str = if json {
-                serde_json::to_string_pretty(&partitions).unwrap()
-            } else {
-                partitions.print_info(bootloader)
-            };
+            let output_str = render(&partitions, file_path, options);
             if let Some(output_path) = output {
-                fs::write(output_path, output_str).unwrap();
+                if let Err(err) = fs::write(output_path, output_str) {
+                    error!("Could not write '{}': {}", output_path, err);
+                }
             } else {
                 println!("{}", output_str);
             }
         }
+        Err(err) => {
+            error!("{}", err);
+        }
+    }
+}
+
+/// Expand glob patterns into evidence paths, keeping the command line order.
+/// Patterns that match nothing are kept as-is so that they are reported as failures.
+/// Later segments of split images (E02, E03...) are dropped when their first segment is
+/// in the input too, since it opens them all.
+fn expand_bodies(patterns: &[String]) -> Vec<String> {
+    let mut files: Vec<String> = Vec::new();
+    for pattern in patterns {
+        let mut matched: Vec<String> = match glob::glob(pattern) {
+            Ok(paths) => paths
+                .filter_map(Result::ok)
+                .filter(|path| path.is_file())
+                .map(|path| path.to_string_lossy().to_string())
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+        if matched.is_empty() {
+            matched.push(pattern.clone());
+        }
+        for file in matched {
+            if !files.contains(&file) {
+                files.push(file);
+            }
+        }
+    }
+    let (files, later) = skip_later_segments(&files);
+    for file in &later {
+        info!(
+            "Skipping '{}', a segment of a split image already in the input.",
+            file
+        );
+    }
+    files
+}
+
+/// Process many evidence files in parallel. Failures are reported per file.
+/// Results go either to one file per evidence in `output_dir`, or as JSON Lines
+/// (one object keyed by evidence path per line) to `output` or stdout.
+fn process_batch(
+    files: &[String],
+    options: &RunOptions,
+    output: Option<&String>,
+    output_dir: Option<&String>,
+    jobs: usize,
+) {
+    let pool = match rayon::ThreadPoolBuilder::new().num_threads(jobs).build() {
+        Ok(pool) => pool,
+        Err(err) => {
+            error!("Could not start the worker threads: {}", err);
+            return;
+        }
+    };
+    // Panics are turned into per-file errors, do not let the default hook spam stderr.
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let results: Vec<(&String, Result<Partitions, String>)> = pool.install(|| {
+        files
+            .par_iter()
+            .map(|file| {
+                info!("Processing '{}'.", file);
+                (file, discover(file, options))
+            })
+            .collect()
+    });
+    panic::set_hook(default_hook);
+
+    let failures = results.iter().filter(|(_, result)| result.is_err()).count();
+    if let Some(dir) = output_dir {
+        if let Err(err) = fs::create_dir_all(dir) {
+            error!("Could not create '{}': {}", dir, err);
+            return;
+        }
+        let extension = match options.format_out {
+            "json" | "compact-json" | "raw-json" => "json",
+            "csv" => "csv",
+            "dfxml" => "xml",
+            "bodyfile" => "body",
+            _ => "txt",
+        };
+        let mut names = HashSet::new();
+        for (file, result) in &results {
+            match result {
+                Ok(partitions) => {
+                    let name = file.trim_start_matches('/').replace(['/', '\\', ':'], "_");
+                    // Different paths can flatten to the same name, number the later ones.
+                    let mut unique = name.clone();
+                    let mut n = 1;
+                    while !names.insert(unique.clone()) {
+                        n += 1;
+                        unique = format!("{}-{}", name, n);
+                    }
+                    if n > 1 {
+                        warn!("'{}' is reported as '{}.{}'.", file, unique, extension);
+                    }
+                    let path = Path::new(dir).join(format!("{}.{}", unique, extension));
+                    if let Err(err) = fs::write(&path, render(partitions, file, options)) {
+                        error!("Could not write '{}': {}", path.display(), err);
+                    }
+                }
+                Err(err) => error!("'{}': {}", file, err),
+            }
+        }
+    } else {
+        let mut lines = String::new();
+        for (file, result) in &results {
+            let report = EvidenceReport {
+                evidence: file,
+                partitions: result
+                    .as_ref()
+                    .ok()
+                    .map(|partitions| PartitionsReport::new(partitions, Some(file))),
+                error: result.as_ref().err().map(String::as_str),
+            };
+            lines.push_str(&serde_json::to_string(&report).unwrap());
+            lines.push('\n');
+        }
+        match output {
+            Some(output_path) => {
+                if let Err(err) = fs::write(output_path, lines) {
+                    error!("Could not write '{}': {}", output_path, err);
+                }
+            }
+            None => print!("{}", lines),
+        }
+    }
+    info!(
+        "Processed {} evidence files, {} failed.",
+        results.len(),
+        failures
+    );
+}
+
+fn extract_file(file_path: &str, format: &str, matches: &ArgMatches) {
+    let selector = PartitionSelector::parse(matches.get_one::<String>("partition").unwrap());
+    let output = matches.get_one::<String>("output").unwrap();
+    // Compare the files rather than their paths, which may differ for the same file.
+    if Path::new(output).exists()
+        && fs::canonicalize(output).ok() == fs::canonicalize(file_path).ok()
+    {
+        error!("Refusing to extract over the evidence itself.");
+        return;
+    }
+    let options = ExtractOptions {
+        resume: matches.get_flag("resume"),
+        sparse: matches.get_flag("sparse"),
+        hash: matches.get_flag("hash"),
+    };
+
+    let key = if let Some(fvek) = matches.get_one::<Vec<u8>>("fvek") {
+        Some(BitLockerKey::Fvek(fvek.clone()))
+    } else if matches.get_flag("clear_key") {
+        Some(BitLockerKey::ClearKey)
+    } else {
+        matches
+            .get_one::<String>("recovery_password")
+            .map(|password| BitLockerKey::RecoveryPassword(password.clone()))
+    };
+
+    let mut body = Body::new(file_path.to_string(), format);
+    let discovery = DiscoveryOptions {
+        offset: *matches.get_one::<u64>("offset").unwrap(),
+        // The BitLocker metadata is only needed to decrypt.
+        encryption: key.is_some(),
+        ..Default::default()
+    };
+    let partitions = match Partitions::with_options(&mut body, &discovery) {
+        Ok(partitions) => partitions,
         Err(err) => {
             error!("Could not discover partitions: {:?}", err);
+            return;
+        }
+    };
+    let extent = match partitions.select(&selector) {
+        Some(extent) => extent,
+        None => {
+            error!("No {} found.", selector);
+            return;
+        }
+    };
+    info!(
+        "Extracting partition #{} ({}) from byte 0x{:x}, {} bytes.",
+        extent.index, extent.description, extent.first_byte_addr, extent.size_bytes
+    );
+
+    let mut decrypted = None;
+    if let Some(key) = key {
+        let info = match &extent.encryption {
+            Some(Encryption::BitLocker(info)) => info,
+            _ => {
+                error!("Partition #{} is not a BitLocker volume.", extent.index);
+                return;
+            }
+        };
+        let volume = unlock(info, &key).and_then(|fvek| {
+            BitLockerReader::new(
+                &mut body,
+                extent.first_byte_addr,
+                extent.size_bytes,
+                info,
+                &fvek,
+            )
+        });
+        match volume {
+            Ok(volume) => {
+                info!("BitLocker volume unlocked, extracting the decrypted data.");
+                decrypted = Some(volume);
+            }
+            Err(err) => {
+                error!("Could not unlock the BitLocker volume: {}", err);
+                return;
+            }
+        }
+    }
+
+    let bar = ProgressBar::new(extent.size_bytes);
+    bar.set_style(
+        ProgressStyle::with_template("{bar:40} {bytes}/{total_bytes} ({bytes_per_sec}, eta {eta})")
+            .unwrap(),
+    );
+    let progress = |done| bar.set_position(done);
+    let result = match decrypted.as_mut() {
+        // The reader starts at the first byte of the volume.
+        Some(volume) => {
+            let volume_extent = PartitionExtent {
+                first_byte_addr: 0,
+                ..extent.clone()
+            };
+            extract_partition(
+                volume,
+                &volume_extent,
+                Path::new(output),
+                &options,
+                progress,
+            )
+        }
+        None => extract_partition(&mut body, &extent, Path::new(output), &options, progress),
+    };
+    match result {
+        Ok(report) => {
+            bar.finish();
+            info!("Partition written to '{}'.", output);
+            if let Some(hashes) = report.hashes {
+                println!("MD5:     {}", hashes.md5);
+                println!("SHA-1:   {}", hashes.sha1);
+                println!("SHA-256: {}", hashes.sha256);
+            }
+        }
+        Err(err) => {
+            bar.abandon();
+            error!("Could not extract the partition: {:?}", err);
         }
     }
 }
 
+fn build_image(matches: &ArgMatches) {
+    let layout_path = matches.get_one::<String>("layout").unwrap();
+    let output = matches.get_one::<String>("output").unwrap();
+    let layout: DiskLayout = match fs::read_to_string(layout_path)
+        .map_err(|err| err.to_string())
+        .and_then(|text| serde_json::from_str(&text).map_err(|err| err.to_string()))
+    {
+        Ok(layout) => layout,
+        Err(err) => {
+            error!("Could not read the layout '{}': {}", layout_path, err);
+            return;
+        }
+    };
+    match DiskImageBuilder::from_layout(layout).write_to(Path::new(output)) {
+        Ok(()) => info!("Disk image written to '{}'.", output),
+        Err(err) => error!("Could not build the disk image: {:?}", err),
+    }
+}
+
+fn repair_gpt(file_path: &str, format: &str, matches: &ArgMatches) {
+    let mut body = Body::new(file_path.to_string(), format);
+    let plan = match plan_gpt_repair(&mut body) {
+        Ok(Some(plan)) => plan,
+        Ok(None) => {
+            println!("Both GPT copies are intact, nothing to repair.");
+            return;
+        }
+        Err(err) => {
+            error!("Could not plan a GPT repair: {:?}", err);
+            return;
+        }
+    };
+    println!("{}", plan.print_info());
+    if let Some(overlay) = matches.get_one::<String>("overlay") {
+        match plan.write_overlay(Path::new(file_path), Path::new(overlay)) {
+            Ok(()) => info!("Repair overlay written to '{}'.", overlay),
+            Err(err) => error!("Could not write the overlay: {:?}", err),
+        }
+    }
+    if let Some(output) = matches.get_one::<String>("output") {
+        match plan.write_copy(&mut body, Path::new(file_path), Path::new(output)) {
+            Ok(()) => info!("Repaired copy written to '{}'.", output),
+            Err(err) => error!("Could not write the repaired copy: {:?}", err),
+        }
+    }
+}
+
+/// Key material given in hexadecimal.
+fn parse_hex(value: &str) -> Result<Vec<u8>, String> {
+    if !value.is_ascii() || !value.len().is_multiple_of(2) {
+        return Err("expected an even number of hex digits".to_string());
+    }
+    (0..value.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&value[i..i + 2], 16).map_err(|err| err.to_string()))
+        .collect()
+}
+
+/// Byte offset given in decimal or, prefixed with 0x, in hexadecimal.
+fn parse_offset(value: &str) -> Result<u64, String> {
+    let parsed = match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => value.parse::<u64>(),
+    };
+    parsed.map_err(|err| format!("invalid offset '{}': {}", value, err))
+}
+
+fn format_out_values() -> Vec<&'static str> {
+    let mut values = vec!["table", "raw-json", "mmls"];
+    values.extend(exporters().iter().map(|exporter| exporter.name()));
+    values
+}
+
 fn main() {
     let matches = Command::new("exhume_partitions")
         .version(crate_version!())
         .author(crate_authors!())
         .about("Exhume the partitions from a given body of data.")
+        .subcommand_negates_reqs(true)
         .arg(
             Arg::new("body")
                 .short('b')
                 .long("body")
                 .value_parser(value_parser!(String))
                 .required(true)
-                .help("The path to the body to exhume."),
+                .num_args(1..)
+                .action(ArgAction::Append)
+                .help("The path(s) or glob pattern(s) of the bodies to exhume."),
         )
         .arg(
             Arg::new("format")
@@ -56,25 +475,61 @@ fn main() {
                     "The format of the file, either 'raw','vmdk', 'aff', 'aff4', 'ewf', or 'auto'.",
                 ),
         )
+        .arg(
+            Arg::new("output_dir")
+                .long("output-dir")
+                .value_parser(value_parser!(String))
+                .help("Batch mode: write one report per evidence file in this directory"),
+        )
+        .arg(
+            Arg::new("jobs")
+                .short('j')
+                .long("jobs")
+                .value_parser(value_parser!(usize))
+                .default_value("0")
+                .help("Batch mode: number of evidence files processed in parallel (0: one per CPU)"),
+        )
         .arg(
             Arg::new("log_level")
                 .short('l')
                 .long("log-level")
                 .value_parser(["error", "warn", "info", "debug", "trace"])
                 .default_value("info")
+                .global(true)
                 .help("Set the log verbosity level"),
         )
         .arg(
             Arg::new("json")
                 .long("json")
                 .action(ArgAction::SetTrue)
-                .help("Display partitions in JSON format"),
+                .help("Display partitions in JSON format (versioned schema, see the schema subcommand)"),
+        )
+        .arg(
+            Arg::new("format_out")
+                .long("format-out")
+                .value_parser(format_out_values())
+                .conflicts_with_all(["json", "mmls"])
+                .help("Output format: table (default), json, compact-json, raw-json (internal structures), mmls, csv, dfxml or bodyfile (TSK mactime)"),
+        )
+        .arg(
+            Arg::new("mmls")
+                .long("mmls")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("json")
+                .help("Display partitions as a flat, mmls compatible listing"),
+        )
+        .arg(
+            Arg::new("units")
+                .long("units")
+                .value_parser(["sectors", "bytes"])
+                .default_value("sectors")
+                .help("Units of the mmls listing"),
         )
         .arg(
             Arg::new("bootloader")
                 .long("bootloader")
                 .action(ArgAction::SetTrue)
-                .help("Display full MBR and potential EBR with bootstrap code"),
+                .help("Display full MBR and potential EBR with bootstrap code, and add the boot code disassembly to the reports"),
         )
         .arg(
             Arg::new("output")
@@ -82,6 +537,200 @@ fn main() {
                 .value_parser(value_parser!(String))
                 .help("Output file path"),
         )
+        .arg(
+            Arg::new("hash")
+                .long("hash")
+                .action(ArgAction::SetTrue)
+                .help("Hash (MD5, SHA-1, SHA-256) every partition, gap and partition table"),
+        )
+        .arg(
+            Arg::new("entropy")
+                .long("entropy")
+                .action(ArgAction::SetTrue)
+                .help("Profile the entropy of every partition and gap, to spot hidden containers and wipes"),
+        )
+        .arg(
+            Arg::new("entropy_block_size")
+                .long("entropy-block-size")
+                .value_parser(value_parser!(u64))
+                .default_value("65536")
+                .help("Bytes per entropy block with --entropy, at least 4096"),
+        )
+        .arg(
+            Arg::new("inspect")
+                .long("inspect")
+                .action(ArgAction::SetTrue)
+                .help("Also read partition contents: encryption headers, hidden data in reserved areas, GRUB core.img and EFI System Partition files"),
+        )
+        .arg(
+            Arg::new("offset")
+                .long("offset")
+                .value_parser(parse_offset)
+                .default_value("0")
+                .help("Byte offset of the partition tables in the body (decimal or 0x hexadecimal)"),
+        )
+        .arg(
+            Arg::new("nested")
+                .long("nested")
+                .action(ArgAction::SetTrue)
+                .help("Look for partition tables inside partitions"),
+        )
+        .arg(
+            Arg::new("max_depth")
+                .long("max-depth")
+                .value_parser(value_parser!(usize))
+                .default_value("4")
+                .help("Maximum nesting depth followed with --nested"),
+        )
+        .arg(
+            Arg::new("types")
+                .long("types")
+                .value_parser(value_parser!(String))
+                .global(true)
+                .help("JSON table of extra partition types, added to the built-in ones (see the types subcommand)"),
+        )
+        .subcommand(
+            Command::new("schema")
+                .about("Print the JSON Schema of the json and compact-json outputs."),
+        )
+        .subcommand(
+            Command::new("types")
+                .about("Print the partition type table in the format --types reads."),
+        )
+        .subcommand(
+            Command::new("build-image")
+                .about("Build a synthetic raw disk image from a JSON layout.")
+                .arg(
+                    Arg::new("layout")
+                        .short('l')
+                        .long("layout")
+                        .value_parser(value_parser!(String))
+                        .required(true)
+                        .help("Path of the JSON layout (see builder::DiskLayout)."),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .value_parser(value_parser!(String))
+                        .required(true)
+                        .help("Path of the raw image to write."),
+                ),
+        )
+        .subcommand(
+            Command::new("repair-gpt")
+                .about("Plan the rebuild of a damaged GPT copy from the intact one. The evidence is never written to.")
+                .arg(
+                    Arg::new("body")
+                        .short('b')
+                        .long("body")
+                        .value_parser(value_parser!(String))
+                        .required(true)
+                        .help("The path to the body to exhume."),
+                )
+                .arg(
+                    Arg::new("format")
+                        .short('f')
+                        .long("format")
+                        .value_parser(value_parser!(String))
+                        .required(false)
+                        .help("The format of the file, either 'raw','vmdk', 'aff', 'aff4', 'ewf', or 'auto'."),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .value_parser(value_parser!(String))
+                        .help("Write a repaired raw copy of the evidence to this new file."),
+                )
+                .arg(
+                    Arg::new("overlay")
+                        .long("overlay")
+                        .value_parser(value_parser!(String))
+                        .help("Write the sectors to rewrite, as JSON, to this file."),
+                ),
+        )
+        .subcommand(
+            Command::new("extract")
+                .about("Extract one partition to a raw (dd) image file.")
+                .arg(
+                    Arg::new("body")
+                        .short('b')
+                        .long("body")
+                        .value_parser(value_parser!(String))
+                        .required(true)
+                        .help("The path to the body to exhume."),
+                )
+                .arg(
+                    Arg::new("format")
+                        .short('f')
+                        .long("format")
+                        .value_parser(value_parser!(String))
+                        .required(false)
+                        .help("The format of the file, either 'raw','vmdk', 'aff', 'aff4', 'ewf', or 'auto'."),
+                )
+                .arg(
+                    Arg::new("partition")
+                        .short('p')
+                        .long("partition")
+                        .value_parser(value_parser!(String))
+                        .required(true)
+                        .help("The partition to extract: its index, its GUID or its name."),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .value_parser(value_parser!(String))
+                        .required(true)
+                        .help("Path of the raw image to write."),
+                )
+                .arg(
+                    Arg::new("offset")
+                        .long("offset")
+                        .value_parser(parse_offset)
+                        .default_value("0")
+                        .help("Byte offset of the partition tables in the body (decimal or 0x hexadecimal)"),
+                )
+                .arg(
+                    Arg::new("fvek")
+                        .long("fvek")
+                        .value_parser(parse_hex)
+                        .conflicts_with("recovery_password")
+                        .help("Decrypt a BitLocker partition with this FVEK (hex)"),
+                )
+                .arg(
+                    Arg::new("recovery_password")
+                        .long("recovery-password")
+                        .value_parser(value_parser!(String))
+                        .help("Decrypt a BitLocker partition with its 48-digit recovery password"),
+                )
+                .arg(
+                    Arg::new("clear_key")
+                        .long("clear-key")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with_all(["fvek", "recovery_password"])
+                        .help("Decrypt a BitLocker partition whose protection is suspended, with the key left in the clear in its metadata"),
+                )
+                .arg(
+                    Arg::new("resume")
+                        .long("resume")
+                        .action(ArgAction::SetTrue)
+                        .help("Resume an interrupted extraction into an existing output file"),
+                )
+                .arg(
+                    Arg::new("sparse")
+                        .long("sparse")
+                        .action(ArgAction::SetTrue)
+                        .help("Leave holes in the output instead of writing zero-filled blocks"),
+                )
+                .arg(
+                    Arg::new("hash")
+                        .long("hash")
+                        .action(ArgAction::SetTrue)
+                        .help("Compute MD5, SHA-1 and SHA-256 of the extracted data"),
+                ),
+        )
         .get_matches();
 
     let log_level_str = matches.get_one::<String>("log_level").unwrap();
@@ -95,11 +744,107 @@ fn main() {
     };
     env_logger::Builder::new().filter_level(level_filter).init();
 
-    let file_path = matches.get_one::<String>("body").unwrap();
+    if let Some(path) = matches.get_one::<String>("types") {
+        if let Err(err) = types::register_file(Path::new(path)) {
+            error!("Could not load the partition types: {}", err);
+            return;
+        }
+    }
+
     let auto = String::from("auto");
-    let format = matches.get_one::<String>("format").unwrap_or(&auto);
-    let json = matches.get_flag("json");
+    if matches.subcommand_matches("types").is_some() {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&types::registry().to_table()).unwrap()
+        );
+        return;
+    }
+    if matches.subcommand_matches("schema").is_some() {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&report::json_schema()).unwrap()
+        );
+        return;
+    }
+    if let Some(build_matches) = matches.subcommand_matches("build-image") {
+        build_image(build_matches);
+        return;
+    }
+    if let Some(repair_matches) = matches.subcommand_matches("repair-gpt") {
+        let file_path = repair_matches.get_one::<String>("body").unwrap();
+        let format = repair_matches.get_one::<String>("format").unwrap_or(&auto);
+        repair_gpt(file_path, format, repair_matches);
+        return;
+    }
+    if let Some(extract_matches) = matches.subcommand_matches("extract") {
+        let file_path = extract_matches.get_one::<String>("body").unwrap();
+        let format = extract_matches.get_one::<String>("format").unwrap_or(&auto);
+        extract_file(file_path, format, extract_matches);
+        return;
+    }
+
+    let patterns: Vec<String> = matches
+        .get_many::<String>("body")
+        .unwrap()
+        .cloned()
+        .collect();
+    let files = expand_bodies(&patterns);
+    let format_out = if matches.get_flag("json") {
+        "json"
+    } else if matches.get_flag("mmls") {
+        "mmls"
+    } else {
+        matches
+            .get_one::<String>("format_out")
+            .map_or("table", String::as_str)
+    };
+    let options = RunOptions {
+        format: matches.get_one::<String>("format").unwrap_or(&auto),
+        format_out,
+        units: match matches.get_one::<String>("units").unwrap().as_str() {
+            "bytes" => Units::Bytes,
+            _ => Units::Sectors,
+        },
+        bootloader: matches.get_flag("bootloader"),
+        hash: matches.get_flag("hash"),
+        entropy: matches
+            .get_flag("entropy")
+            .then(|| *matches.get_one::<u64>("entropy_block_size").unwrap()),
+        discovery: {
+            let discovery = DiscoveryOptions {
+                offset: *matches.get_one::<u64>("offset").unwrap(),
+                nested: matches.get_flag("nested"),
+                max_depth: *matches.get_one::<usize>("max_depth").unwrap(),
+                // Encryption headers explain random content.
+                encryption: matches.get_flag("entropy"),
+                ..Default::default()
+            };
+            if matches.get_flag("inspect") {
+                discovery.inspect_all()
+            } else {
+                discovery
+            }
+        },
+    };
     let output = matches.get_one::<String>("output");
-    let bootloader = matches.get_flag("bootloader");
-    process_file(file_path, format, json, output, bootloader);
+    let output_dir = matches.get_one::<String>("output_dir");
+    if files.len() == 1 && output_dir.is_none() {
+        process_file(&files[0], &options, output);
+    } else {
+        // Without --output-dir, the reports of a batch are JSON Lines.
+        let explicit =
+            matches.get_flag("mmls") || matches.get_one::<String>("format_out").is_some();
+        if output_dir.is_none()
+            && explicit
+            && !matches!(options.format_out, "json" | "compact-json")
+        {
+            error!(
+                "Batch reports are JSON Lines, use --output-dir for one {} file per evidence.",
+                options.format_out
+            );
+            return;
+        }
+        let jobs = *matches.get_one::<usize>("jobs").unwrap();
+        process_batch(&files, &options, output, output_dir, jobs);
+    }
 }
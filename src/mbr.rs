@@ -1,9 +1,12 @@
-use byteorder::{LittleEndian, ReadBytesExt};
-use capstone::prelude::*;
+#[cfg(feature = "disassembly")]
+use crate::bootcode::BootCode;
+use crate::encryption::Encryption;
+use crate::types::{self, PartitionType};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use log::debug;
 use prettytable::{Cell, Row, Table};
 use serde::{Deserialize, Serialize};
-use std::io::{Cursor, Read};
+use std::io::{Cursor, Read, Write};
 
 const DEFAULT_SECTOR_SIZE: usize = 512;
 #[derive(Serialize, Deserialize, Debug, Default, Clone)]
@@ -16,180 +19,26 @@ pub struct MBRPartitionEntry {
     pub start_lba: u32,
     pub size_sectors: u32,
     pub sector_size: usize,
-    pub first_byte_addr: usize,
+    pub first_byte_addr: usize, // Relative to the table, `Partitions::base_offset` excluded
     pub description: String,
     pub fvek: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encryption: Option<Encryption>, // Filled by `Partitions::detect_encryption`
 }
 
 impl MBRPartitionEntry {
-    pub fn partition_type_description(&self) -> &str {
-        match self.partition_type {
-            0x00 => "Unused",
-            0x01 => "FAT12",
-            0x02 => "XENIX root",
-            0x03 => "XENIX usr",
-            0x04 => "FAT16 <32M",
-            0x05 => "Extended",
-            0x06 => "FAT16B",
-            0x07 => "NTFS/exFAT/IFS/HPFS",
-            0x08 => "AIX boot/split/QNX",
-            0x09 => "AIX data/boot/QNX",
-            0x0A => "OS/2 Boot Manager/Coherent Swap",
-            0x0B => "W95 FAT32",
-            0x0C => "W95 FAT32 (LBA)",
-            0x0E => "W95 FAT16 (LBA)",
-            0x0F => "W95 Ext'd (LBA)",
-            0x10 => "Reserved",
-            0x11 => "Hidden FAT12",
-            0x12 => "Hibernation/Service FS/Rescue & Recovery",
-            0x14 => "Logical FAT12/FAT16/Hidden FAT16/Omega",
-            0x15 => "Hidden Extended/Swap",
-            0x16 => "Hidden FAT16B",
-            0x17 => "Hidden IFS/HPFS/NTFS/exFAT",
-            0x18 => "AST Zero Volt Suspend/SmartSleep",
-            0x19 => "Willowtech Photon coS",
-            0x1B => "Hidden FAT32",
-            0x1C => "Hidden FAT32 with LBA/ASUS eRecovery",
-            0x1E => "Hidden FAT16 with LBA",
-            0x20 => "Windows Mobile Update XIP/Willowsoft OFS1",
-            0x21 => "HP Volume Expansion",
-            0x22 => "Oxygen Extended Partition Table",
-            0x23 => "Windows Mobile boot XIP",
-            0x27 => "Windows Recovery Environment/RooterBOOT",
-            0x2A => "AtheOS ForthOS",
-            0x2B => "SyllableSecure (SylStor)",
-            0x31 => "Reserved",
-            0x33 => "Reserved",
-            0x34 => "Reserved",
-            0x36 => "Reserved",
-            0x38 => "THEOS v3.2",
-            0x39 => "Plan 9/TheOS v4 spanned",
-            0x3A => "THEOS v4 (4 GB)",
-            0x3B => "THEOS v4 extended",
-            0x3C => "PartitionMagic PqRP",
-            0x3D => "PartitionMagic Hidden NetWare",
-            0x40 => "PICK Systems/Venix/Venix 80286",
-            0x41 => "Personal RISC Boot/Linux/PowerPC",
-            0x42 => "Secure File System/Old Linux Swap/Dynamic Extended",
-            0x43 => "Old Linux Native",
-            0x44 => "Wildfile GoBack",
-            0x45 => "Priam/EUMEL/ELAN",
-            0x46 => "EUMEL/ELAN",
-            0x47 => "EUMEL/ELAN",
-            0x48 => "EUMEL/ELAN and ERGOS L3",
-            0x4C => "Aos (A2) File System",
-            0x4D => "Primary QNX POSIX",
-            0x4E => "Secondary QNX POSIX",
-            0x4F => "Tertiary QNX POSIX",
-            0x50 => "Alternative Native/LynxOS/Novell Operations",
-            0x51 => "Novell Read-Write/Kurt Skauen Toggle",
-            0x52 => "System V/AT, V/386",
-            0x53 => "Disk Manager 6 Auxiliary 3",
-            0x54 => "Disk Manager 6 Dynamic Drive Overlay",
-            0x55 => "EZ-Drive INT 13h Redirector Volume",
-            0x56 => "Logical FAT12/FAT16/EZ-BIOS/VFeature",
-            0x57 => "DrivePro/VNDI",
-            0x5C => "Priam EDisk",
-            0x63 => "SCO Unix/ISC/UnixWare/BSD",
-            0x64 => "SpeedStor Hidden FAT16/Novell NetWare/PC-ARMOUR",
-            0x65 => "Novell NetWare File System 386",
-            0x66 => "Novell Storage Management Services",
-            0x67 => "Novell Wolf Mountain Cluster",
-            0x68 => "Reserved for DR-DOS",
-            0x69 => "Novell NSS/NetWare 5",
-            0x70 => "DiskSecure Multiboot",
-            0x71 => "Reserved",
-            0x72 => "Unix V7/x86",
-            0x73 => "Reserved",
-            0x75 => "IBM PC/IX",
-            0x76 => "SpeedStor Hidden read-only FAT16B",
-            0x77 => "Novell VNDI/M2FS/M2CS",
-            0x78 => "XOSL Bootloader",
-            0x80 => "Minix 1.1-1.4a",
-            0x81 => "Minix 1.4b+",
-            0x82 => "Linux Swap",
-            0x83 => "Linux/GNU Hurd",
-            0x84 => "APM Hibernation/Hidden FAT16",
-            0x85 => "Linux Extended",
-            0x86 => "Microsoft Fault-tolerant FAT16B mirrored",
-            0x87 => "Microsoft Fault-tolerant HPFS/NTFS mirrored",
-            0x88 => "Linux Plaintext Partition Table",
-            0x8A => "AirBoot Boot Manager",
-            0x8B => "Legacy FAT32 Mirrored (0Bh)",
-            0x8C => "Legacy FAT32 Mirrored (0Ch)",
-            0x8E => "Linux LVM",
-            0x93 => "Amoeba/Amoeba Native",
-            0x94 => "Amoeba Bad Block Table",
-            0x95 => "EXOPC Native",
-            0x96 => "ISO-9660",
-            0x99 => "Early Unix",
-            0x9E => "VSTa/ForthOS",
-            0x9F => "BSD/OS 3.0+",
-            0xA0 => "Hibernate Partition",
-            0xA1 => "HP Volume Expansion/Hibernate Partition",
-            0xA3 => "HP Volume Expansion",
-            0xA4 => "HP Volume Expansion",
-            0xA6 => "HP Volume Expansion",
-            0xA7 => "NeXTSTEP",
-            0xA8 => "Apple Darwin/Mac OS X UFS",
-            0xAB => "Apple Darwin/Mac OS X Boot",
-            0xAC => "Apple RAID",
-            0xAD => "RISC OS FileCore",
-            0xAE => "ShagOS File System",
-            0xAF => "Mac OS X HFS",
-            0xB0 => "Boot-Star Dummy Partition",
-            0xB1 => "HP Volume Expansion/QNX Neutrino",
-            0xB2 => "QNX Neutrino Power-safe File System",
-            0xB3 => "HP Volume Expansion/QNX Neutrino",
-            0xB4 => "HP Volume Expansion",
-            0xB6 => "Corrupted FAT16B Mirrored Master",
-            0xB7 => "Corrupted HPFS/NTFS Mirrored Master",
-            0xB8 => "BSDI Swap/Native",
-            0xBB => "Acronis True Image OEM Secure Zone",
-            0xBD => "BonnyDOS/286",
-            0xBE => "Solaris 8 Boot",
-            0xBF => "Solaris x86",
-            0xC1 => "DR DOS 6.0+ Secured FAT12",
-            0xC2 => "Power Boot Hidden FS",
-            0xC3 => "Power Boot Hidden Swap",
-            0xC4 => "DR DOS 6.0+ Secured FAT16",
-            0xC5 => "DR DOS 6.0+ Secured Extended",
-            0xC6 => "DR DOS 6.0+ Secured FAT16B",
-            0xCB => "Caldera DR-DOS 7.0x Secured FAT32",
-            0xCC => "Caldera DR-DOS 7.0x Secured FAT32",
-            0xCE => "Caldera DR-DOS 7.0x Secured FAT16B",
-            0xCF => "Caldera DR-DOS 7.0x Secured Extended",
-            0xD1 => "Novell Multiuser DOS Secured FAT12",
-            0xD4 => "Novell Multiuser DOS Secured FAT16",
-            0xD5 => "Novell Multiuser DOS Secured Extended",
-            0xD6 => "Novell Multiuser DOS Secured FAT16B",
-            0xD8 => "CP/M-86",
-            0xDB => "CP/M-86/Concurrent DOS/FAT32 System Restore",
-            0xDE => "Dell FAT16 Utility/Diagnostic",
-            0xDF => "BootIt",
-            0xE0 => "ST AVFS",
-            0xE1 => "SpeedStor FAT12",
-            0xE3 => "SpeedStor Read-only FAT12",
-            0xE4 => "SpeedStor FAT16",
-            0xE5 => "Logical FAT12/FAT16",
-            0xE6 => "SpeedStor Read-only FAT16",
-            0xEB => "BFS (BeOS/Haiku)",
-            0xEC => "SkyOS SkyFS",
-            0xEE => "Protective MBR",
-            0xF0 => "PA-RISC Linux Boot Loader",
-            0xF2 => "Secondary FAT12",
-            0xF4 => "SpeedStor FAT16B",
-            0xF5 => "Prologue MD0-MD9",
-            0xF7 => "EFAT/DDRdrive Solid State FS",
-            0xF9 => "pCache ext2/ext3",
-            0xFB => "VMware VMFS",
-            0xFC => "VMware Swap/VMKCORE",
-            0xFD => "Linux RAID superblock",
-            0xFE => "PS/2 IML/Old Linux LVM",
-            0xFF => "XENIX bad block table",
-            _ => "Unknown",
-        }
+    /// Description of the type, from the partition type registry.
+    pub fn partition_type_description(&self) -> String {
+        types::mbr_type(self.partition_type)
+            .map(|partition_type| partition_type.description)
+            .unwrap_or_else(|| "Unknown".to_string())
+    }
+
+    /// The registry entry of the type: description, OS, role and architecture.
+    pub fn type_info(&self) -> Option<PartitionType> {
+        types::mbr_type(self.partition_type)
     }
+
     fn chs_tuple(bytes: [u8; 3]) -> (u16, u8, u8) {
         let head = bytes[0];
         let sector = bytes[1] & 0x3F;
@@ -205,6 +54,20 @@ impl MBRPartitionEntry {
     pub fn _get_first_byte_address(&self) -> usize {
         self.sector_size * self.start_lba as usize
     }
+
+    /// The 16-byte on-disk entry. `start_lba` is written as is: for a logical partition
+    /// parsed from an EBR it is absolute, not relative to the EBR as on disk.
+    pub fn to_bytes(&self) -> [u8; 16] {
+        let mut bytes = [0u8; 16];
+        let mut cursor = Cursor::new(&mut bytes[..]);
+        cursor.write_u8(self.boot_indicator).unwrap();
+        cursor.write_all(&self.start_chs).unwrap();
+        cursor.write_u8(self.partition_type).unwrap();
+        cursor.write_all(&self.end_chs).unwrap();
+        cursor.write_u32::<LittleEndian>(self.start_lba).unwrap();
+        cursor.write_u32::<LittleEndian>(self.size_sectors).unwrap();
+        bytes
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -212,17 +75,24 @@ pub struct MBR {
     pub bootloader: Vec<u8>,
     pub partition_table: [MBRPartitionEntry; 4],
     pub boot_signature: u16,
-    pub bootloader_disam: String,
+    #[serde(default)]
+    pub table_lba: u64, // LBA the record was read from (0 for the MBR, the EBR location otherwise)
 }
 
 impl MBR {
     pub fn from_bytes(bytes: &[u8]) -> Self {
+        MBR::from_bytes_with_sector_size(bytes, DEFAULT_SECTOR_SIZE)
+    }
+
+    /// Parse a record of a disk with `sector_size`-byte sectors, which the entry byte
+    /// addresses are computed with.
+    pub fn from_bytes_with_sector_size(bytes: &[u8], sector_size: usize) -> Self {
         let mut cursor = Cursor::new(bytes);
         let mut mbr = MBR {
             bootloader: vec![0u8; 446],
             partition_table: Default::default(),
             boot_signature: 0,
-            bootloader_disam: Default::default(),
+            table_lba: 0,
         };
         cursor.read_exact(&mut mbr.bootloader).unwrap();
         for i in 0..4 {
@@ -242,28 +112,39 @@ impl MBR {
                 ],
                 start_lba: cursor.read_u32::<LittleEndian>().unwrap(),
                 size_sectors: cursor.read_u32::<LittleEndian>().unwrap(),
-                sector_size: DEFAULT_SECTOR_SIZE,
+                sector_size,
                 first_byte_addr: 0,
                 description: "Unknown".to_string(),
                 fvek: None,
+                encryption: None,
             };
             mbr.partition_table[i].first_byte_addr =
                 mbr.partition_table[i].sector_size * mbr.partition_table[i].start_lba as usize;
-            mbr.partition_table[i].description = mbr.partition_table[i]
-                .partition_type_description()
-                .to_string();
-            let cs = Capstone::new()
-                .x86()
-                .mode(arch::x86::ArchMode::Mode16)
-                .build()
-                .unwrap();
-            let instructions = cs.disasm_all(&mbr.bootloader, 0x1000).unwrap();
-            let opcodes: Vec<String> = instructions.iter().map(|ins| ins.to_string()).collect();
-            mbr.bootloader_disam = opcodes.join("\n");
+            mbr.partition_table[i].description =
+                mbr.partition_table[i].partition_type_description();
         }
         mbr.boot_signature = cursor.read_u16::<LittleEndian>().unwrap();
         mbr
     }
+
+    /// The 512-byte on-disk record: boot code, the four entries and the boot signature.
+    pub fn to_bytes(&self) -> [u8; 512] {
+        let mut bytes = [0u8; 512];
+        let code = self.bootloader.len().min(446);
+        bytes[..code].copy_from_slice(&self.bootloader[..code]);
+        for (i, entry) in self.partition_table.iter().enumerate() {
+            bytes[446 + i * 16..462 + i * 16].copy_from_slice(&entry.to_bytes());
+        }
+        bytes[510..].copy_from_slice(&self.boot_signature.to_le_bytes());
+        bytes
+    }
+
+    /// Disassemble the bootstrap code area. This is only done on demand as it is costly.
+    #[cfg(feature = "disassembly")]
+    pub fn disassemble_bootloader(&self) -> BootCode {
+        BootCode::disassemble(&self.bootloader)
+    }
+
     pub fn is_mbr(&self) -> bool {
         // First check the MBR signature.
         if self.boot_signature != 0xAA55 {
@@ -292,13 +173,15 @@ impl MBR {
         protective_mbr
     }
 
-    pub fn print_info(&self, bootloader: &bool) -> String {
+    /// Render the record, with entry addresses shifted by the `base_offset` of the table,
+    /// and the `bootloader` listing when given.
+    pub fn print_info(&self, bootloader: Option<&str>, base_offset: u64) -> String {
         let mut mbr_table = Table::new();
         let mut partitions_table = Table::new();
-        if *bootloader {
+        if let Some(bootloader_info) = bootloader {
             mbr_table.add_row(Row::new(vec![
                 Cell::new("Bootloader"),
-                Cell::new(&self.bootloader_disam),
+                Cell::new(bootloader_info),
             ]));
         }
 
@@ -320,7 +203,10 @@ impl MBR {
                 Cell::new(&format!("0x{:x}", partition.start_lba)),
                 Cell::new(&format!("0x{:02x}", partition.partition_type)),
                 Cell::new(&format!("{:?}", partition.description)),
-                Cell::new(&format!("0x{:x}", partition.first_byte_addr)),
+                Cell::new(&format!(
+                    "0x{:x}",
+                    base_offset + partition.first_byte_addr as u64
+                )),
                 Cell::new(&format!("0x{:x}", partition.size_sectors)),
             ]));
         }
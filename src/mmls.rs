@@ -0,0 +1,107 @@
+use crate::extent::Scheme;
+use crate::layout::SlotKind;
+use crate::Partitions;
+use std::collections::HashMap;
+
+/// Unit of the Start, End and Length columns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Units {
+    Sectors,
+    Bytes,
+}
+
+impl Partitions {
+    /// Flat, one-row-per-slot listing in the format of The Sleuth Kit's `mmls`.
+    pub fn print_mmls(&self, units: Units) -> String {
+        let sector_size = self.sector_size.max(1);
+        let mut s = String::new();
+
+        let table = if self.gpt.is_some() {
+            "GUID Partition Table (EFI)"
+        } else if self.mbr.is_some() {
+            "DOS Partition Table"
+        } else {
+            "Unknown Partition Table"
+        };
+        s.push_str(&format!("{}\n", table));
+        s.push_str(&format!(
+            "Offset Sector: {}\n",
+            self.base_offset / sector_size
+        ));
+        match units {
+            Units::Sectors => s.push_str(&format!("Units are in {}-byte sectors\n\n", sector_size)),
+            Units::Bytes => s.push_str("Units are in bytes\n\n"),
+        }
+        s.push_str("      Slot      Start        End          Length       Description\n");
+
+        // mmls designates DOS partitions as table:entry and GPT ones by their entry number.
+        let mut labels: HashMap<u64, (String, String)> = HashMap::new();
+        let mut logical = 0;
+        for extent in self.extents() {
+            let entry = extent.entry_id.unwrap_or(0);
+            let label = match extent.scheme {
+                Scheme::MBR => format!("000:{:03}", entry),
+                Scheme::EBR => {
+                    logical += 1;
+                    format!("{:03}:000", logical)
+                }
+                Scheme::GPT => format!("{:03}", entry),
+            };
+            let description = match extent.scheme {
+                Scheme::GPT => extent.name.clone().unwrap_or(extent.description.clone()),
+                _ => format!("{} ({})", extent.description, extent.partition_type),
+            };
+            labels.insert(extent.index, (label, description));
+        }
+        // Extended partitions keep the slot of their MBR entry.
+        let mut containers: HashMap<u64, String> = HashMap::new();
+        for (i, entry) in self
+            .mbr
+            .iter()
+            .flat_map(|mbr| mbr.partition_table.iter().enumerate())
+        {
+            if matches!(entry.partition_type, 0x05 | 0x0F | 0x85) {
+                containers.insert(entry.start_lba as u64, format!("000:{:03}", i));
+            }
+        }
+
+        let scale = match units {
+            Units::Sectors => 1,
+            Units::Bytes => sector_size,
+        };
+        for (row, slot) in self
+            .layout(self.image_size / sector_size)
+            .iter()
+            .enumerate()
+        {
+            let (slot_label, description) = match slot.kind {
+                SlotKind::Table => (String::from("Meta"), slot.description.clone()),
+                SlotKind::Container => (
+                    containers
+                        .get(&slot.start_lba)
+                        .cloned()
+                        .unwrap_or(String::from("Meta")),
+                    slot.description.clone(),
+                ),
+                SlotKind::Unallocated => (String::from("-------"), slot.description.clone()),
+                SlotKind::Partition => slot
+                    .partition_index
+                    .and_then(|index| labels.get(&index).cloned())
+                    .unwrap_or((String::from("-------"), slot.description.clone())),
+            };
+            let (start, end, length) = match units {
+                Units::Sectors => (slot.start_lba, slot.end_lba, slot.length),
+                Units::Bytes => (
+                    slot.start_lba * scale,
+                    (slot.end_lba + 1) * scale - 1,
+                    slot.length * scale,
+                ),
+            };
+            s.push_str(&format!(
+                "{:03}:  {:<10}{:010}   {:010}   {:010}   {}\n",
+                row, slot_label, start, end, length, description
+            ));
+        }
+        s
+    }
+}
@@ -0,0 +1,176 @@
+//! Partition tables found inside partitions: VM disks or carved USB images stored on a data
+//! partition, BSD slices, a GPT inside a logical volume...
+//!
+//! Each partition's byte range is read through a `SubReader`, so the nested tables are
+//! parsed exactly like a top-level disk, with LBAs relative to the start of the partition.
+
+use crate::extent::PartitionExtent;
+use crate::reader::{SectorReader, SubReader};
+use crate::Partitions;
+use log::{info, warn};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::io::SeekFrom;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DiscoveryOptions {
+    pub offset: u64,      // Byte offset of the partition tables in the body
+    pub nested: bool,     // Look for partition tables inside every partition
+    pub max_depth: usize, // How many levels of nesting to follow
+    // Passes reading partition contents rather than the tables, off by default.
+    pub encryption: bool, // Encryption headers, and random-looking data at partition starts
+    pub hidden_data: bool, // Non-zero bytes in the reserved areas of the tables
+    pub grub: bool,       // GRUB core.img, when the boot code is GRUB boot.img
+    pub esp: bool,        // Files of the EFI System Partitions
+}
+
+impl Default for DiscoveryOptions {
+    fn default() -> Self {
+        DiscoveryOptions {
+            offset: 0,
+            nested: false,
+            max_depth: 4,
+            encryption: false,
+            hidden_data: false,
+            grub: false,
+            esp: false,
+        }
+    }
+}
+
+impl DiscoveryOptions {
+    /// Turn on every pass reading partition contents.
+    pub fn inspect_all(self) -> Self {
+        DiscoveryOptions {
+            encryption: true,
+            hidden_data: true,
+            grub: true,
+            esp: true,
+            ..self
+        }
+    }
+}
+
+/// The partition tables found inside one partition of the parent `Partitions`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NestedPartitions {
+    pub parent_index: u64, // Index of the containing `PartitionExtent` in the parent
+    pub depth: usize,      // 1 for tables inside a top-level partition
+    pub partitions: Partitions, // LBAs relative to the containing partition (`base_offset`)
+}
+
+/// One partition of the volume tree, with absolute addresses.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct Volume {
+    pub id: u64,             // Position in the pre-order walk of the tree, from 0
+    pub parent: Option<u64>, // `id` of the containing volume, none for top-level partitions
+    pub depth: u64,
+    pub extent: PartitionExtent,
+}
+
+/// Whether a partition table signature is present at the start of the reader.
+fn has_table<R: SectorReader + ?Sized>(reader: &mut R) -> bool {
+    let sector_size = reader.sector_size() as usize;
+    let mut sectors = vec![0u8; sector_size * 2];
+    if reader
+        .seek(SeekFrom::Start(0))
+        .and_then(|_| reader.read_exact(&mut sectors))
+        .is_err()
+    {
+        return false;
+    }
+    sectors[510..512] == [0x55, 0xAA] || &sectors[sector_size..sector_size + 8] == b"EFI PART"
+}
+
+/// A volume boot record also ends with 0x55AA; only keep tables whose partitions all lie
+/// inside the containing partition.
+fn is_plausible(partitions: &Partitions) -> bool {
+    let extents = partitions.extents();
+    !extents.is_empty()
+        && extents.iter().all(|e| {
+            e.start_lba > 0 && e.end_byte_addr() <= partitions.base_offset + partitions.image_size
+        })
+}
+
+pub(crate) fn discover_nested<R: SectorReader + ?Sized>(
+    body: &mut R,
+    parent: &Partitions,
+    options: &DiscoveryOptions,
+) -> Vec<NestedPartitions> {
+    let mut visited = HashSet::new();
+    visited.insert((parent.base_offset, parent.image_size));
+    walk(body, parent, 1, options, &mut visited)
+}
+
+fn walk<R: SectorReader + ?Sized>(
+    body: &mut R,
+    parent: &Partitions,
+    depth: usize,
+    options: &DiscoveryOptions,
+    visited: &mut HashSet<(u64, u64)>,
+) -> Vec<NestedPartitions> {
+    let mut found = Vec::new();
+    if depth > options.max_depth {
+        return found;
+    }
+    for extent in parent.extents() {
+        // A partition starting at LBA 0 would lead back to the table describing it.
+        if extent.start_lba == 0 || extent.size_bytes == 0 {
+            continue;
+        }
+        let offset = extent.first_byte_addr;
+        if !visited.insert((offset, extent.size_bytes)) {
+            warn!(
+                "Partition #{} at depth {} points back to an already visited range, skipping it.",
+                extent.index, depth
+            );
+            continue;
+        }
+        let mut window = SubReader::new(body, offset, extent.size_bytes);
+        if !has_table(&mut window) {
+            continue;
+        }
+        let mut partitions = match Partitions::discover(&mut window, options) {
+            Ok(partitions) if is_plausible(&partitions) => partitions,
+            _ => continue,
+        };
+        partitions.base_offset = offset;
+        info!(
+            "Found a nested partition table in partition #{} (offset 0x{:x}, depth {}).",
+            extent.index, offset, depth
+        );
+        partitions.nested = walk(body, &partitions, depth + 1, options, visited);
+        found.push(NestedPartitions {
+            parent_index: extent.index,
+            depth,
+            partitions,
+        });
+    }
+    found
+}
+
+fn flatten(partitions: &Partitions, depth: u64, parent: Option<u64>, volumes: &mut Vec<Volume>) {
+    for extent in partitions.extents() {
+        let id = volumes.len() as u64;
+        let index = extent.index;
+        volumes.push(Volume {
+            id,
+            parent,
+            depth,
+            extent,
+        });
+        for nested in partitions.nested.iter().filter(|n| n.parent_index == index) {
+            flatten(&nested.partitions, depth + 1, Some(id), volumes);
+        }
+    }
+}
+
+impl Partitions {
+    /// Every partition, nested ones included, as a tree flattened in pre-order.
+    pub fn volumes(&self) -> Vec<Volume> {
+        let mut volumes = Vec::new();
+        flatten(self, 0, None, &mut volumes);
+        volumes
+    }
+}
@@ -0,0 +1,203 @@
+//! What the parsers need from the evidence: random access to its bytes and its sector size.
+
+use exhume_body::Body;
+use std::fs::File;
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
+use std::path::Path;
+
+const DEFAULT_SECTOR_SIZE: u16 = 512;
+
+/// A seekable source of sectors. `Body` implements it, as do `File` and `Cursor` (assuming
+/// 512-byte sectors); wrap any other reader, or override the sector size, with `WithSectorSize`.
+pub trait SectorReader: Read + Seek {
+    fn sector_size(&self) -> u16;
+
+    /// Logical size of the source in bytes. Defaults to the end of the stream.
+    fn image_size(&mut self) -> io::Result<u64> {
+        let position = self.stream_position()?;
+        let end = self.seek(SeekFrom::End(0))?;
+        self.seek(SeekFrom::Start(position))?;
+        Ok(end)
+    }
+}
+
+impl SectorReader for Body {
+    fn sector_size(&self) -> u16 {
+        self.get_sector_size()
+    }
+
+    fn image_size(&mut self) -> io::Result<u64> {
+        Ok(self.get_image_size())
+    }
+}
+
+impl SectorReader for File {
+    fn sector_size(&self) -> u16 {
+        DEFAULT_SECTOR_SIZE
+    }
+}
+
+impl<T: AsRef<[u8]>> SectorReader for Cursor<T> {
+    fn sector_size(&self) -> u16 {
+        DEFAULT_SECTOR_SIZE
+    }
+}
+
+impl<R: SectorReader + ?Sized> SectorReader for &mut R {
+    fn sector_size(&self) -> u16 {
+        (**self).sector_size()
+    }
+
+    fn image_size(&mut self) -> io::Result<u64> {
+        (**self).image_size()
+    }
+}
+
+impl<R: SectorReader + ?Sized> SectorReader for Box<R> {
+    fn sector_size(&self) -> u16 {
+        (**self).sector_size()
+    }
+
+    fn image_size(&mut self) -> io::Result<u64> {
+        (**self).image_size()
+    }
+}
+
+/// Any `Read + Seek` source (block device, network-backed reader...) with a given sector size.
+pub struct WithSectorSize<R> {
+    inner: R,
+    sector_size: u16,
+}
+
+impl<R: Read + Seek> WithSectorSize<R> {
+    pub fn new(inner: R, sector_size: u16) -> WithSectorSize<R> {
+        WithSectorSize { inner, sector_size }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for WithSectorSize<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl<R: Seek> Seek for WithSectorSize<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+impl<R: Read + Seek> SectorReader for WithSectorSize<R> {
+    fn sector_size(&self) -> u16 {
+        self.sector_size
+    }
+}
+
+/// A window of `length` bytes starting at `offset` in another reader, seen as a reader of
+/// its own: position 0 is `offset`, and reads stop at the end of the window.
+pub struct SubReader<'a, R: ?Sized> {
+    inner: &'a mut R,
+    offset: u64,
+    length: u64,
+    position: u64,
+}
+
+impl<'a, R: SectorReader + ?Sized> SubReader<'a, R> {
+    pub fn new(inner: &'a mut R, offset: u64, length: u64) -> SubReader<'a, R> {
+        SubReader {
+            inner,
+            offset,
+            length,
+            position: 0,
+        }
+    }
+}
+
+impl<R: SectorReader + ?Sized> Read for SubReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.length.saturating_sub(self.position);
+        let len = (buf.len() as u64).min(remaining) as usize;
+        if len == 0 {
+            return Ok(0);
+        }
+        self.inner
+            .seek(SeekFrom::Start(self.offset + self.position))?;
+        let n = self.inner.read(&mut buf[..len])?;
+        self.position += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: SectorReader + ?Sized> Seek for SubReader<'_, R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(n) => Some(n),
+            SeekFrom::End(n) => self.length.checked_add_signed(n),
+            SeekFrom::Current(n) => self.position.checked_add_signed(n),
+        };
+        match target {
+            Some(target) => {
+                self.position = target;
+                Ok(target)
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+}
+
+impl<R: SectorReader + ?Sized> SectorReader for SubReader<'_, R> {
+    fn sector_size(&self) -> u16 {
+        self.inner.sector_size()
+    }
+
+    fn image_size(&mut self) -> io::Result<u64> {
+        Ok(self.length)
+    }
+}
+
+/// The first segment of a split EWF/LEF/SMART image that `path` would be a later segment of:
+/// "disk.E01" for "disk.E02" or "disk.EAA", "disk.Ex01" for "disk.Ex02". None for any other
+/// name, the first segment included.
+pub fn first_segment(path: &str) -> Option<String> {
+    let path = Path::new(path);
+    let extension = path.extension()?.to_str()?;
+    if !extension.is_ascii() || extension.is_empty() {
+        return None;
+    }
+    let (kind, rest) = extension.split_at(1);
+    if !matches!(kind, "E" | "e" | "L" | "l" | "S" | "s") {
+        return None;
+    }
+    let (x, segment) = match rest.strip_prefix(['x', 'X']) {
+        Some(segment) => (&rest[..1], segment),
+        None => ("", rest),
+    };
+    let later = segment.len() == 2
+        && if segment.chars().all(|c| c.is_ascii_digit()) {
+            segment != "01" && segment != "00"
+        } else {
+            // E99 is followed by EAA, EAB...
+            segment.chars().all(|c| c.is_ascii_alphabetic())
+        };
+    if !later {
+        return None;
+    }
+    let first = path.with_extension(format!("{}{}01", kind, x));
+    Some(first.to_string_lossy().to_string())
+}
+
+/// Split `files` into the ones to open and the later segments of split images to skip, the
+/// ones whose first segment is among `files` and opens them.
+pub fn skip_later_segments(files: &[String]) -> (Vec<String>, Vec<String>) {
+    files
+        .iter()
+        .cloned()
+        .partition(|file| !first_segment(file).is_some_and(|first| files.contains(&first)))
+}
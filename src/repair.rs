@@ -0,0 +1,266 @@
+//! Rebuild a damaged GPT copy (primary or backup) from the intact one.
+//!
+//! Planning only reads the body. A plan is applied to a full copy of the evidence or saved as
+//! an overlay listing the sectors to rewrite; nothing is ever written to the evidence itself.
+
+use crate::gpt::{GPTHeader, GPT};
+use crate::hash::to_hex;
+use crate::reader::SectorReader;
+use log::{info, warn};
+use prettytable::{Cell, Row, Table};
+use serde::{Deserialize, Serialize, Serializer};
+use std::error::Error;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Seek, SeekFrom, Write};
+use std::path::Path;
+
+const CHUNK_SIZE: usize = 1024 * 1024;
+/// 256 times the usual array of 128 entries of 128 bytes: room for unusual tables, but a
+/// crafted entry count or size cannot make the check allocate gigabytes.
+const MAX_ARRAY_BYTES: u64 = 4 * 1024 * 1024;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GptCopy {
+    Primary,
+    Backup,
+}
+
+/// What was found at the location of one GPT copy.
+#[derive(Serialize, Debug, Clone)]
+pub struct GptCopyStatus {
+    pub copy: GptCopy,
+    pub header_lba: u64,
+    pub signature: bool,     // "EFI PART" found
+    pub header_crc_ok: bool, // Header CRC32 matches
+    pub array_crc_ok: bool,  // Partition array CRC32 matches
+    #[serde(skip)]
+    pub header: Option<GPTHeader>,
+    #[serde(skip)]
+    pub array: Vec<u8>, // Raw partition entry array, as read
+}
+
+impl GptCopyStatus {
+    pub fn is_intact(&self) -> bool {
+        self.signature && self.header_crc_ok && self.array_crc_ok
+    }
+}
+
+fn serialize_hex<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&to_hex(bytes))
+}
+
+/// Sectors to rewrite, starting at `lba`.
+#[derive(Serialize, Debug, Clone)]
+pub struct SectorWrite {
+    pub lba: u64,
+    pub description: String,
+    #[serde(serialize_with = "serialize_hex")]
+    pub bytes: Vec<u8>, // A whole number of sectors
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct RepairPlan {
+    pub source: GptCopy, // The intact copy
+    pub target: GptCopy, // The copy being rebuilt
+    pub sector_size: u64,
+    pub writes: Vec<SectorWrite>,
+}
+
+fn read_copy<R: SectorReader + ?Sized>(body: &mut R, copy: GptCopy, lba: u64) -> GptCopyStatus {
+    let sector_size = body.sector_size() as u64;
+    let mut status = GptCopyStatus {
+        copy,
+        header_lba: lba,
+        signature: false,
+        header_crc_ok: false,
+        array_crc_ok: false,
+        header: None,
+        array: Vec::new(),
+    };
+    let mut sector = vec![0u8; sector_size as usize];
+    if body
+        .seek(SeekFrom::Start(lba * sector_size))
+        .and_then(|_| body.read_exact(&mut sector))
+        .is_err()
+    {
+        return status;
+    }
+    let header = GPT::from_bytes(&sector).header;
+    status.signature = header.signature == *b"EFI PART";
+    if !status.signature {
+        return status;
+    }
+    status.header_crc_ok = header.is_crc32_valid();
+
+    let array_bytes = header.num_partition_entries as u64 * header.partition_entry_size as u64;
+    if array_bytes <= MAX_ARRAY_BYTES {
+        let mut array = vec![0u8; array_bytes as usize];
+        let read = body
+            .seek(SeekFrom::Start(header.partition_entry_lba * sector_size))
+            .and_then(|_| body.read_exact(&mut array));
+        if read.is_ok() {
+            status.array_crc_ok = crc32fast::hash(&array) == header.partition_array_crc32;
+            status.array = array;
+        }
+    }
+    status.header = Some(header);
+    status
+}
+
+/// Read both GPT copies. The backup is looked for where the primary says it is, or at the
+/// last LBA of the body when the primary is unusable.
+pub fn inspect_gpt<R: SectorReader + ?Sized>(
+    body: &mut R,
+) -> Result<(GptCopyStatus, GptCopyStatus), Box<dyn Error>> {
+    let sector_size = body.sector_size() as u64;
+    let last_lba = (body.seek(SeekFrom::End(0))? / sector_size)
+        .checked_sub(1)
+        .ok_or("Evidence is smaller than one sector")?;
+    let primary = read_copy(body, GptCopy::Primary, 1);
+    let backup_lba = match &primary.header {
+        Some(header) if primary.header_crc_ok && header.backup_lba <= last_lba => header.backup_lba,
+        _ => last_lba,
+    };
+    let backup = read_copy(body, GptCopy::Backup, backup_lba);
+    Ok((primary, backup))
+}
+
+/// Plan the rebuild of the damaged GPT copy from the intact one: the header with its LBAs
+/// swapped and its CRC recomputed, and a verbatim copy of the partition entry array.
+/// Returns `None` when both copies are intact.
+pub fn plan_gpt_repair<R: SectorReader + ?Sized>(
+    body: &mut R,
+) -> Result<Option<RepairPlan>, Box<dyn Error>> {
+    let sector_size = body.sector_size() as u64;
+    let (primary, backup) = inspect_gpt(body)?;
+    let (good, damaged) = match (primary.is_intact(), backup.is_intact()) {
+        (true, true) => {
+            info!("Both GPT copies are intact, nothing to repair.");
+            return Ok(None);
+        }
+        (true, false) => (primary, backup),
+        (false, true) => (backup, primary),
+        (false, false) => return Err("Both GPT copies are damaged, nothing to rebuild from".into()),
+    };
+    warn!(
+        "The {:?} GPT at LBA {} is damaged, rebuilding it from the {:?} one.",
+        damaged.copy, damaged.header_lba, good.copy
+    );
+
+    let source = good.header.as_ref().unwrap();
+    let array_sectors = (good.array.len() as u64).div_ceil(sector_size);
+    let mut header = source.clone();
+    header.current_lba = source.backup_lba;
+    header.backup_lba = source.current_lba;
+    header.partition_entry_lba = match damaged.copy {
+        GptCopy::Primary => header.current_lba + 1,
+        GptCopy::Backup => header
+            .current_lba
+            .checked_sub(array_sectors)
+            .ok_or("The intact GPT points to an impossible backup location")?,
+    };
+    let last_lba = body.seek(SeekFrom::End(0))? / sector_size - 1;
+    if header.current_lba > last_lba || header.partition_entry_lba + array_sectors > last_lba + 1 {
+        return Err("The rebuilt GPT would lie past the end of the evidence".into());
+    }
+    header.crc32 = header.compute_crc32();
+
+    let mut header_sector = header.to_bytes().to_vec();
+    header_sector.extend_from_slice(&header.reserved_tail);
+    header_sector.resize(sector_size as usize, 0);
+    let mut array = good.array.clone();
+    array.resize((array_sectors * sector_size) as usize, 0);
+
+    Ok(Some(RepairPlan {
+        source: good.copy,
+        target: damaged.copy,
+        sector_size,
+        writes: vec![
+            SectorWrite {
+                lba: header.current_lba,
+                description: format!("{:?} GPT header", damaged.copy),
+                bytes: header_sector,
+            },
+            SectorWrite {
+                lba: header.partition_entry_lba,
+                description: format!("{:?} partition entry array", damaged.copy),
+                bytes: array,
+            },
+        ],
+    }))
+}
+
+impl RepairPlan {
+    pub fn print_info(&self) -> String {
+        let mut table = Table::new();
+        table.add_row(Row::new(vec![
+            Cell::new("Start (LBA)"),
+            Cell::new("Sectors"),
+            Cell::new("Description"),
+        ]));
+        for write in &self.writes {
+            table.add_row(Row::new(vec![
+                Cell::new(&format!("0x{:X}", write.lba)),
+                Cell::new(&(write.bytes.len() as u64 / self.sector_size).to_string()),
+                Cell::new(&write.description),
+            ]));
+        }
+        format!(
+            "Rebuild the {:?} GPT from the {:?} one:\n{}",
+            self.target, self.source, table
+        )
+    }
+
+    /// Save the plan as a JSON overlay: the sectors to rewrite and their content in hex.
+    /// Refuses to write over the evidence or over an existing file.
+    pub fn write_overlay(&self, evidence: &Path, output: &Path) -> Result<(), Box<dyn Error>> {
+        let mut out = create_output(evidence, output)?;
+        out.write_all(serde_json::to_string_pretty(self)?.as_bytes())?;
+        Ok(())
+    }
+
+    /// Copy the whole evidence to `output` as a raw image, then apply the plan to the copy.
+    /// Refuses to write over the evidence or over an existing file.
+    pub fn write_copy<R: SectorReader + ?Sized>(
+        &self,
+        body: &mut R,
+        evidence: &Path,
+        output: &Path,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut out = create_output(evidence, output)?;
+        let size = body.seek(SeekFrom::End(0))?;
+        body.seek(SeekFrom::Start(0))?;
+        let mut buf = vec![0u8; CHUNK_SIZE];
+        let mut done = 0u64;
+        while done < size {
+            let len = std::cmp::min(CHUNK_SIZE as u64, size - done) as usize;
+            body.read_exact(&mut buf[..len])?;
+            out.write_all(&buf[..len])?;
+            done += len as u64;
+        }
+        self.apply(&mut out)
+    }
+
+    fn apply(&self, out: &mut File) -> Result<(), Box<dyn Error>> {
+        for write in &self.writes {
+            out.seek(SeekFrom::Start(write.lba * self.sector_size))?;
+            out.write_all(&write.bytes)?;
+        }
+        out.sync_all()?;
+        Ok(())
+    }
+}
+
+/// Create `output` for writing, never over the evidence nor over an existing file.
+fn create_output(evidence: &Path, output: &Path) -> Result<File, Box<dyn Error>> {
+    if output.exists() {
+        if fs::canonicalize(output)? == fs::canonicalize(evidence)? {
+            return Err("Refusing to write the repair over the evidence".into());
+        }
+        return Err(format!("'{}' already exists", output.display()).into());
+    }
+    Ok(OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(output)?)
+}
@@ -0,0 +1,388 @@
+//! Stable, versioned JSON output.
+//!
+//! The structures parsed from the disk follow the on-disk layouts and change whenever the
+//! parsers do. The types of this module are what gets serialized for consumers instead:
+//! no raw byte arrays, GUIDs as strings, every LBA, size and address as a u64.
+//! `SCHEMA_VERSION` is bumped whenever a field is removed, renamed or changes type;
+//! adding a field does not bump it. The JSON Schema is generated from these types
+//! (`exhume_partitions schema`) and kept in `schema/partitions.schema.json`.
+
+use crate::abslot::AbSlots;
+#[cfg(feature = "disassembly")]
+use crate::bootcode::BootCode;
+use crate::dps::DpsPartition;
+use crate::entropy::SlotEntropy;
+use crate::esp::EspInventory;
+use crate::extent::PartitionExtent;
+use crate::gpt::{GPTPartitionEntry, GPT};
+use crate::grub::GrubInfo;
+#[cfg(feature = "disassembly")]
+use crate::hash::to_hex;
+use crate::hash::SlotHash;
+use crate::hidden::HiddenData;
+use crate::mbr::{MBRPartitionEntry, MBR};
+use crate::nested::Volume;
+use crate::Partitions;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Partition layout of one evidence file.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct PartitionsReport {
+    /// Version of this schema.
+    pub schema_version: u32,
+    /// Path of the evidence the report was produced from.
+    pub evidence: Option<String>,
+    /// Sector size of the evidence in bytes.
+    pub sector_size: u64,
+    /// Logical size of the evidence in bytes, from `base_offset` on.
+    pub image_size: u64,
+    /// Byte offset in the evidence the partition tables were found at. LBAs are relative to
+    /// it, byte addresses are not.
+    #[serde(default)]
+    pub base_offset: u64,
+    /// Master Boot Record, or protective MBR of a GPT disk.
+    pub mbr: Option<MbrReport>,
+    /// Extended Boot Records of the logical partition chain, in chain order.
+    pub ebr: Vec<MbrReport>,
+    /// GUID Partition Table (primary, or backup when the primary is unreadable).
+    pub gpt: Option<GptReport>,
+    /// Every used partition across all schemes, in a single list.
+    pub partitions: Vec<PartitionExtent>,
+    /// The volume tree, top-level partitions included so that `parent` ids resolve, with
+    /// absolute addresses. Empty unless nested discovery found tables inside partitions.
+    #[serde(default)]
+    pub volumes: Vec<Volume>,
+    /// ChromeOS and Android A/B partition pairs, with the slot the bootloader picks.
+    #[serde(default)]
+    pub ab_slots: Vec<AbSlots>,
+    /// GPT partitions typed per the Discoverable Partitions Specification, with their role
+    /// and architecture.
+    #[serde(default)]
+    pub dps: Vec<DpsPartition>,
+    /// Disassembly of the MBR boot code, when requested.
+    #[serde(default)]
+    pub bootcode: Option<BootCodeReport>,
+    /// GRUB core.img, when the MBR boot code is GRUB boot.img.
+    #[serde(default)]
+    pub grub: Option<GrubInfo>,
+    /// Files under `/EFI` of every EFI System Partition.
+    #[serde(default)]
+    pub esp: Vec<EspInventory>,
+    /// Reserved areas of the partition tables that hold non-zero bytes, with absolute
+    /// addresses.
+    #[serde(default)]
+    pub hidden_data: Vec<HiddenData>,
+    /// Digests of the partitions, gaps and tables, when hashing was requested.
+    pub hashes: Option<Vec<SlotHash>>,
+    /// Entropy profiles of the partitions and gaps, when profiling was requested.
+    #[serde(default)]
+    pub entropy: Option<Vec<SlotEntropy>>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct MbrReport {
+    /// LBA the record was read from.
+    pub table_lba: u64,
+    /// Boot signature, 43605 (0xAA55) when valid.
+    pub boot_signature: u64,
+    /// Whether the record holds a 0xEE protective entry.
+    pub protective: bool,
+    /// The four partition table entries, used or not.
+    pub entries: Vec<MbrEntryReport>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct Chs {
+    pub cylinder: u64,
+    pub head: u64,
+    pub sector: u64,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct MbrEntryReport {
+    /// Position of the entry in its table (0 to 3).
+    pub entry_id: u64,
+    pub bootable: bool,
+    pub boot_indicator: u64,
+    pub partition_type: u64,
+    pub description: String,
+    pub start_chs: Chs,
+    pub end_chs: Chs,
+    /// Absolute starting LBA.
+    pub start_lba: u64,
+    pub size_sectors: u64,
+    /// Absolute address of the first byte.
+    pub first_byte_addr: u64,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct GptReport {
+    pub header: GptHeaderReport,
+    /// Used entries only.
+    pub entries: Vec<GptEntryReport>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct GptHeaderReport {
+    pub revision: u64,
+    pub header_size: u64,
+    pub header_crc32: u64,
+    pub current_lba: u64,
+    pub backup_lba: u64,
+    pub first_usable_lba: u64,
+    pub last_usable_lba: u64,
+    pub disk_guid: String,
+    pub partition_entry_lba: u64,
+    pub num_partition_entries: u64,
+    pub partition_entry_size: u64,
+    pub partition_array_crc32: u64,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct GptEntryReport {
+    /// Position of the entry in the partition entry array.
+    pub entry_id: u64,
+    pub partition_guid: String,
+    pub partition_type_guid: String,
+    pub description: String,
+    /// Partition name, trailing NULs removed.
+    pub name: String,
+    pub start_lba: u64,
+    /// Inclusive.
+    pub end_lba: u64,
+    pub size_sectors: u64,
+    /// Absolute address of the first byte.
+    pub first_byte_addr: u64,
+    pub attributes: u64,
+    /// Meaning of the attribute bits set.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attribute_flags: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct BootCodeReport {
+    /// Address the BIOS loads the boot sector at.
+    pub load_address: u64,
+    /// Address the code copies itself to and continues at, if it does.
+    pub relocation_address: Option<u64>,
+    /// Instructions reachable from the entry point, by offset.
+    pub instructions: Vec<BootInstructionReport>,
+    /// Ranges never reached from the entry point.
+    pub data: Vec<BootDataReport>,
+    /// Printable runs of at least 4 characters in the data ranges.
+    pub strings: Vec<BootStringReport>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct BootInstructionReport {
+    /// Address of the instruction once loaded, or relocated.
+    pub address: u64,
+    /// Offset of the instruction in the boot code.
+    pub offset: u64,
+    /// Encoded instruction, in hexadecimal.
+    pub bytes: String,
+    pub mnemonic: String,
+    pub operands: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct BootDataReport {
+    pub address: u64,
+    pub offset: u64,
+    pub length: u64,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct BootStringReport {
+    pub address: u64,
+    pub offset: u64,
+    pub value: String,
+}
+
+fn chs(tuple: (u16, u8, u8)) -> Chs {
+    Chs {
+        cylinder: tuple.0 as u64,
+        head: tuple.1 as u64,
+        sector: tuple.2 as u64,
+    }
+}
+
+impl From<&MBRPartitionEntry> for MbrEntryReport {
+    fn from(entry: &MBRPartitionEntry) -> Self {
+        MbrEntryReport {
+            entry_id: entry.id.unwrap_or(0) as u64,
+            bootable: entry.boot_indicator == 0x80,
+            boot_indicator: entry.boot_indicator as u64,
+            partition_type: entry.partition_type as u64,
+            description: entry.description.clone(),
+            start_chs: chs(entry.start_chs_tuple()),
+            end_chs: chs(entry.end_chs_tuple()),
+            start_lba: entry.start_lba as u64,
+            size_sectors: entry.size_sectors as u64,
+            first_byte_addr: entry.first_byte_addr as u64,
+        }
+    }
+}
+
+impl From<&MBR> for MbrReport {
+    fn from(mbr: &MBR) -> Self {
+        MbrReport {
+            table_lba: mbr.table_lba,
+            boot_signature: mbr.boot_signature as u64,
+            protective: mbr.is_pmbr(),
+            entries: mbr
+                .partition_table
+                .iter()
+                .map(MbrEntryReport::from)
+                .collect(),
+        }
+    }
+}
+
+#[cfg(feature = "disassembly")]
+impl From<&BootCode> for BootCodeReport {
+    fn from(bootcode: &BootCode) -> Self {
+        BootCodeReport {
+            load_address: bootcode.load_address as u64,
+            relocation_address: bootcode.relocation_address.map(u64::from),
+            instructions: bootcode
+                .instructions
+                .iter()
+                .map(|i| BootInstructionReport {
+                    address: i.address as u64,
+                    offset: i.offset as u64,
+                    bytes: to_hex(&i.bytes),
+                    mnemonic: i.mnemonic.clone(),
+                    operands: i.operands.clone(),
+                })
+                .collect(),
+            data: bootcode
+                .data
+                .iter()
+                .map(|d| BootDataReport {
+                    address: d.address as u64,
+                    offset: d.offset as u64,
+                    length: d.length as u64,
+                })
+                .collect(),
+            strings: bootcode
+                .strings
+                .iter()
+                .map(|s| BootStringReport {
+                    address: s.address as u64,
+                    offset: s.offset as u64,
+                    value: s.value.clone(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<&GPTPartitionEntry> for GptEntryReport {
+    fn from(entry: &GPTPartitionEntry) -> Self {
+        GptEntryReport {
+            entry_id: entry.id.unwrap_or(0) as u64,
+            partition_guid: entry.partition_guid_string.clone(),
+            partition_type_guid: entry.partition_type_guid_string.clone(),
+            description: entry.description.clone(),
+            name: entry.partition_name.trim_end_matches('\0').to_string(),
+            start_lba: entry.starting_lba,
+            end_lba: entry.ending_lba,
+            size_sectors: entry.size_sectors,
+            first_byte_addr: entry.first_byte_addr,
+            attributes: entry.attributes,
+            attribute_flags: entry.attribute_flags(),
+        }
+    }
+}
+
+impl From<&GPT> for GptReport {
+    fn from(gpt: &GPT) -> Self {
+        let h = &gpt.header;
+        GptReport {
+            header: GptHeaderReport {
+                revision: h.revision as u64,
+                header_size: h.header_size as u64,
+                header_crc32: h.crc32 as u64,
+                current_lba: h.current_lba,
+                backup_lba: h.backup_lba,
+                first_usable_lba: h.first_usable_lba,
+                last_usable_lba: h.last_usable_lba,
+                disk_guid: h.disk_guid_string.clone(),
+                partition_entry_lba: h.partition_entry_lba,
+                num_partition_entries: h.num_partition_entries as u64,
+                partition_entry_size: h.partition_entry_size as u64,
+                partition_array_crc32: h.partition_array_crc32 as u64,
+            },
+            entries: gpt
+                .partition_entries
+                .iter()
+                .map(GptEntryReport::from)
+                .collect(),
+        }
+    }
+}
+
+impl PartitionsReport {
+    pub fn new(partitions: &Partitions, evidence: Option<&str>) -> PartitionsReport {
+        // Entry addresses are relative to the table, the report's are absolute.
+        let base_offset = partitions.base_offset;
+        let mbr_report = |mbr: &MBR| {
+            let mut report = MbrReport::from(mbr);
+            for entry in &mut report.entries {
+                entry.first_byte_addr += base_offset;
+            }
+            report
+        };
+        let ebr: Vec<MbrReport> = partitions.ebr.iter().flatten().map(mbr_report).collect();
+        let gpt = partitions.gpt.as_ref().map(|gpt| {
+            let mut report = GptReport::from(gpt);
+            for entry in &mut report.entries {
+                entry.first_byte_addr = entry.first_byte_addr.saturating_add(base_offset);
+            }
+            report
+        });
+        PartitionsReport {
+            schema_version: SCHEMA_VERSION,
+            evidence: evidence.map(str::to_string),
+            sector_size: partitions.sector_size,
+            image_size: partitions.image_size,
+            base_offset: partitions.base_offset,
+            mbr: partitions.mbr.as_ref().map(mbr_report),
+            ebr,
+            gpt,
+            partitions: partitions.extents(),
+            volumes: if partitions.nested.is_empty() {
+                Vec::new()
+            } else {
+                partitions.volumes()
+            },
+            ab_slots: partitions.ab_slots(),
+            dps: partitions.dps_partitions(),
+            #[cfg(feature = "disassembly")]
+            bootcode: partitions.bootcode.as_ref().map(BootCodeReport::from),
+            #[cfg(not(feature = "disassembly"))]
+            bootcode: None,
+            grub: partitions.grub.clone(),
+            esp: partitions.esp.clone(),
+            hidden_data: partitions
+                .hidden_data
+                .iter()
+                .map(|hidden| HiddenData {
+                    first_byte_addr: partitions.base_offset + hidden.first_byte_addr,
+                    ..hidden.clone()
+                })
+                .collect(),
+            hashes: partitions.hashes.clone(),
+            entropy: partitions.entropy.clone(),
+        }
+    }
+}
+
+/// JSON Schema of `PartitionsReport`.
+pub fn json_schema() -> serde_json::Value {
+    serde_json::to_value(schemars::schema_for!(PartitionsReport)).unwrap()
+}
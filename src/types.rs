@@ -0,0 +1,164 @@
+//! Registry of the MBR and GPT partition types.
+//!
+//! The built-in table is `data/partition_types.json`, embedded at build time. Every entry
+//! carries a description and, when known, the operating system, the role of the partition
+//! (root, usr, root-verity, swap, esp...) and its architecture, using the names of the
+//! Discoverable Partitions Specification (x86-64, arm64...). Entries can be added or
+//! overridden at runtime, or from a user-supplied file in the same format, so that new vendor
+//! types are recognized without a new release. Descriptions are looked up when the tables are
+//! parsed: register extra types before discovery.
+
+use crate::gpt::parse_guid;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::path::Path;
+use std::sync::{OnceLock, RwLock, RwLockReadGuard};
+
+const BUILTIN: &str = include_str!("../data/partition_types.json");
+
+/// One partition type.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq)]
+pub struct PartitionType {
+    #[serde(rename = "type")]
+    pub type_id: String, // GPT type GUID, or MBR type byte as "0x07"
+    pub description: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub os: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub arch: Option<String>,
+}
+
+/// The on-disk format of a type table, built-in or user-supplied.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Default)]
+pub struct TypeTable {
+    #[serde(default)]
+    pub mbr: Vec<PartitionType>,
+    #[serde(default)]
+    pub gpt: Vec<PartitionType>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PartitionTypeRegistry {
+    mbr: BTreeMap<u8, PartitionType>,
+    gpt: BTreeMap<String, PartitionType>, // Keyed by lowercase GUID
+}
+
+fn parse_mbr_type(type_id: &str) -> Option<u8> {
+    let hex = type_id
+        .strip_prefix("0x")
+        .or_else(|| type_id.strip_prefix("0X"))?;
+    u8::from_str_radix(hex, 16).ok()
+}
+
+impl PartitionTypeRegistry {
+    /// The built-in table.
+    pub fn builtin() -> PartitionTypeRegistry {
+        let mut registry = PartitionTypeRegistry::default();
+        registry
+            .extend_from_json(BUILTIN)
+            .expect("The built-in partition type table is valid");
+        registry
+    }
+
+    /// Add the types of a JSON table, overriding the ones already registered. A type listed
+    /// twice in the same table is an error.
+    pub fn extend_from_json(&mut self, json: &str) -> Result<(), Box<dyn Error>> {
+        let table: TypeTable = serde_json::from_str(json)?;
+        self.extend(table)
+    }
+
+    pub fn extend(&mut self, table: TypeTable) -> Result<(), Box<dyn Error>> {
+        let mut mbr = BTreeMap::new();
+        for entry in table.mbr {
+            let byte = parse_mbr_type(&entry.type_id)
+                .ok_or_else(|| format!("Invalid MBR partition type {:?}", entry.type_id))?;
+            if mbr.insert(byte, entry).is_some() {
+                return Err(format!("MBR partition type 0x{:02x} is listed twice", byte).into());
+            }
+        }
+        let mut gpt = BTreeMap::new();
+        for mut entry in table.gpt {
+            if parse_guid(&entry.type_id).is_none() {
+                return Err(format!("Invalid GPT partition type {:?}", entry.type_id).into());
+            }
+            entry.type_id = entry.type_id.to_lowercase();
+            let guid = entry.type_id.clone();
+            if gpt.insert(guid.clone(), entry).is_some() {
+                return Err(format!("GPT partition type {} is listed twice", guid).into());
+            }
+        }
+        self.mbr.extend(mbr);
+        self.gpt.extend(gpt);
+        Ok(())
+    }
+
+    /// Add the types of a JSON table file.
+    pub fn load_file(&mut self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+        self.extend_from_json(&json)
+            .map_err(|e| format!("{}: {}", path.display(), e).into())
+    }
+
+    pub fn register_mbr(&mut self, partition_type: u8, entry: PartitionType) {
+        self.mbr.insert(partition_type, entry);
+    }
+
+    pub fn register_gpt(&mut self, mut entry: PartitionType) -> Result<(), Box<dyn Error>> {
+        if parse_guid(&entry.type_id).is_none() {
+            return Err(format!("Invalid GPT partition type {:?}", entry.type_id).into());
+        }
+        entry.type_id = entry.type_id.to_lowercase();
+        self.gpt.insert(entry.type_id.clone(), entry);
+        Ok(())
+    }
+
+    pub fn mbr(&self, partition_type: u8) -> Option<&PartitionType> {
+        self.mbr.get(&partition_type)
+    }
+
+    pub fn gpt(&self, type_guid: &str) -> Option<&PartitionType> {
+        self.gpt.get(&type_guid.to_lowercase())
+    }
+
+    /// Every registered type, in the table format.
+    pub fn to_table(&self) -> TypeTable {
+        TypeTable {
+            mbr: self.mbr.values().cloned().collect(),
+            gpt: self.gpt.values().cloned().collect(),
+        }
+    }
+}
+
+fn global() -> &'static RwLock<PartitionTypeRegistry> {
+    static REGISTRY: OnceLock<RwLock<PartitionTypeRegistry>> = OnceLock::new();
+    REGISTRY.get_or_init(|| RwLock::new(PartitionTypeRegistry::builtin()))
+}
+
+/// The registry the table parsers use.
+pub fn registry() -> RwLockReadGuard<'static, PartitionTypeRegistry> {
+    global().read().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Change the registry the table parsers use, e.g. to register vendor types.
+pub fn update_registry<F: FnOnce(&mut PartitionTypeRegistry) -> T, T>(update: F) -> T {
+    let mut registry = global().write().unwrap_or_else(|e| e.into_inner());
+    update(&mut registry)
+}
+
+/// Add the types of a user-supplied JSON table file to the registry the parsers use.
+pub fn register_file(path: &Path) -> Result<(), Box<dyn Error>> {
+    update_registry(|registry| registry.load_file(path))
+}
+
+pub fn mbr_type(partition_type: u8) -> Option<PartitionType> {
+    registry().mbr(partition_type).cloned()
+}
+
+pub fn gpt_type(type_guid: &str) -> Option<PartitionType> {
+    registry().gpt(type_guid).cloned()
+}
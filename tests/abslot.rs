@@ -0,0 +1,136 @@
+use exhume_partitions::abslot::{slot_of, Platform};
+use exhume_partitions::report::PartitionsReport;
+
+mod common;
+use common::gpt_disk;
+
+const CHROMEOS_KERNEL: &str = "fe3a2a5d-4f32-41a7-b725-accc3285a309";
+const CHROMEOS_ROOTFS: &str = "3cb8e202-3b7e-47dd-8a3c-7ff2a13cfcec";
+const LINUX: &str = "0fc63daf-8483-4772-8e79-3d69d8477de4";
+const ANDROID_BOOT: &str = "49a4d17f-93a3-45c1-a0de-f50b2ebe2599";
+
+/// ChromeOS kernel attributes: priority, tries left and successful.
+fn kernel(priority: u64, tries: u64, successful: bool) -> u64 {
+    (priority | tries << 4 | (successful as u64) << 8) << 48
+}
+
+/// Android boot attributes: priority, active, retry count, successful and unbootable.
+fn boot(priority: u64, active: bool, retries: u64, successful: bool, unbootable: bool) -> u64 {
+    (priority
+        | (active as u64) << 2
+        | retries << 3
+        | (successful as u64) << 6
+        | (unbootable as u64) << 7)
+        << 48
+}
+
+#[test]
+fn picks_chromeos_kernel_by_priority() {
+    let partitions = gpt_disk(&[
+        (CHROMEOS_KERNEL, "KERN-A", kernel(1, 0, true)),
+        (CHROMEOS_ROOTFS, "ROOT-A", 0),
+        (CHROMEOS_KERNEL, "KERN-B", kernel(2, 6, false)),
+        (CHROMEOS_ROOTFS, "ROOT-B", 0),
+        (LINUX, "STATE", 0),
+    ]);
+    let gpt = partitions.gpt.as_ref().unwrap();
+    assert_eq!(gpt.partition_entries[0].description, "ChromeOS kernel");
+    assert_eq!(
+        gpt.partition_entries[2].attribute_flags(),
+        ["Priority 2", "Tries 6"]
+    );
+
+    let all = partitions.ab_slots();
+    assert_eq!(all.len(), 1);
+    let slots = &all[0];
+    assert_eq!(slots.platform, Platform::ChromeOS);
+    assert_eq!(slots.active_slot.as_deref(), Some("B"));
+    assert_eq!(slots.decided_by.as_deref(), Some("KERN"));
+    assert!(slots.issues.is_empty(), "{:?}", slots.issues);
+    let groups: Vec<&str> = slots.groups.iter().map(|g| g.base_name.as_str()).collect();
+    assert_eq!(groups, ["KERN", "ROOT"]);
+    assert_eq!(slots.groups[0].members[1].partition_index, 3);
+
+    // Out of tries and never successful: the other kernel boots.
+    let partitions = gpt_disk(&[
+        (CHROMEOS_KERNEL, "KERN-A", kernel(1, 0, true)),
+        (CHROMEOS_KERNEL, "KERN-B", kernel(2, 0, false)),
+    ]);
+    assert_eq!(partitions.ab_slots()[0].active_slot.as_deref(), Some("A"));
+}
+
+#[test]
+fn groups_android_slots_and_finds_active_one() {
+    let partitions = gpt_disk(&[
+        (ANDROID_BOOT, "boot_a", boot(3, false, 0, true, false)),
+        (ANDROID_BOOT, "boot_b", boot(3, true, 7, false, false)),
+        (LINUX, "system_a", 0),
+        (LINUX, "system_b", 0),
+        (LINUX, "vbmeta_a", 0),
+        (LINUX, "userdata", 0),
+    ]);
+    let all = partitions.ab_slots();
+    assert_eq!(all.len(), 1);
+    let slots = &all[0];
+    assert_eq!(slots.platform, Platform::Android);
+    assert_eq!(slots.active_slot.as_deref(), Some("b"));
+    assert_eq!(slots.decided_by.as_deref(), Some("boot"));
+    assert_eq!(slots.issues, ["vbmeta_a has a single slot"]);
+    assert_eq!(slots.groups.len(), 3);
+    assert!(slots.groups[1].members.iter().all(|m| m.boot.is_none()));
+
+    let report = PartitionsReport::new(&partitions, None);
+    assert_eq!(report.ab_slots[0].active_slot.as_deref(), Some("b"));
+    let flags = &report.gpt.as_ref().unwrap().entries[1].attribute_flags;
+    assert_eq!(flags, &["Priority 3", "Tries 7", "Active"]);
+}
+
+#[test]
+fn flags_unbootable_or_conflicting_android_slots() {
+    let partitions = gpt_disk(&[
+        (ANDROID_BOOT, "boot_a", boot(0, true, 0, false, true)),
+        (ANDROID_BOOT, "boot_b", boot(1, false, 0, true, false)),
+    ]);
+    let slots = &partitions.ab_slots()[0];
+    assert_eq!(slots.active_slot.as_deref(), Some("a"));
+    assert_eq!(
+        slots.issues,
+        ["Active slot a is not bootable (priority 0, retry count 0, unbootable)"]
+    );
+
+    let partitions = gpt_disk(&[
+        (ANDROID_BOOT, "boot_a", boot(1, true, 0, true, false)),
+        (ANDROID_BOOT, "boot_b", boot(2, true, 0, true, false)),
+    ]);
+    let slots = &partitions.ab_slots()[0];
+    assert_eq!(slots.active_slot.as_deref(), Some("b"));
+    assert_eq!(slots.issues, ["Several slots are marked active"]);
+
+    let partitions = gpt_disk(&[(LINUX, "super_a", 0), (LINUX, "super_b", 0)]);
+    let slots = &partitions.ab_slots()[0];
+    assert_eq!(slots.active_slot, None);
+    assert_eq!(slots.issues.len(), 1);
+}
+
+#[test]
+fn decodes_generic_attribute_bits() {
+    let partitions = gpt_disk(&[(LINUX, "data", 1 << 2 | 1 | 1 << 10 | 1 << 62)]);
+    let entry = &partitions.gpt.as_ref().unwrap().partition_entries[0];
+    assert_eq!(
+        entry.attribute_flags(),
+        [
+            "Required by the platform",
+            "Legacy BIOS bootable",
+            "Reserved bits 0x400",
+            "Type-specific bits 0x4000",
+        ]
+    );
+    assert!(partitions.ab_slots().is_empty());
+
+    assert_eq!(
+        slot_of(CHROMEOS_KERNEL, "KERN-A"),
+        Some((Platform::ChromeOS, "KERN".to_string(), "A".to_string()))
+    );
+    assert_eq!(slot_of(LINUX, "KERN-A"), None);
+    assert_eq!(slot_of(LINUX, "my_data"), None);
+}
@@ -0,0 +1,129 @@
+use exhume_partitions::bitlocker::{self, filetime_to_string};
+use std::io::Cursor;
+
+mod common;
+
+/// A description entry (type 0x0007, UTF-16LE string value).
+fn description_entry(text: &str) -> Vec<u8> {
+    let mut value: Vec<u8> = text.encode_utf16().flat_map(u16::to_le_bytes).collect();
+    value.extend([0, 0]);
+    let mut entry = Vec::new();
+    entry.extend(((8 + value.len()) as u16).to_le_bytes());
+    entry.extend(0x0007u16.to_le_bytes());
+    entry.extend(0x0002u16.to_le_bytes());
+    entry.extend(1u16.to_le_bytes());
+    entry.extend(value);
+    entry
+}
+
+fn parse(volume: Vec<u8>) -> bitlocker::BitLockerInfo {
+    bitlocker::parse(&mut Cursor::new(volume), 0).unwrap()
+}
+
+#[test]
+fn parses_the_three_metadata_blocks() {
+    let info = parse(common::bitlocker_volume(
+        0x8001,
+        &[0x0500, 0x0800],
+        &description_entry("DESKTOP-XYZ C: 2024-01-01"),
+    ));
+    assert_eq!(info.blocks.len(), 3);
+    for (block, offset) in info.blocks.iter().zip(common::FVE_BLOCKS) {
+        assert_eq!(block.offset, offset);
+        assert_eq!(block.version, 2);
+        assert_eq!(block.metadata_offsets, common::FVE_BLOCKS.to_vec());
+        assert_eq!(block.volume_guid, common::BITLOCKER_VOLUME_GUID);
+        assert_eq!(block.entries.len(), 3);
+    }
+    assert_eq!(
+        info.encryption_method.as_deref(),
+        Some("AES-256-CBC with Elephant diffuser")
+    );
+    assert_eq!(
+        info.description.as_deref(),
+        Some("DESKTOP-XYZ C: 2024-01-01")
+    );
+    assert_eq!(info.creation_time.as_deref(), Some("2024-01-01T02:56:30Z"));
+}
+
+#[test]
+fn lists_protectors_with_their_key_ids() {
+    let info = parse(common::bitlocker_volume(0x8004, &[0x0100, 0x0800], &[]));
+    assert_eq!(info.protectors, vec!["TPM", "Recovery password"]);
+    assert_eq!(info.volume_master_keys.len(), 2);
+    assert_eq!(
+        info.volume_master_keys[1].key_id,
+        "41414141-4141-4141-4141-414141414141"
+    );
+    assert_eq!(
+        info.recovery_key_ids(),
+        vec!["41414141-4141-4141-4141-414141414141"]
+    );
+}
+
+#[test]
+fn survives_a_damaged_metadata_block() {
+    let mut volume = common::bitlocker_volume(0x8004, &[0x0800], &[]);
+    let first = common::FVE_BLOCKS[0] as usize;
+    volume[first..first + 8].copy_from_slice(&[0; 8]);
+    let info = parse(volume);
+    assert_eq!(info.blocks.len(), 2);
+    assert_eq!(info.blocks[0].offset, common::FVE_BLOCKS[1]);
+    assert_eq!(info.protectors, vec!["Recovery password"]);
+}
+
+#[test]
+fn ignores_other_boot_sectors() {
+    let mut ntfs = vec![0u8; 4096];
+    ntfs[3..11].copy_from_slice(b"NTFS    ");
+    assert!(bitlocker::parse(&mut Cursor::new(ntfs), 0).is_none());
+}
+
+#[test]
+fn converts_filetimes() {
+    assert_eq!(filetime_to_string(0), None);
+    assert_eq!(
+        filetime_to_string(116_444_736_000_000_000).as_deref(),
+        Some("1970-01-01T00:00:00Z")
+    );
+}
+
+#[test]
+fn survives_out_of_range_offsets() {
+    // Vista header: the first block is given in clusters.
+    let mut vista = common::bitlocker_volume(0x8004, &[0x0800], &[]);
+    vista[0xA0..0xC8].fill(0);
+    vista[0x38..0x40].copy_from_slice(&u64::MAX.to_le_bytes());
+    assert!(bitlocker::parse(&mut Cursor::new(vista), 0).is_none());
+
+    // A block offset that overflows once added to the volume offset.
+    let mut volume = vec![0u8; 512];
+    volume.extend(common::bitlocker_volume(0x8004, &[0x0800], &[]));
+    volume[512 + 0xB0..512 + 0xB8].copy_from_slice(&u64::MAX.to_le_bytes());
+    let info = bitlocker::parse(&mut Cursor::new(volume), 512).unwrap();
+    assert_eq!(info.blocks.len(), 2);
+}
+
+#[test]
+fn stops_at_the_nesting_depth_bitlocker_writes() {
+    // Use-key entries nested in one another as deep as the 16-bit entry sizes allow.
+    let mut entry = Vec::new();
+    for _ in 0..5000 {
+        let mut outer = Vec::new();
+        outer.extend(((12 + entry.len()) as u16).to_le_bytes());
+        outer.extend(0x0000u16.to_le_bytes());
+        outer.extend(bitlocker::VALUE_TYPE_USE_KEY.to_le_bytes());
+        outer.extend(1u16.to_le_bytes());
+        outer.extend(0x8004u32.to_le_bytes());
+        outer.extend(entry);
+        entry = outer;
+    }
+    let entries = bitlocker::parse_entries(&entry);
+    let mut depth = 0;
+    let mut level = &entries;
+    while let Some(first) = level.first() {
+        depth += 1;
+        level = &first.properties;
+    }
+    assert_eq!(depth, 3);
+}
@@ -0,0 +1,149 @@
+#![cfg(feature = "disassembly")]
+
+use exhume_partitions::bootcode::{BootCode, LOAD_ADDRESS};
+use exhume_partitions::builder::DiskImageBuilder;
+use exhume_partitions::report::PartitionsReport;
+use exhume_partitions::Partitions;
+use serde_json::json;
+use std::io::Cursor;
+
+/// The 440-byte code area of an MBR starting with `code`.
+fn code_area(code: &[u8]) -> Vec<u8> {
+    let mut area = code.to_vec();
+    area.resize(440, 0);
+    area
+}
+
+fn instructions(bootcode: &BootCode) -> Vec<(u32, String)> {
+    bootcode
+        .instructions
+        .iter()
+        .map(|i| (i.address, format!("{} {}", i.mnemonic, i.operands)))
+        .collect()
+}
+
+#[test]
+fn follows_jumps_and_calls() {
+    #[rustfmt::skip]
+    let code = code_area(&[
+        0xEB, 0x06,             // 7C00: jmp 0x7c08
+        b'H', b'e', b'l', b'l', b'o', 0x00,
+        0xE8, 0x02, 0x00,       // 7C08: call 0x7c0d
+        0xCD, 0x18,             // 7C0B: int 0x18
+        0xB4, 0x0E,             // 7C0D: mov ah, 0xe
+        0xC3,                   // 7C0F: ret
+    ]);
+    let mut partitions = Partitions::new(&mut Cursor::new(
+        DiskImageBuilder::new(2048)
+            .boot_code(&code)
+            .primary(0x83, 63, 1000)
+            .build()
+            .unwrap(),
+    ))
+    .unwrap();
+    let bootcode = partitions.mbr.as_ref().unwrap().disassemble_bootloader();
+    assert_eq!(bootcode.load_address, LOAD_ADDRESS);
+    assert_eq!(bootcode.relocation_address, None);
+    assert_eq!(
+        instructions(&bootcode),
+        [
+            (0x7C00, "jmp 0x7c08".to_string()),
+            (0x7C08, "call 0x7c0d".to_string()),
+            (0x7C0B, "int 0x18".to_string()),
+            (0x7C0D, "mov ah, 0xe".to_string()),
+            (0x7C0F, "ret ".to_string()),
+        ]
+    );
+    assert_eq!(bootcode.instructions[1].offset, 8);
+    assert_eq!(bootcode.instructions[1].bytes, [0xE8, 0x02, 0x00]);
+
+    // What is jumped over is data, and so is the zero padding up to the partition table.
+    let data: Vec<(u32, usize, usize)> = bootcode
+        .data
+        .iter()
+        .map(|d| (d.address, d.offset, d.length))
+        .collect();
+    assert_eq!(data, [(0x7C02, 2, 6), (0x7C10, 16, 430)]);
+    assert_eq!(bootcode.strings.len(), 1);
+    assert_eq!(bootcode.strings[0].address, 0x7C02);
+    assert_eq!(bootcode.strings[0].value, "Hello");
+
+    let listing = bootcode.listing();
+    assert!(listing.starts_with("0x7c00: eb 06"), "{}", listing);
+    assert!(listing.ends_with("0x7c02: \"Hello\""), "{}", listing);
+
+    // The report carries the disassembly once it was requested.
+    assert!(PartitionsReport::new(&partitions, None).bootcode.is_none());
+    partitions.bootcode = Some(bootcode);
+    let report = serde_json::to_value(PartitionsReport::new(&partitions, None)).unwrap();
+    assert_eq!(
+        report["bootcode"]["instructions"][1],
+        json!({
+            "address": 0x7C08,
+            "offset": 8,
+            "bytes": "e80200",
+            "mnemonic": "call",
+            "operands": "0x7c0d"
+        })
+    );
+    assert_eq!(report["bootcode"]["strings"][0]["value"], "Hello");
+}
+
+#[test]
+fn follows_relocation() {
+    #[rustfmt::skip]
+    let code = code_area(&[
+        0x31, 0xC0,                   // 7C00: xor ax, ax
+        0x8E, 0xD8,                   // 7C02: mov ds, ax
+        0xBE, 0x00, 0x7C,             // 7C04: mov si, 0x7c00
+        0xBF, 0x00, 0x06,             // 7C07: mov di, 0x600
+        0xB9, 0x00, 0x01,             // 7C0A: mov cx, 0x100
+        0xF3, 0xA5,                   // 7C0D: rep movsw
+        0xEA, 0x14, 0x06, 0x00, 0x00, // 7C0F: ljmp 0:0x614
+        0xCD, 0x19,                   // 0614: int 0x19
+    ]);
+    let bootcode = BootCode::disassemble(&code);
+    assert_eq!(bootcode.relocation_address, Some(0x600));
+    let last = bootcode.instructions.last().unwrap();
+    assert_eq!((last.address, last.offset), (0x614, 0x14));
+    assert_eq!(last.mnemonic, "int");
+    assert_eq!(bootcode.instructions.len(), 8);
+    // Data is addressed where the code runs.
+    assert_eq!(bootcode.data[0].address, 0x616);
+    assert!(bootcode
+        .listing()
+        .starts_with("; loaded at 0x7c00, relocated to 0x0600\n"));
+}
+
+#[test]
+fn ignores_empty_boot_code() {
+    let bootcode = BootCode::disassemble(&[0u8; 440]);
+    assert!(bootcode.instructions.is_empty());
+    assert!(bootcode.data.is_empty());
+    assert_eq!(bootcode.listing(), "");
+}
+
+#[test]
+fn table_lists_the_disassembled_boot_code_once() {
+    let mut partitions = Partitions::new(&mut Cursor::new(
+        DiskImageBuilder::new(8192)
+            .boot_code(&code_area(&[0xCD, 0x18]))
+            .primary(0x83, 2048, 1024)
+            .extended(4096, 4096)
+            .logical(0x83, 4160, 2048)
+            .build()
+            .unwrap(),
+    ))
+    .unwrap();
+    let table = partitions.print_info(true);
+    assert_eq!(table.matches("Bootloader").count(), 1, "{}", table);
+    assert!(table.contains("int 0x18"), "{}", table);
+
+    // The listing is rendered from `bootcode` once it was filled.
+    let mut bootcode = partitions.mbr.as_ref().unwrap().disassemble_bootloader();
+    bootcode.instructions[0].mnemonic = "filled".to_string();
+    partitions.bootcode = Some(bootcode);
+    let table = partitions.print_info(true);
+    assert!(table.contains("filled"), "{}", table);
+    assert!(!partitions.print_info(false).contains("Bootloader"));
+}
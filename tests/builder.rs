@@ -0,0 +1,277 @@
+use exhume_body::Body;
+use exhume_partitions::builder::{Corruption, DiskImageBuilder, DiskLayout};
+use exhume_partitions::extent::Scheme;
+use exhume_partitions::reader::WithSectorSize;
+use exhume_partitions::Partitions;
+use tempfile::TempDir;
+
+const ESP: &str = "c12a7328-f81f-11d2-ba4b-00a0c93ec93b";
+const LINUX: &str = "0fc63daf-8483-4772-8e79-3d69d8477de4";
+const DISK: &str = "5e0b1c39-3c4a-4b4e-9d57-2f1e0a7b8c11";
+
+fn discover(builder: &DiskImageBuilder) -> Partitions {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("disk.raw");
+    builder.write_to(&path).unwrap();
+    let mut body = Body::new(path.to_string_lossy().to_string(), "raw");
+    Partitions::new(&mut body).unwrap()
+}
+
+fn gpt_disk() -> DiskImageBuilder {
+    DiskImageBuilder::new(8192)
+        .gpt(DISK)
+        .gpt_partition(
+            ESP,
+            "11111111-2222-3333-4444-555555555555",
+            "EFI",
+            2048,
+            4095,
+        )
+        .gpt_partition(
+            LINUX,
+            "66666666-7777-8888-9999-aaaaaaaaaaaa",
+            "root",
+            4096,
+            8000,
+        )
+}
+
+#[test]
+fn mbr_with_logical_partitions() {
+    let builder = DiskImageBuilder::new(16384)
+        .primary(0x07, 2048, 2048)
+        .bootable(0)
+        .extended(4096, 12288)
+        .logical(0x83, 4097, 1000)
+        .logical(0x82, 6000, 2000)
+        .logical(0x0B, 10000, 4000);
+    let partitions = discover(&builder);
+
+    let mbr = partitions.mbr.as_ref().unwrap();
+    assert_eq!(mbr.partition_table[0].boot_indicator, 0x80);
+    let ebr = partitions.ebr.as_ref().unwrap();
+    let tables: Vec<u64> = ebr.iter().map(|e| e.table_lba).collect();
+    assert_eq!(tables, [4096, 5999, 9999]);
+
+    let logicals: Vec<(u64, u64)> = partitions
+        .extents()
+        .iter()
+        .filter(|e| e.scheme == Scheme::EBR)
+        .map(|e| (e.start_lba, e.size_sectors))
+        .collect();
+    assert_eq!(logicals, [(4097, 1000), (6000, 2000), (10000, 4000)]);
+    assert!(partitions.gpt.is_none());
+}
+
+#[test]
+fn gpt_with_guids_and_names() {
+    let partitions = discover(&gpt_disk());
+    assert!(partitions.mbr.as_ref().unwrap().is_pmbr());
+    let gpt = partitions.gpt.as_ref().unwrap();
+    assert!(gpt.header.is_crc32_valid());
+    assert_eq!(gpt.header.disk_guid_string, DISK);
+    assert_eq!(gpt.header.current_lba, 1);
+    assert_eq!(gpt.header.backup_lba, 8191);
+    assert_eq!(
+        gpt.compute_partition_array_crc32(),
+        gpt.header.partition_array_crc32
+    );
+
+    let extents = partitions.extents();
+    assert_eq!(extents.len(), 2);
+    assert_eq!(extents[0].name.as_deref(), Some("EFI"));
+    assert_eq!(
+        extents[1].guid.as_deref(),
+        Some("66666666-7777-8888-9999-aaaaaaaaaaaa")
+    );
+    assert_eq!(extents[1].partition_type, LINUX);
+    assert_eq!(extents[1].size_sectors, 3905);
+}
+
+#[test]
+fn hybrid_mbr() {
+    let partitions = discover(&gpt_disk().primary(0xEF, 2048, 2048));
+    let mbr = partitions.mbr.as_ref().unwrap();
+    assert!(mbr.is_pmbr());
+    assert_eq!(mbr.partition_table[0].partition_type, 0xEE);
+    assert_eq!(mbr.partition_table[0].size_sectors, 33);
+    assert_eq!(mbr.partition_table[1].partition_type, 0xEF);
+    assert_eq!(mbr.partition_table[1].start_lba, 2048);
+    assert_eq!(partitions.gpt.as_ref().unwrap().partition_entries.len(), 2);
+}
+
+#[test]
+fn bad_crcs_are_detectable() {
+    let partitions = discover(&gpt_disk().corrupt(Corruption::BadHeaderCrc));
+    assert!(!partitions.gpt.as_ref().unwrap().header.is_crc32_valid());
+
+    let partitions = discover(&gpt_disk().corrupt(Corruption::BadPartitionArrayCrc));
+    let gpt = partitions.gpt.as_ref().unwrap();
+    assert!(gpt.header.is_crc32_valid());
+    assert!(!gpt.is_partition_array_crc32_valid());
+}
+
+#[test]
+fn array_crc_covers_the_unused_entries_as_read() {
+    // Bytes in an unused entry and an unpaired surrogate in a name, under valid CRCs.
+    let mut image = gpt_disk().build().unwrap();
+    image[2 * 512 + 5 * 128 + 40] = 0xA5;
+    image[2 * 512 + 128 + 56 + 11] = 0xD8;
+    let array_crc32 = crc32fast::hash(&image[2 * 512..34 * 512]);
+    image[512 + 88..512 + 92].copy_from_slice(&array_crc32.to_le_bytes());
+    image[512 + 16..512 + 20].fill(0);
+    let header_crc32 = crc32fast::hash(&image[512..512 + 92]);
+    image[512 + 16..512 + 20].copy_from_slice(&header_crc32.to_le_bytes());
+
+    let partitions = Partitions::new(&mut std::io::Cursor::new(&image)).unwrap();
+    let gpt = partitions.gpt.as_ref().unwrap();
+    assert!(gpt.header.is_crc32_valid());
+    assert!(gpt.is_partition_array_crc32_valid());
+    assert_eq!(
+        gpt.partition_entries[1].to_bytes().to_vec(),
+        image[2 * 512 + 128..2 * 512 + 256].to_vec()
+    );
+}
+
+#[test]
+fn missing_primary_gpt_falls_back_to_backup() {
+    let partitions = discover(&gpt_disk().corrupt(Corruption::MissingPrimaryGpt));
+    let gpt = partitions.gpt.as_ref().unwrap();
+    assert_eq!(gpt.header.current_lba, 8191);
+    assert_eq!(gpt.header.partition_entry_lba, 8191 - 32);
+    assert!(gpt.header.is_crc32_valid());
+    assert_eq!(gpt.partition_entries.len(), 2);
+}
+
+#[test]
+fn looping_ebr_chain_terminates() {
+    let builder = DiskImageBuilder::new(16384)
+        .extended(2048, 14336)
+        .logical(0x83, 2049, 1000)
+        .logical(0x83, 4000, 1000)
+        .corrupt(Corruption::LoopingEbr);
+    let partitions = discover(&builder);
+    assert_eq!(partitions.ebr.as_ref().unwrap().len(), 2);
+    let ebr = partitions.ebr.as_ref().unwrap();
+    assert_eq!(ebr[1].partition_table[1].start_lba, 2048);
+}
+
+#[test]
+fn overlapping_entries() {
+    let partitions = discover(&gpt_disk().corrupt(Corruption::OverlappingEntries));
+    let extents = partitions.extents();
+    assert!(extents[0].start_lba + extents[0].size_sectors > extents[1].start_lba);
+
+    let builder = DiskImageBuilder::new(8192)
+        .primary(0x07, 2048, 100)
+        .primary(0x83, 4096, 100)
+        .corrupt(Corruption::OverlappingEntries);
+    let extents = discover(&builder).extents();
+    assert_eq!(extents[0].start_lba + extents[0].size_sectors, 4097);
+}
+
+#[test]
+fn in_memory_and_file_images_match() {
+    let builder = gpt_disk().write_at(2048, b"\xEB\x58\x90MSDOS5.0");
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("disk.raw");
+    builder.write_to(&path).unwrap();
+    let image = builder.build().unwrap();
+    assert_eq!(std::fs::read(&path).unwrap(), image);
+    assert_eq!(&image[2048 * 512 + 3..2048 * 512 + 11], b"MSDOS5.0");
+}
+
+#[test]
+fn layout_from_json() {
+    let layout: DiskLayout = serde_json::from_str(
+        r#"{
+            "total_sectors": 4096,
+            "primaries": [{ "partition_type": 131, "start_lba": 2048, "size_sectors": 2048 }],
+            "corruptions": []
+        }"#,
+    )
+    .unwrap();
+    let partitions = discover(&DiskImageBuilder::from_layout(layout));
+    assert_eq!(partitions.extents()[0].partition_type, "0x83");
+}
+
+#[test]
+fn invalid_layouts_are_rejected() {
+    assert!(DiskImageBuilder::new(1024)
+        .primary(0x83, 2048, 10)
+        .build()
+        .is_err());
+    assert!(DiskImageBuilder::new(8192)
+        .logical(0x83, 2049, 10)
+        .build()
+        .is_err());
+    assert!(DiskImageBuilder::new(8192)
+        .gpt("not a guid")
+        .build()
+        .is_err());
+    // Checked before any LBA is derived from the disk size.
+    for total_sectors in [0, 1, 68] {
+        assert!(DiskImageBuilder::new(total_sectors)
+            .gpt(DISK)
+            .build()
+            .is_err());
+    }
+    assert!(DiskImageBuilder::new(69).gpt(DISK).build().is_ok());
+    // Logical partitions past the end of the extended one.
+    assert!(DiskImageBuilder::new(8192)
+        .extended(2048, 1024)
+        .logical(0x83, 2049, 1024)
+        .build()
+        .is_err());
+}
+
+#[test]
+fn links_logical_partitions_ending_past_2_pow_32() {
+    // A 2 TiB sparse image whose last logical partition ends at LBA 2^32.
+    let partitions = discover(
+        &DiskImageBuilder::new(1 << 32)
+            .extended(0x1000_0000, 0xF000_0000)
+            .logical(0x83, 0x1000_0001, 10)
+            .logical(0x83, 0xE000_0000, 0x2000_0000),
+    );
+    let extents = partitions.extents();
+    assert_eq!(extents.len(), 2);
+    assert_eq!(extents[1].start_lba + extents[1].size_sectors, 1 << 32);
+}
+
+#[test]
+fn gpt_arrays_fit_in_large_sectors() {
+    // The 16 KiB entry array takes one sector of 32 KiB.
+    let image = DiskImageBuilder::new(64)
+        .sector_size(32768)
+        .gpt(DISK)
+        .gpt_partition(LINUX, "11111111-2222-3333-4444-555555555555", "root", 8, 60)
+        .build()
+        .unwrap();
+    let mut reader = WithSectorSize::new(std::io::Cursor::new(image), 32768);
+    let partitions = Partitions::new(&mut reader).unwrap();
+    let gpt = partitions.gpt.as_ref().unwrap();
+    assert_eq!(gpt.header.first_usable_lba, 3);
+    assert_eq!(gpt.header.last_usable_lba, 61);
+    assert!(gpt.is_partition_array_crc32_valid());
+    assert_eq!(partitions.extents()[0].first_byte_addr, 8 * 32768);
+}
+
+#[test]
+fn overflowing_gpt_entries_are_skipped() {
+    // The second entry ends past what 64-bit byte addresses reach.
+    let mut image = gpt_disk().build().unwrap();
+    let ending_lba = 2 * 512 + 128 + 40;
+    image[ending_lba..ending_lba + 8].copy_from_slice(&(1u64 << 62).to_le_bytes());
+
+    let mut body = std::io::Cursor::new(&image);
+    let partitions = Partitions::new(&mut body).unwrap();
+    assert_eq!(partitions.gpt.as_ref().unwrap().partition_entries.len(), 2);
+    let extents = partitions.extents();
+    assert_eq!(extents.len(), 1);
+    assert_eq!(extents[0].name.as_deref(), Some("EFI"));
+
+    assert!(partitions.compute_hashes(&mut body).is_ok());
+    assert!(partitions.compute_entropy(&mut body, 4096).is_ok());
+    assert!(partitions.print_info(false).contains("EFI"));
+}
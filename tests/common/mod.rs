@@ -0,0 +1,220 @@
+//! Hand-written on-disk structures shared by the integration tests.
+#![allow(dead_code)]
+
+pub const ESP_TYPE: [u8; 16] = [
+    0x28, 0x73, 0x2a, 0xc1, 0x1f, 0xf8, 0xd2, 0x11, 0xba, 0x4b, 0x00, 0xa0, 0xc9, 0x3e, 0xc9, 0x3b,
+];
+
+/// A 512-byte MBR with the given (boot indicator, type, start LBA, size) entries.
+pub fn mbr_sector(entries: &[(u8, u8, u32, u32)]) -> Vec<u8> {
+    let mut sector = vec![0u8; 512];
+    for (i, (boot, ptype, start, size)) in entries.iter().enumerate() {
+        let e = 446 + i * 16;
+        sector[e] = *boot;
+        sector[e + 4] = *ptype;
+        sector[e + 8..e + 12].copy_from_slice(&start.to_le_bytes());
+        sector[e + 12..e + 16].copy_from_slice(&size.to_le_bytes());
+    }
+    sector[510] = 0x55;
+    sector[511] = 0xAA;
+    sector
+}
+
+/// Primary GPT header of a 2048-sector disk, array at LBA 2, CRC fields left as given.
+pub fn gpt_header(header_crc32: u32, partition_array_crc32: u32) -> Vec<u8> {
+    let mut h = vec![0u8; 92];
+    h[0..8].copy_from_slice(b"EFI PART");
+    h[8..12].copy_from_slice(&0x0001_0000u32.to_le_bytes());
+    h[12..16].copy_from_slice(&92u32.to_le_bytes());
+    h[16..20].copy_from_slice(&header_crc32.to_le_bytes());
+    h[24..32].copy_from_slice(&1u64.to_le_bytes());
+    h[32..40].copy_from_slice(&2047u64.to_le_bytes());
+    h[40..48].copy_from_slice(&34u64.to_le_bytes());
+    h[48..56].copy_from_slice(&2014u64.to_le_bytes());
+    h[56..72].copy_from_slice(&[0x11; 16]);
+    h[72..80].copy_from_slice(&2u64.to_le_bytes());
+    h[80..84].copy_from_slice(&128u32.to_le_bytes());
+    h[84..88].copy_from_slice(&128u32.to_le_bytes());
+    h[88..92].copy_from_slice(&partition_array_crc32.to_le_bytes());
+    h
+}
+
+/// An EFI System partition entry spanning LBA 34 to 1057.
+pub fn gpt_entry(name: &str, attributes: u64) -> Vec<u8> {
+    let mut e = vec![0u8; 128];
+    e[0..16].copy_from_slice(&ESP_TYPE);
+    e[16..32].copy_from_slice(&[0x22; 16]);
+    e[32..40].copy_from_slice(&34u64.to_le_bytes());
+    e[40..48].copy_from_slice(&1057u64.to_le_bytes());
+    e[48..56].copy_from_slice(&attributes.to_le_bytes());
+    for (i, c) in name.encode_utf16().enumerate() {
+        e[56 + i * 2..58 + i * 2].copy_from_slice(&c.to_le_bytes());
+    }
+    e
+}
+
+/// An 8192-sector GPT disk with one 256-sector partition per (type, name, attributes), from
+/// LBA 2048 on.
+pub fn gpt_disk(partitions: &[(&str, &str, u64)]) -> exhume_partitions::Partitions {
+    use exhume_partitions::builder::{DiskImageBuilder, GptPartitionSpec};
+    let mut builder = DiskImageBuilder::new(8192).gpt("5e0b1c39-3c4a-4b4e-9d57-2f1e0a7b8c11");
+    for (i, (type_guid, name, attributes)) in partitions.iter().enumerate() {
+        let first_lba = 2048 + i as u64 * 256;
+        builder = builder.gpt_partition_spec(GptPartitionSpec {
+            type_guid: type_guid.to_string(),
+            guid: format!("{:08x}-0000-4000-8000-000000000000", i + 1),
+            name: name.to_string(),
+            first_lba,
+            last_lba: first_lba + 255,
+            attributes: *attributes,
+        });
+    }
+    exhume_partitions::Partitions::new(&mut std::io::Cursor::new(builder.build().unwrap())).unwrap()
+}
+
+/// Deterministic pseudo-random bytes (xorshift64), standing in for ciphertext.
+pub fn random_bytes(len: usize, seed: u64) -> Vec<u8> {
+    let mut state = seed | 1;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state >> 24) as u8
+        })
+        .collect()
+}
+
+pub const BITLOCKER_VOLUME_GUID: &str = "8f3c1d2e-4b5a-4c6d-9e7f-0a1b2c3d4e5f";
+/// FVE metadata blocks of `bitlocker_volume`, in bytes from the volume start.
+pub const FVE_BLOCKS: [u64; 3] = [0x10000, 0x20000, 0x30000];
+
+fn put_guid(buf: &mut [u8], guid: &str) {
+    buf[..16].copy_from_slice(&exhume_partitions::gpt::parse_guid(guid).unwrap());
+}
+
+/// A 256 KiB BitLocker (Windows 7+) volume: its volume header, and three FVE metadata blocks
+/// with one VMK entry per protection type followed by the given `extra_entries`.
+pub fn bitlocker_volume(method: u16, protectors: &[u16], extra_entries: &[u8]) -> Vec<u8> {
+    let mut volume = vec![0u8; 0x40000];
+    volume[0..3].copy_from_slice(&[0xEB, 0x58, 0x90]);
+    volume[3..11].copy_from_slice(b"-FVE-FS-");
+    volume[0x0B..0x0D].copy_from_slice(&512u16.to_le_bytes());
+    volume[0x0D] = 8;
+    put_guid(&mut volume[0xA0..], "4967d63b-2e29-4ad8-8399-f6a339e3d001");
+    for (i, offset) in FVE_BLOCKS.iter().enumerate() {
+        volume[0xB0 + i * 8..0xB8 + i * 8].copy_from_slice(&offset.to_le_bytes());
+    }
+    volume[510] = 0x55;
+    volume[511] = 0xAA;
+
+    let mut entries = Vec::new();
+    for (i, protection) in protectors.iter().enumerate() {
+        let mut vmk = vec![0u8; 36];
+        vmk[0..2].copy_from_slice(&36u16.to_le_bytes());
+        vmk[2..4].copy_from_slice(&0x0002u16.to_le_bytes());
+        vmk[4..6].copy_from_slice(&0x0008u16.to_le_bytes());
+        vmk[6..8].copy_from_slice(&1u16.to_le_bytes());
+        vmk[8..24].copy_from_slice(&[0x40 + i as u8; 16]);
+        vmk[24..32].copy_from_slice(&0x01da_3c5e_1f2a_0000u64.to_le_bytes());
+        vmk[34..36].copy_from_slice(&protection.to_le_bytes());
+        entries.extend(vmk);
+    }
+    entries.extend_from_slice(extra_entries);
+
+    let metadata_size = 48 + entries.len() as u32;
+    for offset in FVE_BLOCKS {
+        let block = &mut volume[offset as usize..];
+        block[0..8].copy_from_slice(b"-FVE-FS-");
+        block[8..10].copy_from_slice(&64u16.to_le_bytes());
+        block[10..12].copy_from_slice(&2u16.to_le_bytes());
+        block[16..24].copy_from_slice(&0x40000u64.to_le_bytes());
+        for (i, offset) in FVE_BLOCKS.iter().enumerate() {
+            block[32 + i * 8..40 + i * 8].copy_from_slice(&offset.to_le_bytes());
+        }
+        let metadata = &mut block[64..];
+        metadata[0..4].copy_from_slice(&metadata_size.to_le_bytes());
+        metadata[4..8].copy_from_slice(&1u32.to_le_bytes());
+        metadata[8..12].copy_from_slice(&48u32.to_le_bytes());
+        metadata[12..16].copy_from_slice(&metadata_size.to_le_bytes());
+        put_guid(&mut metadata[16..], BITLOCKER_VOLUME_GUID);
+        metadata[32..36].copy_from_slice(&1u32.to_le_bytes());
+        metadata[36..38].copy_from_slice(&method.to_le_bytes());
+        metadata[40..48].copy_from_slice(&0x01da_3c5e_1f2a_0000u64.to_le_bytes());
+        metadata[48..48 + entries.len()].copy_from_slice(&entries);
+    }
+    volume
+}
+
+/// A LUKS1 header with AES-XTS, the first keyslot active.
+pub fn luks1_header() -> Vec<u8> {
+    let mut h = vec![0u8; 4096];
+    h[0..6].copy_from_slice(b"LUKS\xba\xbe");
+    h[6..8].copy_from_slice(&1u16.to_be_bytes());
+    h[8..11].copy_from_slice(b"aes");
+    h[40..52].copy_from_slice(b"xts-plain64\0");
+    h[72..78].copy_from_slice(b"sha256");
+    h[104..108].copy_from_slice(&4096u32.to_be_bytes());
+    h[108..112].copy_from_slice(&64u32.to_be_bytes());
+    h[168..204].copy_from_slice(b"0b5d6c9e-2f1a-4e3b-8c7d-6a5b4c3d2e1f");
+    for slot in 0..8 {
+        let s = 208 + slot * 48;
+        let active: u32 = if slot == 0 { 0x00AC_71F3 } else { 0x0000_DEAD };
+        h[s..s + 4].copy_from_slice(&active.to_be_bytes());
+        h[s + 4..s + 8].copy_from_slice(&100_000u32.to_be_bytes());
+    }
+    h
+}
+
+pub const LUKS2_HEADER_SIZE: usize = 16384;
+
+/// One copy of a LUKS2 header: binary header and JSON area (16 KiB), with a valid checksum.
+pub fn luks2_copy(secondary: bool, seqid: u64, json: &str) -> Vec<u8> {
+    use sha2::{Digest, Sha256};
+    let mut h = vec![0u8; LUKS2_HEADER_SIZE];
+    h[0..6].copy_from_slice(if secondary {
+        b"SKUL\xba\xbe"
+    } else {
+        b"LUKS\xba\xbe"
+    });
+    h[6..8].copy_from_slice(&2u16.to_be_bytes());
+    h[8..16].copy_from_slice(&(LUKS2_HEADER_SIZE as u64).to_be_bytes());
+    h[16..24].copy_from_slice(&seqid.to_be_bytes());
+    h[24..30].copy_from_slice(b"vault0");
+    h[72..78].copy_from_slice(b"sha256");
+    h[168..204].copy_from_slice(b"3c2b1a09-8f7e-4d6c-9b5a-4f3e2d1c0b0a");
+    let hdr_offset = if secondary {
+        LUKS2_HEADER_SIZE as u64
+    } else {
+        0
+    };
+    h[256..264].copy_from_slice(&hdr_offset.to_be_bytes());
+    h[4096..4096 + json.len()].copy_from_slice(json.as_bytes());
+    let checksum = Sha256::digest(&h);
+    h[448..480].copy_from_slice(&checksum);
+    h
+}
+
+/// Both copies of a LUKS2 header, primary then secondary (32 KiB in total).
+pub fn luks2_header(json: &str) -> Vec<u8> {
+    let mut h = luks2_copy(false, 1, json);
+    h.extend(luks2_copy(true, 1, json));
+    h
+}
+
+pub const LUKS2_JSON: &str = r#"{
+  "keyslots": {
+    "0": {"type": "luks2", "key_size": 64, "kdf": {"type": "argon2id", "time": 4, "memory": 1048576, "cpus": 4, "salt": "AA=="}},
+    "1": {"type": "luks2", "key_size": 64, "kdf": {"type": "pbkdf2", "hash": "sha256", "iterations": 1000, "salt": "AA=="}}
+  },
+  "segments": {
+    "0": {"type": "crypt", "offset": "16777216", "size": "dynamic", "iv_tweak": "0", "encryption": "aes-xts-plain64", "sector_size": 512}
+  },
+  "tokens": {
+    "0": {"type": "systemd-tpm2", "keyslots": ["0"], "tpm2-pcrs": [7]}
+  },
+  "digests": {
+    "0": {"type": "pbkdf2", "keyslots": ["0", "1"], "segments": ["0"], "hash": "sha256", "iterations": 117448, "salt": "AA==", "digest": "AA=="}
+  },
+  "config": {"json_size": "12288", "keyslots_size": "16744448"}
+}"#;
@@ -0,0 +1,336 @@
+use aes::Aes256;
+use ccm::aead::{AeadInPlace, KeyInit};
+use ccm::consts::{U12, U16};
+use ccm::Ccm;
+use exhume_partitions::bitlocker;
+use exhume_partitions::builder::DiskImageBuilder;
+use exhume_partitions::decrypt::{
+    recovery_password_key, stretch_key, unlock, BitLockerKey, BitLockerReader, Fvek, SectorCipher,
+};
+use exhume_partitions::hash::to_hex;
+use exhume_partitions::nested::DiscoveryOptions;
+use exhume_partitions::Partitions;
+use sha2::{Digest, Sha256};
+use std::io::{Cursor, Read, Seek, SeekFrom};
+
+mod common;
+
+/// A valid recovery password, the key it encodes, and that key stretched with `SALT`
+/// (reference values computed with Python's hashlib).
+const PASSWORD: &str = "000011-000022-003300-044000-550000-720885-000077-000000";
+const PASSWORD_KEY: &str = "010002002c01a00f50c3ffff07000000";
+const STRETCHED: &str = "2472c129c0431a6e01c89c29ac480365d1f7f25ada814d7854999bb7d51fac45";
+const SALT: [u8; 16] = [
+    0xa0, 0xa1, 0xa2, 0xa3, 0xa4, 0xa5, 0xa6, 0xa7, 0xa8, 0xa9, 0xaa, 0xab, 0xac, 0xad, 0xae, 0xaf,
+];
+const VMK: [u8; 32] = [0x5a; 32];
+const VOLUME_SIZE: usize = 0x80000;
+const HEADER_OFFSET: u64 = 0x40000;
+const HEADER_SECTORS: u32 = 16;
+
+/// Discovery with the encryption pass on.
+fn options() -> DiscoveryOptions {
+    DiscoveryOptions {
+        encryption: true,
+        ..Default::default()
+    }
+}
+
+fn unhex(hex: &str) -> Vec<u8> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+fn entry(entry_type: u16, value_type: u16, value: &[u8]) -> Vec<u8> {
+    let mut entry = Vec::new();
+    entry.extend(((8 + value.len()) as u16).to_le_bytes());
+    entry.extend(entry_type.to_le_bytes());
+    entry.extend(value_type.to_le_bytes());
+    entry.extend(1u16.to_le_bytes());
+    entry.extend_from_slice(value);
+    entry
+}
+
+/// A key value (0x0001) as found once an AES-CCM value is decrypted.
+fn key_datum(method: u16, key: &[u8]) -> Vec<u8> {
+    let mut value = method.to_le_bytes().to_vec();
+    value.extend([0, 0]);
+    value.extend_from_slice(key);
+    entry(0x0000, 0x0001, &value)
+}
+
+/// An AES-CCM encrypted value (0x0005): nonce, tag, ciphertext.
+fn ccm_value(key: &[u8], nonce: u8, plaintext: &[u8]) -> Vec<u8> {
+    let nonce = [nonce; 12];
+    let mut ciphertext = plaintext.to_vec();
+    let tag = Ccm::<Aes256, U16, U12>::new_from_slice(key)
+        .unwrap()
+        .encrypt_in_place_detached(&nonce.into(), &[], &mut ciphertext)
+        .unwrap();
+    let mut value = nonce.to_vec();
+    value.extend_from_slice(&tag);
+    value.extend(ciphertext);
+    value
+}
+
+fn vmk_entry(key_id: u8, protection: u16, properties: &[u8]) -> Vec<u8> {
+    let mut value = vec![key_id; 16];
+    value.extend(0x01da_3c5e_1f2a_0000u64.to_le_bytes());
+    value.extend([0, 0]);
+    value.extend(protection.to_le_bytes());
+    value.extend_from_slice(properties);
+    entry(0x0002, 0x0008, &value)
+}
+
+/// Metadata entries: a recovery password VMK, a clear key VMK and the FVEK wrapped by the VMK.
+fn entries(method: u16, fvek: &[u8]) -> Vec<u8> {
+    let mut stretch = 0x1000u32.to_le_bytes().to_vec();
+    stretch.extend(SALT);
+    let mut recovery = entry(0x0000, 0x0003, &stretch);
+    recovery.extend(entry(
+        0x0000,
+        0x0005,
+        &ccm_value(&unhex(STRETCHED), 1, &key_datum(0x2000, &VMK)),
+    ));
+
+    let clear_key = [0x33u8; 32];
+    let mut clear = entry(
+        0x0000,
+        0x0001,
+        &[&[0x00, 0x20, 0, 0][..], &clear_key].concat(),
+    );
+    clear.extend(entry(
+        0x0000,
+        0x0005,
+        &ccm_value(&clear_key, 2, &key_datum(0x2000, &VMK)),
+    ));
+
+    let mut entries = vmk_entry(0x41, 0x0800, &recovery);
+    entries.extend(vmk_entry(0x42, 0x0000, &clear));
+    entries.extend(entry(
+        0x0003,
+        0x0005,
+        &ccm_value(&VMK, 3, &key_datum(method, fvek)),
+    ));
+    entries
+}
+
+fn fvek_for(method: u16) -> Vec<u8> {
+    (0..64).map(|i| 0x80 + i as u8).collect::<Vec<u8>>()[..match method {
+        0x8002 | 0x8004 => 32,
+        _ => 64,
+    }]
+        .to_vec()
+}
+
+/// The plaintext volume: an NTFS boot sector, then pseudo-random data.
+fn plaintext() -> Vec<u8> {
+    let mut volume = common::random_bytes(VOLUME_SIZE, 42);
+    volume[..512].fill(0);
+    volume[3..11].copy_from_slice(b"NTFS    ");
+    volume[510] = 0x55;
+    volume[511] = 0xAA;
+    volume
+}
+
+/// What the decrypted view should return: the plaintext, with the FVE metadata areas and the
+/// relocated volume header zeroed.
+fn expected_view() -> Vec<u8> {
+    let mut view = plaintext();
+    view[common::FVE_BLOCKS[0] as usize..HEADER_OFFSET as usize + HEADER_SECTORS as usize * 512]
+        .fill(0);
+    view
+}
+
+/// An encrypted BitLocker volume whose first sectors were moved to `HEADER_OFFSET`.
+fn encrypted_volume(method: u16) -> Vec<u8> {
+    let fvek = fvek_for(method);
+    let mut volume = common::bitlocker_volume(method, &[], &entries(method, &fvek));
+    volume.resize(VOLUME_SIZE, 0);
+    for block in common::FVE_BLOCKS {
+        let block = block as usize;
+        volume[block + 16..block + 24].copy_from_slice(&(VOLUME_SIZE as u64).to_le_bytes());
+        volume[block + 28..block + 32].copy_from_slice(&HEADER_SECTORS.to_le_bytes());
+        volume[block + 56..block + 64].copy_from_slice(&HEADER_OFFSET.to_le_bytes());
+    }
+
+    let cipher = SectorCipher::new(&Fvek { method, key: fvek }, 512).unwrap();
+    let plaintext = plaintext();
+    for (i, sector) in plaintext.chunks(512).enumerate() {
+        let offset = i as u64 * 512;
+        let (target, in_place) = if offset < HEADER_SECTORS as u64 * 512 {
+            (HEADER_OFFSET + offset, false)
+        } else {
+            (offset, true)
+        };
+        let metadata =
+            offset >= common::FVE_BLOCKS[0] && offset < HEADER_OFFSET + HEADER_SECTORS as u64 * 512;
+        if in_place && metadata {
+            continue;
+        }
+        let mut sector = sector.to_vec();
+        cipher.encrypt_sector(target, &mut sector);
+        volume[target as usize..target as usize + 512].copy_from_slice(&sector);
+    }
+    volume
+}
+
+fn info(volume: &[u8]) -> bitlocker::BitLockerInfo {
+    bitlocker::parse(&mut Cursor::new(volume.to_vec()), 0).unwrap()
+}
+
+fn sector_pattern() -> Vec<u8> {
+    (0..512).map(|i| i as u8).collect()
+}
+
+/// Reference ciphertext digests computed with Python's `cryptography`.
+#[test]
+fn matches_reference_ciphers() {
+    let cases = [
+        (
+            0x8004,
+            (0..32).collect::<Vec<u8>>(),
+            5 * 512,
+            "b1c483beb69bd3eda9116107abbb7fdedfc45a36ae27814f6f9a09d3b0b8ab9c",
+        ),
+        (
+            0x8005,
+            (0..64).collect(),
+            5 * 512,
+            "e42dfc0cdfba4b2167b76646959738ed3a9ed53847269fd15a7c578c7ca222cb",
+        ),
+        (
+            0x8003,
+            (0..32).collect(),
+            0x10000,
+            "4232a3e257ffa633a12cf9c3d216bb5b39375ecb369e9ce60a475c35287e0195",
+        ),
+    ];
+    for (method, key, offset, digest) in cases {
+        let cipher = SectorCipher::new(&Fvek { method, key }, 512).unwrap();
+        let mut sector = sector_pattern();
+        cipher.encrypt_sector(offset, &mut sector);
+        assert_eq!(
+            to_hex(&Sha256::digest(&sector)),
+            digest,
+            "method {:x}",
+            method
+        );
+        cipher.decrypt_sector(offset, &mut sector);
+        assert_eq!(sector, sector_pattern());
+    }
+}
+
+#[test]
+fn diffuser_round_trips() {
+    for method in [0x8000, 0x8001] {
+        let cipher = SectorCipher::new(
+            &Fvek {
+                method,
+                key: fvek_for(method),
+            },
+            512,
+        )
+        .unwrap();
+        let mut sector = sector_pattern();
+        cipher.encrypt_sector(0x2400, &mut sector);
+        assert_ne!(sector, sector_pattern());
+        cipher.decrypt_sector(0x2400, &mut sector);
+        assert_eq!(sector, sector_pattern());
+    }
+}
+
+#[test]
+fn derives_the_recovery_password_key() {
+    assert_eq!(
+        to_hex(&recovery_password_key(PASSWORD).unwrap()),
+        PASSWORD_KEY
+    );
+    let hash: [u8; 32] = Sha256::digest(unhex(PASSWORD_KEY)).into();
+    assert_eq!(to_hex(&stretch_key(&hash, &SALT)), STRETCHED);
+
+    assert!(recovery_password_key("000011-000022").is_err());
+    // 000012 is not a multiple of 11.
+    assert!(
+        recovery_password_key("000012-000022-003300-044000-550000-720885-000077-000000").is_err()
+    );
+}
+
+#[test]
+fn unwraps_the_fvek() {
+    let volume = encrypted_volume(0x8004);
+    let info = info(&volume);
+    assert_eq!(
+        info.recovery_key_ids(),
+        vec!["41414141-4141-4141-4141-414141414141"]
+    );
+
+    let fvek = unlock(&info, &BitLockerKey::RecoveryPassword(PASSWORD.to_string())).unwrap();
+    assert_eq!(fvek.method, 0x8004);
+    assert_eq!(fvek.key, fvek_for(0x8004));
+    let fvek = unlock(&info, &BitLockerKey::ClearKey).unwrap();
+    assert_eq!(fvek.key, fvek_for(0x8004));
+
+    let wrong = "000011-000022-003300-044000-550000-720885-000077-000011";
+    assert!(unlock(&info, &BitLockerKey::RecoveryPassword(wrong.to_string())).is_err());
+}
+
+#[test]
+fn reads_the_decrypted_volume() {
+    for method in [0x8000, 0x8001, 0x8002, 0x8003, 0x8004, 0x8005] {
+        let volume = encrypted_volume(method);
+        let info = info(&volume);
+        let fvek = unlock(&info, &BitLockerKey::Fvek(fvek_for(method))).unwrap();
+        let mut reader =
+            BitLockerReader::new(Cursor::new(volume), 0, VOLUME_SIZE as u64, &info, &fvek).unwrap();
+        let mut view = Vec::new();
+        reader.read_to_end(&mut view).unwrap();
+        assert!(view == expected_view(), "method {:x}", method);
+
+        // Unaligned reads across the relocated header boundary.
+        let mut buf = vec![0u8; 1000];
+        reader.seek(SeekFrom::Start(8000)).unwrap();
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, expected_view()[8000..9000]);
+    }
+}
+
+#[test]
+fn unlocks_partitions_of_a_disk() {
+    let image = DiskImageBuilder::new(4096)
+        .primary(0x07, 2048, (VOLUME_SIZE / 512) as u32)
+        .write_at(2048, &encrypted_volume(0x8004))
+        .build()
+        .unwrap();
+    let mut body = Cursor::new(image);
+    let mut partitions = Partitions::with_options(&mut body, &options()).unwrap();
+    assert_eq!(partitions.unlock_bitlocker(&BitLockerKey::ClearKey), 1);
+    let entry = &partitions.mbr.as_ref().unwrap().partition_table[0];
+    assert_eq!(
+        entry.fvek.as_deref(),
+        Some(to_hex(&fvek_for(0x8004)).as_str())
+    );
+
+    let extent = &partitions.extents()[0];
+    let Some(exhume_partitions::encryption::Encryption::BitLocker(info)) = &extent.encryption
+    else {
+        panic!("expected a BitLocker partition");
+    };
+    let fvek = Fvek {
+        method: 0x8004,
+        key: unhex(entry.fvek.as_ref().unwrap()),
+    };
+    let mut reader = BitLockerReader::new(
+        &mut body,
+        extent.first_byte_addr,
+        extent.size_bytes,
+        info,
+        &fvek,
+    )
+    .unwrap();
+    let mut boot_sector = [0u8; 512];
+    reader.read_exact(&mut boot_sector).unwrap();
+    assert_eq!(&boot_sector[3..11], b"NTFS    ");
+}
@@ -0,0 +1,159 @@
+use exhume_partitions::dps::{classify, Architecture, DpsClass, DpsRole, NO_AUTO, READ_ONLY};
+use exhume_partitions::report::PartitionsReport;
+use exhume_partitions::types::PartitionTypeRegistry;
+use serde_json::json;
+
+mod common;
+use common::gpt_disk;
+
+const ESP: &str = "c12a7328-f81f-11d2-ba4b-00a0c93ec93b";
+const ROOT_X86_64: &str = "4f68bce3-e8cd-4db1-96e7-fbcaf984b709";
+const ROOT_ARM64: &str = "b921b045-1df0-41c3-af44-4c6f280d3fae";
+const ROOT_VERITY_X86_64: &str = "2c7357ed-ebd2-46d9-aec1-23d437ec2bf5";
+const HOME: &str = "933ac7e1-2eb4-4f13-b844-0e14e2aef915";
+const LINUX: &str = "0fc63daf-8483-4772-8e79-3d69d8477de4";
+
+#[test]
+fn classifies_every_dps_type() {
+    assert_eq!(
+        classify(ROOT_X86_64),
+        Some(DpsClass {
+            role: DpsRole::Root,
+            arch: Some(Architecture::X86_64)
+        })
+    );
+    assert_eq!(
+        classify("6E11A4E7-FBCA-4DED-B9E9-E1A512BB664E"),
+        Some(DpsClass {
+            role: DpsRole::UsrVerity,
+            arch: Some(Architecture::Arm64)
+        })
+    );
+    assert_eq!(
+        classify("41092b05-9fc8-4523-994f-2def0408b176").map(|c| c.role),
+        Some(DpsRole::RootVeritySig)
+    );
+    assert_eq!(
+        classify(ESP),
+        Some(DpsClass {
+            role: DpsRole::Esp,
+            arch: None
+        })
+    );
+    assert_eq!(classify(HOME).map(|c| c.role), Some(DpsRole::Home));
+    // Not DPS types: generic Linux data, GNU/Hurd root, FreeBSD swap.
+    assert_eq!(classify(LINUX), None);
+    assert_eq!(classify("3bd3c9df-5f3c-4b0b-9d22-5d1b012fcf10"), None);
+    assert_eq!(classify("516e7cb5-6ecf-11d6-8ff8-00022d09712b"), None);
+
+    // Every per-architecture role is defined for the architectures of the specification.
+    let table = PartitionTypeRegistry::builtin().to_table();
+    for role in [
+        DpsRole::Root,
+        DpsRole::Usr,
+        DpsRole::RootVerity,
+        DpsRole::UsrVerity,
+        DpsRole::RootVeritySig,
+        DpsRole::UsrVeritySig,
+    ] {
+        let archs: Vec<Architecture> = table
+            .gpt
+            .iter()
+            .filter_map(|t| classify(&t.type_id))
+            .filter(|c| c.role == role)
+            .map(|c| c.arch.unwrap())
+            .collect();
+        assert!(archs.len() >= 19, "{:?}: {:?}", role, archs);
+        assert!(archs.contains(&Architecture::LoongArch64), "{:?}", role);
+    }
+    for role in [
+        DpsRole::Esp,
+        DpsRole::Xbootldr,
+        DpsRole::Swap,
+        DpsRole::Home,
+        DpsRole::Srv,
+        DpsRole::Var,
+        DpsRole::Tmp,
+    ] {
+        let count = table
+            .gpt
+            .iter()
+            .filter(|t| classify(&t.type_id) == Some(DpsClass { role, arch: None }))
+            .count();
+        assert_eq!(count, 1, "{:?}", role);
+    }
+}
+
+#[test]
+fn decodes_dps_attribute_bits() {
+    let partitions = gpt_disk(&[
+        (ROOT_X86_64, "root", NO_AUTO | READ_ONLY | 1 << 59 | 1 << 50),
+        (LINUX, "data", READ_ONLY),
+    ]);
+    let entries = &partitions.gpt.as_ref().unwrap().partition_entries;
+    assert_eq!(entries[0].description, "Linux root (x86-64)");
+    assert_eq!(
+        entries[0].attribute_flags(),
+        [
+            "Grow file system",
+            "Read-only",
+            "No auto-mount",
+            "Type-specific bits 0x4"
+        ]
+    );
+    // The DPS bits mean nothing on other types.
+    assert_eq!(entries[1].attribute_flags(), ["Type-specific bits 0x1000"]);
+}
+
+#[test]
+fn finds_the_root_partition_of_each_architecture() {
+    let partitions = gpt_disk(&[
+        (ESP, "esp", 0),
+        (ROOT_X86_64, "root-old", NO_AUTO),
+        (ROOT_X86_64, "root", READ_ONLY),
+        (ROOT_VERITY_X86_64, "root-verity", 0),
+        (ROOT_ARM64, "root-arm64", 0),
+        (HOME, "home", 0),
+        (LINUX, "data", 0),
+    ]);
+    let dps = partitions.dps_partitions();
+    assert_eq!(dps.len(), 6);
+    assert_eq!(
+        dps.iter().map(|p| p.role).collect::<Vec<_>>(),
+        [
+            DpsRole::Esp,
+            DpsRole::Root,
+            DpsRole::Root,
+            DpsRole::RootVerity,
+            DpsRole::Root,
+            DpsRole::Home
+        ]
+    );
+
+    let root = partitions.dps_root(Architecture::X86_64).unwrap();
+    assert_eq!((root.partition_index, root.name.as_str()), (3, "root"));
+    assert!(root.read_only && !root.no_auto);
+    assert_eq!(
+        partitions
+            .dps_root(Architecture::Arm64)
+            .unwrap()
+            .partition_index,
+        5
+    );
+    assert!(partitions.dps_root(Architecture::RiscV64).is_none());
+
+    let report = serde_json::to_value(PartitionsReport::new(&partitions, None)).unwrap();
+    assert_eq!(
+        report["dps"][3],
+        json!({
+            "partition_index": 4,
+            "name": "root-verity",
+            "role": "root-verity",
+            "arch": "x86-64",
+            "no_auto": false,
+            "read_only": false,
+            "grow_fs": false
+        })
+    );
+    assert_eq!(report["dps"][0]["arch"], json!(null));
+}
@@ -0,0 +1,120 @@
+use exhume_partitions::builder::DiskImageBuilder;
+use exhume_partitions::encryption::Encryption;
+use exhume_partitions::entropy::shannon_entropy;
+use exhume_partitions::nested::DiscoveryOptions;
+use exhume_partitions::Partitions;
+use std::io::Cursor;
+
+mod common;
+
+const BASIC_DATA: &str = "ebd0a0a2-b9e5-4433-87c0-68b6b72699c7";
+const LINUX: &str = "0fc63daf-8483-4772-8e79-3d69d8477de4";
+const DISK: &str = "5e0b1c39-3c4a-4b4e-9d57-2f1e0a7b8c11";
+
+/// Discovery with the encryption pass on.
+fn options() -> DiscoveryOptions {
+    DiscoveryOptions {
+        encryption: true,
+        ..Default::default()
+    }
+}
+
+fn partition_guid(n: u8) -> String {
+    format!("{:08x}-0000-4000-8000-000000000000", n)
+}
+
+/// One partition per kind of content, in this order: BitLocker, LUKS1, LUKS2, random, zeros.
+fn disk() -> Partitions {
+    let parts = [
+        (BASIC_DATA, 2048, 2559),
+        (LINUX, 2560, 2687),
+        (LINUX, 2688, 2815),
+        (LINUX, 2816, 3071),
+        (LINUX, 3072, 3327),
+    ];
+    let mut builder = DiskImageBuilder::new(8192).gpt(DISK);
+    for (i, (type_guid, first, last)) in parts.iter().enumerate() {
+        builder = builder.gpt_partition(
+            type_guid,
+            &partition_guid(i as u8 + 1),
+            &format!("part{}", i + 1),
+            *first,
+            *last,
+        );
+    }
+    let image = builder
+        .write_at(
+            2048,
+            &common::bitlocker_volume(0x8004, &[0x0100, 0x0800], &[]),
+        )
+        .write_at(2560, &common::luks1_header())
+        .write_at(2688, &common::luks2_header(common::LUKS2_JSON))
+        .write_at(2816, &common::random_bytes(128 * 1024, 7))
+        .build()
+        .unwrap();
+    Partitions::with_options(&mut Cursor::new(image), &options()).unwrap()
+}
+
+#[test]
+fn detects_bitlocker() {
+    let extents = disk().extents();
+    match &extents[0].encryption {
+        Some(Encryption::BitLocker(info)) => {
+            assert!(!info.to_go);
+            assert_eq!(info.metadata_offsets, common::FVE_BLOCKS.to_vec());
+            assert_eq!(info.encryption_method.as_deref(), Some("AES-128-XTS"));
+            assert_eq!(
+                info.volume_guid.as_deref(),
+                Some(common::BITLOCKER_VOLUME_GUID)
+            );
+            assert_eq!(info.protectors, vec!["TPM", "Recovery password"]);
+        }
+        other => panic!("expected BitLocker, got {:?}", other),
+    }
+}
+
+#[test]
+fn detects_luks1_and_luks2() {
+    let extents = disk().extents();
+    match &extents[1].encryption {
+        Some(Encryption::Luks(header)) => {
+            assert_eq!(header.version, 1);
+            assert_eq!(header.cipher, "aes-xts-plain64");
+            assert_eq!(header.uuid, "0b5d6c9e-2f1a-4e3b-8c7d-6a5b4c3d2e1f");
+            assert_eq!(header.payload_offset, 4096 * 512);
+            assert_eq!(header.keyslots.len(), 8);
+            assert_eq!(header.keyslots.iter().filter(|s| s.active).count(), 1);
+        }
+        other => panic!("expected LUKS1, got {:?}", other),
+    }
+    match &extents[2].encryption {
+        Some(Encryption::Luks(header)) => {
+            assert_eq!(header.version, 2);
+            assert_eq!(header.label.as_deref(), Some("vault0"));
+            assert_eq!(header.cipher, "aes-xts-plain64");
+            assert_eq!(header.payload_offset, 16777216);
+            assert_eq!(header.keyslots.len(), 2);
+            assert_eq!(header.keyslots[0].kdf.as_deref(), Some("argon2id"));
+            assert_eq!(header.keyslots[1].iterations, Some(1000));
+        }
+        other => panic!("expected LUKS2, got {:?}", other),
+    }
+}
+
+#[test]
+fn flags_random_content_only() {
+    let extents = disk().extents();
+    match &extents[3].encryption {
+        Some(Encryption::PossiblyEncrypted { entropy }) => assert!(*entropy > 7.95),
+        other => panic!("expected a possibly encrypted volume, got {:?}", other),
+    }
+    assert!(extents[4].encryption.is_none());
+}
+
+#[test]
+fn entropy_bounds() {
+    assert_eq!(shannon_entropy(&[]), 0.0);
+    assert_eq!(shannon_entropy(&[0x41; 4096]), 0.0);
+    let all: Vec<u8> = (0..=255).collect();
+    assert!((shannon_entropy(&all) - 8.0).abs() < 1e-9);
+}
@@ -0,0 +1,127 @@
+use exhume_partitions::builder::DiskImageBuilder;
+use exhume_partitions::entropy::{EntropyFinding, EntropyRegion, SlotEntropy};
+use exhume_partitions::layout::SlotKind;
+use exhume_partitions::nested::DiscoveryOptions;
+use exhume_partitions::Partitions;
+use std::io::Cursor;
+
+mod common;
+
+const LINUX: &str = "0fc63daf-8483-4772-8e79-3d69d8477de4";
+const DISK: &str = "5e0b1c39-3c4a-4b4e-9d57-2f1e0a7b8c11";
+
+/// Zero-filled, random and LUKS partitions, then a gap with 2 MiB of random data at LBA 8192.
+fn profiles(block_size: u64) -> Vec<SlotEntropy> {
+    let mut luks = common::luks1_header();
+    luks.extend(common::random_bytes(124 * 1024, 3));
+    let image = DiskImageBuilder::new(16384)
+        .gpt(DISK)
+        .gpt_partition(
+            LINUX,
+            "00000001-0000-4000-8000-000000000000",
+            "wiped",
+            2048,
+            4095,
+        )
+        .gpt_partition(
+            LINUX,
+            "00000002-0000-4000-8000-000000000000",
+            "random",
+            4096,
+            6143,
+        )
+        .gpt_partition(
+            LINUX,
+            "00000003-0000-4000-8000-000000000000",
+            "luks",
+            6144,
+            6399,
+        )
+        .write_at(4096, &common::random_bytes(1024 * 1024, 1))
+        .write_at(6144, &luks)
+        .write_at(8192, &common::random_bytes(2 * 1024 * 1024, 2))
+        .build()
+        .unwrap();
+    let mut body = Cursor::new(image);
+    let options = DiscoveryOptions {
+        encryption: true,
+        ..Default::default()
+    };
+    let partitions = Partitions::with_options(&mut body, &options).unwrap();
+    partitions.compute_entropy(&mut body, block_size).unwrap()
+}
+
+fn slot(profiles: &[SlotEntropy], kind: SlotKind, start_lba: u64) -> &SlotEntropy {
+    profiles
+        .iter()
+        .find(|p| p.slot.kind == kind && p.slot.start_lba == start_lba)
+        .unwrap()
+}
+
+#[test]
+fn flags_wiped_and_random_partitions() {
+    let profiles = profiles(64 * 1024);
+    assert!(profiles
+        .iter()
+        .all(|p| matches!(p.slot.kind, SlotKind::Partition | SlotKind::Unallocated)));
+
+    let wiped = slot(&profiles, SlotKind::Partition, 2048);
+    assert_eq!(wiped.finding, Some(EntropyFinding::Wiped));
+    assert_eq!(wiped.blocks, 16);
+    assert_eq!(wiped.zero_blocks, 16);
+    assert_eq!(wiped.max, 0.0);
+    assert_eq!(wiped.histogram[0], 16);
+
+    let random = slot(&profiles, SlotKind::Partition, 4096);
+    assert_eq!(random.finding, Some(EntropyFinding::HighEntropy));
+    assert_eq!(random.high_entropy_blocks, 16);
+    assert!(random.min > 7.99);
+    assert_eq!(random.histogram[7], 16);
+    assert_eq!(
+        random.high_entropy_regions,
+        vec![EntropyRegion {
+            first_byte_addr: 4096 * 512,
+            size_bytes: 1024 * 1024,
+        }]
+    );
+
+    // Random content behind a LUKS header is expected.
+    let luks = slot(&profiles, SlotKind::Partition, 6144);
+    assert_eq!(luks.finding, None);
+    assert!(luks.high_entropy_regions.is_empty());
+    assert!(luks.high_entropy_blocks > 0);
+}
+
+#[test]
+fn reports_random_region_in_gap() {
+    let profiles = profiles(64 * 1024);
+    let gap = slot(&profiles, SlotKind::Unallocated, 6400);
+    assert_eq!(gap.finding, None);
+    assert_eq!(
+        gap.high_entropy_regions,
+        vec![EntropyRegion {
+            first_byte_addr: 8192 * 512,
+            size_bytes: 2 * 1024 * 1024,
+        }]
+    );
+    assert_eq!(gap.histogram.iter().sum::<u64>(), gap.blocks);
+    assert!(gap.profile.len() <= 64);
+    assert!(gap.profile.iter().any(|&entropy| entropy > 7.9));
+    assert!(gap.profile.contains(&0.0));
+}
+
+#[test]
+fn profile_is_downsampled() {
+    let profiles = profiles(4096);
+    let gap = slot(&profiles, SlotKind::Unallocated, 6400);
+    assert!(gap.blocks > 64);
+    assert_eq!(gap.profile.len(), 64);
+}
+
+#[test]
+fn rejects_small_blocks() {
+    let image = DiskImageBuilder::new(4096).gpt(DISK).build().unwrap();
+    let mut body = Cursor::new(image);
+    let partitions = Partitions::new(&mut body).unwrap();
+    assert!(partitions.compute_entropy(&mut body, 512).is_err());
+}
@@ -0,0 +1,411 @@
+use exhume_partitions::builder::DiskImageBuilder;
+use exhume_partitions::esp::{known_bootloader, parse_pe, EspInventory};
+use exhume_partitions::export::{BodyfileExporter, Exporter};
+use exhume_partitions::fat::{FatType, FatVolume};
+use exhume_partitions::hash::MultiHasher;
+use exhume_partitions::nested::DiscoveryOptions;
+use exhume_partitions::report::PartitionsReport;
+use exhume_partitions::Partitions;
+use std::io::Cursor;
+
+const ESP: &str = "c12a7328-f81f-11d2-ba4b-00a0c93ec93b";
+const DISK: &str = "5e0b1c39-3c4a-4b4e-9d57-2f1e0a7b8c11";
+/// 2024-03-05 10:20:30 as a DOS date and time.
+const DATE: u16 = (44 << 9) | (3 << 5) | 5;
+const TIME: u16 = (10 << 11) | (20 << 5) | 15;
+
+enum Node {
+    Dir(&'static str, Vec<Node>),
+    File(&'static str, Vec<u8>),
+}
+
+/// Discovery with the EFI System Partition pass on.
+fn options() -> DiscoveryOptions {
+    DiscoveryOptions {
+        esp: true,
+        ..Default::default()
+    }
+}
+
+/// A PE32+ EFI application linked on 2023-11-14T22:13:20Z, with an Authenticode certificate
+/// table when `signed`.
+fn pe(machine: u16, signed: bool) -> Vec<u8> {
+    let mut image = vec![0u8; 1024];
+    image[0..2].copy_from_slice(b"MZ");
+    image[0x3C..0x40].copy_from_slice(&0x80u32.to_le_bytes());
+    image[0x80..0x84].copy_from_slice(b"PE\0\0");
+    image[0x84..0x86].copy_from_slice(&machine.to_le_bytes());
+    image[0x88..0x8C].copy_from_slice(&1_700_000_000u32.to_le_bytes());
+    image[0x94..0x96].copy_from_slice(&240u16.to_le_bytes());
+    let optional = 0x98;
+    image[optional..optional + 2].copy_from_slice(&0x20Bu16.to_le_bytes());
+    image[optional + 68..optional + 70].copy_from_slice(&10u16.to_le_bytes());
+    image[optional + 108..optional + 112].copy_from_slice(&16u32.to_le_bytes());
+    if signed {
+        let security = optional + 112 + 4 * 8;
+        image[security..security + 4].copy_from_slice(&1024u32.to_le_bytes());
+        image[security + 4..security + 8].copy_from_slice(&16u32.to_le_bytes());
+        let mut certificate = 16u32.to_le_bytes().to_vec();
+        certificate.extend(0x0200u16.to_le_bytes());
+        certificate.extend(0x0002u16.to_le_bytes());
+        certificate.extend([0x30; 8]);
+        image.extend(certificate);
+    }
+    image
+}
+
+/// The 8.3 name of `name`, padded, as mkfs.fat would give the first file of that name.
+fn short_name(name: &str) -> [u8; 11] {
+    let upper = name.to_uppercase();
+    let (base, ext) = upper.rsplit_once('.').unwrap_or((&upper, ""));
+    let base = if base.len() > 8 {
+        format!("{}~1", &base[..6])
+    } else {
+        base.to_string()
+    };
+    let mut short = [b' '; 11];
+    short[..base.len()].copy_from_slice(base.as_bytes());
+    short[8..8 + ext.len()].copy_from_slice(ext.as_bytes());
+    short
+}
+
+fn checksum(short: &[u8; 11]) -> u8 {
+    short.iter().fold(0u8, |sum, &c| {
+        (sum >> 1).wrapping_add(sum << 7).wrapping_add(c)
+    })
+}
+
+/// Directory entries of `name`: long file name entries when it is not a plain 8.3 name.
+fn dir_entries(name: &str, is_dir: bool, cluster: u32, size: u32) -> Vec<u8> {
+    let short = short_name(name);
+    let mut entries = Vec::new();
+    let plain = name.to_uppercase() == name && name.len() <= 12;
+    if !plain {
+        let mut units: Vec<u16> = name.encode_utf16().collect();
+        units.push(0);
+        while !units.len().is_multiple_of(13) {
+            units.push(0xFFFF);
+        }
+        let count = units.len() / 13;
+        for order in (1..=count).rev() {
+            let chars = &units[(order - 1) * 13..order * 13];
+            let mut entry = [0u8; 32];
+            entry[0] = order as u8 | if order == count { 0x40 } else { 0 };
+            entry[11] = 0x0F;
+            entry[13] = checksum(&short);
+            let at = [1, 3, 5, 7, 9, 14, 16, 18, 20, 22, 24, 28, 30];
+            for (unit, at) in chars.iter().zip(at) {
+                entry[at..at + 2].copy_from_slice(&unit.to_le_bytes());
+            }
+            entries.extend(entry);
+        }
+    }
+    let mut entry = [0u8; 32];
+    entry[0..11].copy_from_slice(&short);
+    entry[11] = if is_dir { 0x10 } else { 0x20 };
+    entry[20..22].copy_from_slice(&((cluster >> 16) as u16).to_le_bytes());
+    entry[22..24].copy_from_slice(&TIME.to_le_bytes());
+    entry[24..26].copy_from_slice(&DATE.to_le_bytes());
+    entry[26..28].copy_from_slice(&(cluster as u16).to_le_bytes());
+    entry[28..32].copy_from_slice(&size.to_le_bytes());
+    entries.extend(entry);
+    entries
+}
+
+/// Clusters allocated one after the other, with their FAT.
+struct Allocator {
+    cluster_size: usize,
+    fat: Vec<u32>,
+    data: Vec<u8>,
+}
+
+impl Allocator {
+    fn alloc(&mut self, bytes: &[u8], end_of_chain: u32) -> u32 {
+        let first = self.fat.len() as u32;
+        let count = bytes.len().div_ceil(self.cluster_size).max(1);
+        for i in 0..count as u32 {
+            let next = if i + 1 == count as u32 {
+                end_of_chain
+            } else {
+                first + i + 1
+            };
+            self.fat.push(next);
+        }
+        let mut bytes = bytes.to_vec();
+        bytes.resize(count * self.cluster_size, 0);
+        self.data.extend(bytes);
+        first
+    }
+
+    /// Allocate the children of a directory and return its entries.
+    fn dir(&mut self, children: &[Node], end_of_chain: u32) -> Vec<u8> {
+        let mut entries = Vec::new();
+        for child in children {
+            match child {
+                Node::Dir(name, grandchildren) => {
+                    let mut content = dir_entries(".", true, 0, 0);
+                    content[0..11].copy_from_slice(b".          ");
+                    content.extend(self.dir(grandchildren, end_of_chain));
+                    let cluster = self.alloc(&content, end_of_chain);
+                    entries.extend(dir_entries(name, true, cluster, 0));
+                }
+                Node::File(name, data) => {
+                    let cluster = self.alloc(data, end_of_chain);
+                    entries.extend(dir_entries(name, false, cluster, data.len() as u32));
+                }
+            }
+        }
+        entries
+    }
+}
+
+/// A FAT16 (or FAT32) volume of `sectors` sectors holding `root`.
+fn fat_volume(sectors: u32, fat32: bool, root: &[Node]) -> Vec<u8> {
+    let (spc, reserved, root_entries, width, end_of_chain) = if fat32 {
+        (1u32, 32u32, 0u32, 4u32, 0x0FFF_FFFFu32)
+    } else {
+        (4, 1, 512, 2, 0xFFFF)
+    };
+    let cluster_size = spc as usize * 512;
+    let fat_sectors = ((sectors / spc + 2) * width).div_ceil(512);
+    let root_sectors = root_entries * 32 / 512;
+    let mut allocator = Allocator {
+        cluster_size,
+        fat: vec![0x0FFF_FFF8 & end_of_chain, end_of_chain],
+        data: Vec::new(),
+    };
+    let root_dir = allocator.dir(root, end_of_chain);
+    let root_cluster = if fat32 {
+        allocator.alloc(&root_dir, end_of_chain)
+    } else {
+        0
+    };
+
+    let mut volume = vec![0u8; sectors as usize * 512];
+    let boot = &mut volume[0..512];
+    boot[0..3].copy_from_slice(&[0xEB, 0x3C, 0x90]);
+    boot[3..11].copy_from_slice(b"mkfs.fat");
+    boot[11..13].copy_from_slice(&512u16.to_le_bytes());
+    boot[13] = spc as u8;
+    boot[14..16].copy_from_slice(&(reserved as u16).to_le_bytes());
+    boot[16] = 2;
+    boot[17..19].copy_from_slice(&(root_entries as u16).to_le_bytes());
+    boot[21] = 0xF8;
+    boot[32..36].copy_from_slice(&sectors.to_le_bytes());
+    let (label_at, signature_at) = if fat32 {
+        boot[36..40].copy_from_slice(&fat_sectors.to_le_bytes());
+        boot[44..48].copy_from_slice(&root_cluster.to_le_bytes());
+        (71, 66)
+    } else {
+        boot[22..24].copy_from_slice(&(fat_sectors as u16).to_le_bytes());
+        (43, 38)
+    };
+    boot[signature_at] = 0x29;
+    boot[label_at..label_at + 11].copy_from_slice(b"ESP        ");
+    boot[510..512].copy_from_slice(&[0x55, 0xAA]);
+
+    let mut fat = Vec::new();
+    for entry in &allocator.fat {
+        fat.extend_from_slice(&entry.to_le_bytes()[..width as usize]);
+    }
+    for copy in 0..2 {
+        let at = (reserved + copy * fat_sectors) as usize * 512;
+        volume[at..at + fat.len()].copy_from_slice(&fat);
+    }
+    let root_at = (reserved + 2 * fat_sectors) as usize * 512;
+    if !fat32 {
+        volume[root_at..root_at + root_dir.len()].copy_from_slice(&root_dir);
+    }
+    let data_at = root_at + root_sectors as usize * 512;
+    volume[data_at..data_at + allocator.data.len()].copy_from_slice(&allocator.data);
+    volume
+}
+
+fn windows_esp() -> Vec<Node> {
+    vec![Node::Dir(
+        "EFI",
+        vec![
+            Node::Dir(
+                "Microsoft",
+                vec![Node::Dir(
+                    "Boot",
+                    vec![
+                        Node::File("bootmgfw.efi", pe(0x8664, true)),
+                        Node::File("BCD", vec![0x72, 0x65, 0x67, 0x66]),
+                    ],
+                )],
+            ),
+            Node::Dir("BOOT", vec![Node::File("BOOTX64.EFI", pe(0x8664, true))]),
+        ],
+    )]
+}
+
+fn inventory(partitions: &Partitions) -> &EspInventory {
+    assert_eq!(partitions.esp.len(), 1);
+    &partitions.esp[0]
+}
+
+#[test]
+fn inventories_fat16_esp() {
+    let sectors = 20480;
+    let image = DiskImageBuilder::new(2048 + sectors as u64 + 34)
+        .gpt(DISK)
+        .gpt_partition(
+            ESP,
+            "00000001-0000-4000-8000-000000000000",
+            "EFI system partition",
+            2048,
+            2048 + sectors as u64 - 1,
+        )
+        .write_at(2048, &fat_volume(sectors, false, &windows_esp()))
+        .build()
+        .unwrap();
+    let partitions = Partitions::with_options(&mut Cursor::new(image), &options()).unwrap();
+    let esp = inventory(&partitions);
+    assert!(esp.issues.is_empty(), "{:?}", esp.issues);
+    assert_eq!(esp.partition_index, 1);
+    assert_eq!(esp.fat_type, Some(FatType::Fat16));
+    assert_eq!(esp.label.as_deref(), Some("ESP"));
+    let paths: Vec<&str> = esp.files.iter().map(|file| file.path.as_str()).collect();
+    assert_eq!(
+        paths,
+        [
+            "/EFI/Microsoft/Boot/bootmgfw.efi",
+            "/EFI/Microsoft/Boot/BCD",
+            "/EFI/BOOT/BOOTX64.EFI",
+        ]
+    );
+
+    let bootmgfw = &esp.files[0];
+    let mut hasher = MultiHasher::new();
+    hasher.update(&pe(0x8664, true));
+    assert_eq!(bootmgfw.hashes.as_ref(), Some(&hasher.finalize()));
+    assert_eq!(bootmgfw.size, 1040);
+    assert_eq!(bootmgfw.modified.as_deref(), Some("2024-03-05 10:20:30"));
+    assert_eq!(bootmgfw.bootloader.as_deref(), Some("Windows Boot Manager"));
+    let pe = bootmgfw.pe.as_ref().unwrap();
+    assert_eq!(pe.machine, "x64");
+    assert_eq!(pe.subsystem, "EFI application");
+    assert_eq!(pe.timestamp.as_deref(), Some("2023-11-14T22:13:20Z"));
+    assert!(pe.signed);
+    assert_eq!(pe.signature_size, 16);
+
+    assert!(esp.files[1].hashes.is_none() && esp.files[1].pe.is_none());
+    assert_eq!(
+        esp.files[2].bootloader.as_deref(),
+        Some("Removable media loader")
+    );
+
+    let report = PartitionsReport::new(&partitions, None);
+    assert_eq!(report.esp[0].files.len(), 3);
+
+    // The body file timeline carries the FAT modification times.
+    let body = BodyfileExporter.export(&partitions, "disk.raw").unwrap();
+    let lines: Vec<&str> = body.lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert_eq!(
+        lines[1],
+        format!(
+            "{}|disk.raw:partition 1/EFI/Microsoft/Boot/bootmgfw.efi|0|r/rrwxrwxrwx|0|0|1040|0|1709634030|0|0",
+            bootmgfw.hashes.as_ref().unwrap().md5
+        )
+    );
+}
+
+#[test]
+fn flags_blacklotus_layout_on_fat32_mbr_esp() {
+    let sectors = 70000;
+    let root = vec![
+        Node::Dir(
+            "EFI",
+            vec![Node::Dir(
+                "Microsoft",
+                vec![Node::Dir(
+                    "Boot",
+                    vec![
+                        Node::File("bootmgfw.efi", pe(0x8664, false)),
+                        Node::File("grubx64.efi", pe(0x8664, false)),
+                        Node::File("winload.efi", b"not a binary".to_vec()),
+                    ],
+                )],
+            )],
+        ),
+        Node::Dir(
+            "system32",
+            vec![Node::File("bootmgr.efi", pe(0x8664, true))],
+        ),
+    ];
+    let image = DiskImageBuilder::new(2048 + sectors as u64)
+        .primary(0xEF, 2048, sectors)
+        .write_at(2048, &fat_volume(sectors, true, &root))
+        .build()
+        .unwrap();
+    let partitions = Partitions::with_options(&mut Cursor::new(image), &options()).unwrap();
+    let esp = inventory(&partitions);
+    assert_eq!(esp.fat_type, Some(FatType::Fat32));
+    assert_eq!(esp.files.len(), 3);
+    assert_eq!(
+        esp.issues,
+        [
+            "/system32 directory at the root of the ESP, as created by BlackLotus",
+            "/EFI/Microsoft/Boot/winload.efi: not a PE image (No MZ header)",
+            "/EFI/Microsoft/Boot/bootmgfw.efi: Windows Boot Manager without an Authenticode \
+             signature",
+            "/EFI/Microsoft/Boot/grubx64.efi: GRUB under /EFI/Microsoft, where BlackLotus drops \
+             its loader",
+        ]
+    );
+}
+
+#[test]
+fn reports_unreadable_esp() {
+    let image = DiskImageBuilder::new(8192)
+        .primary(0xEF, 2048, 4096)
+        .build()
+        .unwrap();
+    let partitions = Partitions::with_options(&mut Cursor::new(image), &options()).unwrap();
+    let esp = inventory(&partitions);
+    assert_eq!(esp.fat_type, None);
+    assert_eq!(
+        esp.issues,
+        ["No readable FAT file system: No FAT boot sector"]
+    );
+}
+
+#[test]
+fn parses_pe_headers_and_names() {
+    assert!(parse_pe(b"MZ").is_err());
+    let mut image = pe(0xAA64, false);
+    let pe_info = parse_pe(&image).unwrap();
+    assert_eq!(pe_info.machine, "ARM64");
+    assert!(!pe_info.signed);
+    image[0x80] = b'X';
+    assert_eq!(parse_pe(&image).unwrap_err(), "No PE signature");
+
+    assert_eq!(known_bootloader("/EFI/ubuntu/shimx64.efi"), Some("shim"));
+    assert_eq!(known_bootloader("/EFI/ubuntu/grubaa64.efi"), Some("GRUB"));
+    assert_eq!(
+        known_bootloader("/EFI/systemd/systemd-bootx64.efi"),
+        Some("systemd-boot")
+    );
+    assert_eq!(known_bootloader("/EFI/ubuntu/bootx64.efi"), None);
+    assert_eq!(known_bootloader("/EFI/tools/shell.efi"), None);
+}
+
+#[test]
+fn stops_on_cluster_loops() {
+    // LOOP.BIN takes clusters 2 and 3; cluster 3 points back to 2, and the boot sector claims
+    // about 2^32 sectors, as a crafted volume would.
+    let mut volume = fat_volume(70000, true, &[Node::File("LOOP.BIN", vec![0x5A; 1024])]);
+    volume[32..36].copy_from_slice(&0xFFFF_FFF0u32.to_le_bytes());
+    let fat_at = 32 * 512;
+    volume[fat_at + 12..fat_at + 16].copy_from_slice(&2u32.to_le_bytes());
+
+    let mut body = Cursor::new(volume);
+    let fat = FatVolume::open(&mut body, 0).unwrap();
+    assert_eq!(fat.chain(2).collect::<Vec<_>>(), [2, 3]);
+    let root = fat.root_dir(&mut body).unwrap();
+    let file = root.iter().find(|e| e.name == "LOOP.BIN").unwrap();
+    assert_eq!(file.first_cluster, 2);
+    assert_eq!(fat.read_file(&mut body, file).unwrap(), vec![0x5A; 1024]);
+}
@@ -0,0 +1,113 @@
+use exhume_partitions::builder::DiskImageBuilder;
+use exhume_partitions::export::{exporter_for, BodyfileExporter, DfxmlExporter, Exporter};
+use exhume_partitions::Partitions;
+use std::io::Cursor;
+
+const LINUX: &str = "0fc63daf-8483-4772-8e79-3d69d8477de4";
+const DISK: &str = "5e0b1c39-3c4a-4b4e-9d57-2f1e0a7b8c11";
+
+/// Characters every format has to escape or replace.
+const NAME: &str = "<a & b>, \"c\" 'd'|\u{1}e";
+const EVIDENCE: &str = "case \"1\", disk <&>.raw";
+
+/// A GPT disk with two partitions, the second named `NAME`, and their hashes.
+fn partitions() -> Partitions {
+    let image = DiskImageBuilder::new(8192)
+        .gpt(DISK)
+        .gpt_partition(
+            LINUX,
+            "00000001-0000-4000-8000-000000000000",
+            "root",
+            2048,
+            4095,
+        )
+        .gpt_partition(
+            LINUX,
+            "00000002-0000-4000-8000-000000000000",
+            NAME,
+            4096,
+            6143,
+        )
+        .build()
+        .unwrap();
+    let mut body = Cursor::new(image);
+    let mut partitions = Partitions::new(&mut body).unwrap();
+    partitions.hashes = Some(partitions.compute_hashes(&mut body).unwrap());
+    partitions
+}
+
+#[test]
+fn csv_quotes_separators() {
+    let partitions = partitions();
+    let document = exporter_for("csv")
+        .unwrap()
+        .export(&partitions, EVIDENCE)
+        .unwrap();
+    let mut reader = csv::Reader::from_reader(document.as_bytes());
+    let headers = reader.headers().unwrap().clone();
+    let rows: Vec<csv::StringRecord> = reader.records().map(Result::unwrap).collect();
+    assert_eq!(rows.len(), 2);
+    let field = |row: &csv::StringRecord, name: &str| {
+        let column = headers.iter().position(|h| h == name).unwrap();
+        row[column].to_string()
+    };
+    assert_eq!(field(&rows[1], "evidence"), EVIDENCE);
+    assert_eq!(field(&rows[1], "name"), NAME);
+    assert_eq!(field(&rows[1], "start_lba"), "4096");
+    assert_eq!(field(&rows[1], "end_lba"), "6143");
+    assert_eq!(field(&rows[0], "md5").len(), 32);
+}
+
+#[test]
+fn dfxml_is_well_formed() {
+    let partitions = partitions();
+    let document = DfxmlExporter.export(&partitions, EVIDENCE).unwrap();
+    let xml = roxmltree::Document::parse(&document).unwrap();
+    let text = |name: &str| -> Vec<&str> {
+        xml.descendants()
+            .filter(|node| node.has_tag_name(name))
+            .map(|node| node.text().unwrap_or(""))
+            .collect()
+    };
+    assert_eq!(text("image_filename"), [EVIDENCE]);
+    // Control characters are not allowed in XML 1.0 and are dropped.
+    assert_eq!(text("partition_label"), ["root", "<a & b>, \"c\" 'd'|e"]);
+    assert_eq!(text("pstype_str"), ["gpt"]);
+    assert_eq!(text("hashdigest").len(), 6);
+    let runs: Vec<(&str, &str)> = xml
+        .descendants()
+        .filter(|node| node.has_tag_name("volume"))
+        .map(|node| {
+            let run = node
+                .descendants()
+                .find(|child| child.has_tag_name("byte_run"))
+                .unwrap();
+            (
+                run.attribute("img_offset").unwrap(),
+                run.attribute("len").unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(runs, [("1048576", "1048576"), ("2097152", "1048576")]);
+}
+
+#[test]
+fn bodyfile_has_one_record_per_partition() {
+    let partitions = partitions();
+    let document = BodyfileExporter.export(&partitions, "disk.raw").unwrap();
+    let lines: Vec<&str> = document.lines().collect();
+    assert_eq!(lines.len(), 2);
+    for line in &lines {
+        assert_eq!(line.split('|').count(), 11, "{}", line);
+    }
+    let fields: Vec<&str> = lines[1].split('|').collect();
+    assert_eq!(fields[0].len(), 32);
+    assert_eq!(
+        fields[1],
+        "disk.raw:partition 2 (Linux filesystem data) \"<a & b>, \"c\" 'd'__e\""
+    );
+    assert_eq!(
+        &fields[2..],
+        ["2", "-/----------", "0", "0", "1048576", "0", "0", "0", "0"]
+    );
+}
@@ -0,0 +1,95 @@
+use exhume_partitions::builder::DiskImageBuilder;
+use exhume_partitions::extent::PartitionExtent;
+use exhume_partitions::extract::{extract_partition, ExtractOptions};
+use exhume_partitions::hash::{Hashes, MultiHasher};
+use exhume_partitions::Partitions;
+use std::fs;
+use std::io::Cursor;
+use tempfile::TempDir;
+
+mod common;
+
+const MIB: usize = 1024 * 1024;
+
+/// An MBR disk whose 3 MiB partition holds 1 MiB of random data, then zeros.
+fn disk() -> (Cursor<Vec<u8>>, PartitionExtent, Vec<u8>) {
+    let mut content = common::random_bytes(MIB, 7);
+    content.resize(3 * MIB, 0);
+    let image = DiskImageBuilder::new(8192)
+        .primary(0x83, 2048, 6144)
+        .write_at(2048, &content)
+        .build()
+        .unwrap();
+    let mut body = Cursor::new(image);
+    let extent = Partitions::new(&mut body).unwrap().extents().remove(0);
+    (body, extent, content)
+}
+
+fn digests(data: &[u8]) -> Hashes {
+    let mut hasher = MultiHasher::new();
+    hasher.update(data);
+    hasher.finalize()
+}
+
+#[test]
+fn extracts_and_hashes_the_partition() {
+    let (mut body, extent, content) = disk();
+    let dir = TempDir::new().unwrap();
+    let output = dir.path().join("p1.dd");
+    let options = ExtractOptions {
+        hash: true,
+        ..Default::default()
+    };
+    let mut positions = Vec::new();
+    let report = extract_partition(&mut body, &extent, &output, &options, |done| {
+        positions.push(done)
+    })
+    .unwrap();
+    assert_eq!(report.first_byte_addr, 2048 * 512);
+    assert_eq!(report.size_bytes, 3 * MIB as u64);
+    assert_eq!(report.resumed_from, 0);
+    assert_eq!(positions, [0, MIB as u64, 2 * MIB as u64, 3 * MIB as u64]);
+    let written = fs::read(&output).unwrap();
+    assert!(written == content);
+    // The digests are those of the data written.
+    assert_eq!(report.hashes, Some(digests(&written)));
+}
+
+#[test]
+fn resumes_an_interrupted_extraction() {
+    let (mut body, extent, content) = disk();
+    let dir = TempDir::new().unwrap();
+    let output = dir.path().join("p1.dd");
+    fs::write(&output, &content[..MIB + 4096]).unwrap();
+    let options = ExtractOptions {
+        resume: true,
+        hash: true,
+        ..Default::default()
+    };
+    let report = extract_partition(&mut body, &extent, &output, &options, |_| {}).unwrap();
+    assert_eq!(report.resumed_from, MIB as u64 + 4096);
+    assert!(fs::read(&output).unwrap() == content);
+    // The part already there is read back to hash the whole partition.
+    assert_eq!(report.hashes, Some(digests(&content)));
+
+    // An output larger than the partition is not a previous run of this extraction.
+    fs::write(&output, vec![0u8; 3 * MIB + 1]).unwrap();
+    assert!(extract_partition(&mut body, &extent, &output, &options, |_| {}).is_err());
+}
+
+#[test]
+fn sparse_output_has_the_same_content() {
+    let (mut body, extent, content) = disk();
+    let dir = TempDir::new().unwrap();
+    let output = dir.path().join("p1.dd");
+    fs::write(&output, vec![0xFFu8; 4 * MIB]).unwrap();
+    let options = ExtractOptions {
+        sparse: true,
+        ..Default::default()
+    };
+    let report = extract_partition(&mut body, &extent, &output, &options, |_| {}).unwrap();
+    assert!(report.hashes.is_none());
+    // The zero-filled tail is skipped, yet the output has the partition's length, and what
+    // a previous run left in the file is gone.
+    assert!(fs::read(&output).unwrap() == content);
+}
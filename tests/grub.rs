@@ -0,0 +1,265 @@
+use exhume_partitions::builder::DiskImageBuilder;
+use exhume_partitions::grub::{parse_blocklist, GrubBlock};
+use exhume_partitions::nested::DiscoveryOptions;
+use exhume_partitions::Partitions;
+use std::io::Cursor;
+
+const BIOS_BOOT: &str = "21686148-6449-6e6f-744e-656564454649";
+const LINUX: &str = "0fc63daf-8483-4772-8e79-3d69d8477de4";
+const DISK: &str = "5e0b1c39-3c4a-4b4e-9d57-2f1e0a7b8c11";
+
+/// Discovery with the GRUB pass on.
+fn options() -> DiscoveryOptions {
+    DiscoveryOptions {
+        grub: true,
+        ..Default::default()
+    }
+}
+
+/// boot.img loading core.img from `kernel_sector`.
+fn boot_img(kernel_sector: u64) -> Vec<u8> {
+    let mut code = vec![0u8; 440];
+    code[0..3].copy_from_slice(&[0xEB, 0x63, 0x90]);
+    code[0x5C..0x64].copy_from_slice(&kernel_sector.to_le_bytes());
+    code[0x64] = 0xFF;
+    let strings = b"GRUB \0Geom\0Hard Disk\0Read\0 Error\r\n";
+    code[0x17C..0x17C + strings.len()].copy_from_slice(strings);
+    code
+}
+
+/// A module object: a minimal ELF32 image with a `.modname` section.
+fn module(name: &str) -> Vec<u8> {
+    let shstrtab = b"\0.shstrtab\0.modname\0";
+    let modname = format!("{}\0", name);
+    let mut elf = vec![0u8; 52];
+    elf[0..4].copy_from_slice(b"\x7fELF");
+    elf[4] = 1;
+    elf[5] = 1;
+    let shstrtab_offset = elf.len();
+    elf.extend_from_slice(shstrtab);
+    let modname_offset = elf.len();
+    elf.extend_from_slice(modname.as_bytes());
+    while !elf.len().is_multiple_of(4) {
+        elf.push(0);
+    }
+    let shoff = elf.len();
+    elf[0x20..0x24].copy_from_slice(&(shoff as u32).to_le_bytes());
+    elf[0x2E..0x30].copy_from_slice(&40u16.to_le_bytes());
+    elf[0x30..0x32].copy_from_slice(&3u16.to_le_bytes());
+    elf[0x32..0x34].copy_from_slice(&1u16.to_le_bytes());
+    let sections = [
+        (0u32, 0usize, 0usize),
+        (1, shstrtab_offset, shstrtab.len()),
+        (11, modname_offset, modname.len()),
+    ];
+    for (name, offset, size) in sections {
+        let mut header = [0u8; 40];
+        header[0..4].copy_from_slice(&name.to_le_bytes());
+        header[16..20].copy_from_slice(&(offset as u32).to_le_bytes());
+        header[20..24].copy_from_slice(&(size as u32).to_le_bytes());
+        elf.extend_from_slice(&header);
+    }
+    elf
+}
+
+fn object(kind: u32, data: &[u8]) -> Vec<u8> {
+    let mut data = data.to_vec();
+    while !data.len().is_multiple_of(4) {
+        data.push(0);
+    }
+    let mut object = kind.to_le_bytes().to_vec();
+    object.extend(((data.len() + 8) as u32).to_le_bytes());
+    object.extend(data);
+    object
+}
+
+/// Kernel code followed by the module area: two modules, the prefix and a config.
+fn kernel() -> Vec<u8> {
+    let mut objects = object(0, &module("biosdisk"));
+    objects.extend(object(0, &module("part_gpt")));
+    objects.extend(object(3, b"(hd0,gpt2)/boot/grub\0"));
+    objects.extend(object(2, b"search --set=root --label boot\0"));
+    let mut kernel: Vec<u8> = (0..3000u32).map(|i| (i * 7 % 251) as u8).collect();
+    kernel.extend(0x676D_696Du32.to_le_bytes());
+    kernel.extend(12u32.to_le_bytes());
+    kernel.extend(((objects.len() + 12) as u32).to_le_bytes());
+    kernel.extend(objects);
+    kernel
+}
+
+/// core.img at `lba`: diskboot.img, a one-sector decompressor and the compressed kernel.
+/// With `redundancy`, the image is padded with Reed-Solomon-like bytes as grub-install does.
+fn core_img(lba: u64, redundancy: bool) -> Vec<u8> {
+    let kernel = kernel();
+    let mut compressed = Vec::new();
+    let options = lzma_rs::compress::Options {
+        unpacked_size: lzma_rs::compress::UnpackedSize::SkipWritingToHeader,
+    };
+    lzma_rs::lzma_compress_with_options(&mut Cursor::new(&kernel), &mut compressed, &options)
+        .unwrap();
+    let compressed = &compressed[5..]; // Properties and dictionary size
+
+    let mut decompressor = vec![0x90u8; 512];
+    decompressor[0x08..0x0C].copy_from_slice(&(compressed.len() as u32).to_le_bytes());
+    decompressor[0x0C..0x10].copy_from_slice(&(kernel.len() as u32).to_le_bytes());
+    decompressor[0x10..0x14].fill(0);
+    let mut body = decompressor;
+    body.extend_from_slice(compressed);
+    let sectors = body.len().div_ceil(512) as u64;
+    if redundancy {
+        let padding = sectors as usize * 512 - body.len();
+        body[0x10..0x14].copy_from_slice(&(padding as u32).to_le_bytes());
+        body.extend(std::iter::repeat_n(0xAB, padding));
+    } else {
+        body.resize(sectors as usize * 512, 0);
+    }
+
+    let mut diskboot = vec![0u8; 512];
+    diskboot[0] = 0x52;
+    diskboot[0x150..0x157].copy_from_slice(b"Loading");
+    diskboot[0x1F4..0x1FC].copy_from_slice(&(lba + 1).to_le_bytes());
+    diskboot[0x1FC..0x1FE].copy_from_slice(&(sectors as u16).to_le_bytes());
+    diskboot[0x1FE..0x200].copy_from_slice(&0x820u16.to_le_bytes());
+    diskboot.extend(body);
+    diskboot
+}
+
+fn mbr_disk(core: &[u8]) -> Partitions {
+    let image = DiskImageBuilder::new(8192)
+        .boot_code(&boot_img(1))
+        .primary(0x83, 2048, 4096)
+        .write_at(1, core)
+        .build()
+        .unwrap();
+    Partitions::with_options(&mut Cursor::new(image), &options()).unwrap()
+}
+
+#[test]
+fn parses_core_img_in_mbr_gap() {
+    let grub = mbr_disk(&core_img(1, true)).grub.unwrap();
+    assert!(grub.issues.is_empty(), "{:?}", grub.issues);
+    assert_eq!(grub.kernel_sector, 1);
+    assert_eq!(grub.boot_drive, 0xFF);
+    assert_eq!(grub.location, "MBR gap");
+    assert_eq!(grub.blocklist[0].start_lba, 2);
+    assert_eq!(grub.blocklist[0].segment, 0x820);
+    assert_eq!(grub.uncompressed_size, Some(kernel().len() as u64));
+    assert_eq!(grub.modules, vec!["biosdisk", "part_gpt"]);
+    assert_eq!(grub.prefix.as_deref(), Some("(hd0,gpt2)/boot/grub"));
+    assert_eq!(
+        grub.config.as_deref(),
+        Some("search --set=root --label boot")
+    );
+    assert_eq!(grub.objects.len(), 4);
+    assert_eq!(grub.sha256.as_ref().map(String::len), Some(64));
+}
+
+#[test]
+fn finds_padded_kernel_without_redundancy() {
+    let grub = mbr_disk(&core_img(1, false)).grub.unwrap();
+    assert!(grub.issues.is_empty(), "{:?}", grub.issues);
+    assert_eq!(grub.reed_solomon_redundancy, Some(0));
+    assert_eq!(grub.modules, vec!["biosdisk", "part_gpt"]);
+}
+
+#[test]
+fn parses_core_img_in_bios_boot_partition() {
+    let image = DiskImageBuilder::new(8192)
+        .boot_code(&boot_img(40))
+        .gpt(DISK)
+        .gpt_partition(
+            BIOS_BOOT,
+            "00000001-0000-4000-8000-000000000000",
+            "bios",
+            40,
+            2047,
+        )
+        .gpt_partition(
+            LINUX,
+            "00000002-0000-4000-8000-000000000000",
+            "root",
+            2048,
+            6143,
+        )
+        .write_at(40, &core_img(40, true))
+        .build()
+        .unwrap();
+    let grub = Partitions::with_options(&mut Cursor::new(image), &options())
+        .unwrap()
+        .grub
+        .unwrap();
+    assert!(grub.issues.is_empty(), "{:?}", grub.issues);
+    assert_eq!(grub.location, "BIOS boot partition #0");
+    assert_eq!(grub.modules, vec!["biosdisk", "part_gpt"]);
+}
+
+#[test]
+fn flags_block_list_outside_the_embedding_area() {
+    let mut core = core_img(1, true);
+    core[0x1F4..0x1FC].copy_from_slice(&3000u64.to_le_bytes());
+    let grub = mbr_disk(&core).grub.unwrap();
+    assert!(grub
+        .issues
+        .iter()
+        .any(|i| i.contains("not right after diskboot")));
+    assert!(grub
+        .issues
+        .iter()
+        .any(|i| i.contains("outside the MBR gap")));
+}
+
+#[test]
+fn flags_missing_diskboot() {
+    let image = DiskImageBuilder::new(8192)
+        .boot_code(&boot_img(5000))
+        .primary(0x83, 2048, 4096)
+        .build()
+        .unwrap();
+    let grub = Partitions::with_options(&mut Cursor::new(image), &options())
+        .unwrap()
+        .grub
+        .unwrap();
+    assert_eq!(grub.location, "Outside the embedding area");
+    assert_eq!(grub.issues.len(), 2);
+    assert!(grub.blocklist.is_empty());
+}
+
+#[test]
+fn reads_block_list_entries_downwards() {
+    let mut sector = vec![0u8; 512];
+    sector[0x100..0x107].copy_from_slice(b"Loading");
+    sector[0x1F4..0x1FC].copy_from_slice(&2u64.to_le_bytes());
+    sector[0x1FC..0x1FE].copy_from_slice(&10u16.to_le_bytes());
+    sector[0x1FE..0x200].copy_from_slice(&0x820u16.to_le_bytes());
+    sector[0x1E8..0x1F0].copy_from_slice(&100u64.to_le_bytes());
+    sector[0x1F0..0x1F2].copy_from_slice(&5u16.to_le_bytes());
+    sector[0x1F2..0x1F4].copy_from_slice(&0xA20u16.to_le_bytes());
+    assert_eq!(
+        parse_blocklist(&sector).unwrap(),
+        vec![
+            GrubBlock {
+                start_lba: 2,
+                sectors: 10,
+                segment: 0x820
+            },
+            GrubBlock {
+                start_lba: 100,
+                sectors: 5,
+                segment: 0xA20
+            },
+        ]
+    );
+    assert!(parse_blocklist(&[0u8; 512]).is_none());
+}
+
+#[test]
+fn rejects_empty_compressed_kernel() {
+    let mut core = core_img(1, false);
+    core[512 + 0x08..512 + 0x0C].fill(0);
+    core[512 + 0x0C..512 + 0x10].copy_from_slice(&4096u32.to_le_bytes());
+    core[512 + 0x10..512 + 0x14].fill(0);
+    let grub = mbr_disk(&core).grub.unwrap();
+    assert_eq!(grub.compressed_size, Some(0));
+    assert!(grub.modules.is_empty());
+    assert!(grub.issues.iter().any(|i| i.contains("does not fit")));
+}
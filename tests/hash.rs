@@ -0,0 +1,90 @@
+use exhume_partitions::builder::DiskImageBuilder;
+use exhume_partitions::hash::MultiHasher;
+use exhume_partitions::layout::SlotKind;
+use exhume_partitions::Partitions;
+use std::io::Cursor;
+
+mod common;
+
+const DISK: &str = "5e0b1c39-3c4a-4b4e-9d57-2f1e0a7b8c11";
+const LINUX: &str = "0fc63daf-8483-4772-8e79-3d69d8477de4";
+
+#[test]
+fn lays_out_the_whole_disk() {
+    let image = DiskImageBuilder::new(8192)
+        .gpt(DISK)
+        .gpt_partition(
+            LINUX,
+            "00000001-0000-4000-8000-000000000000",
+            "data",
+            2048,
+            4095,
+        )
+        .build()
+        .unwrap();
+    let partitions = Partitions::new(&mut Cursor::new(image)).unwrap();
+    let slots = partitions.layout(8192);
+    let kinds: Vec<(SlotKind, u64, u64, &str)> = slots
+        .iter()
+        .map(|s| (s.kind, s.start_lba, s.end_lba, s.description.as_str()))
+        .collect();
+    assert_eq!(
+        kinds,
+        [
+            (SlotKind::Table, 0, 0, "Safety Table"),
+            (SlotKind::Table, 1, 1, "GPT Header"),
+            (SlotKind::Table, 2, 33, "Partition Table"),
+            (SlotKind::Unallocated, 34, 2047, "Unallocated"),
+            (SlotKind::Partition, 2048, 4095, "Linux filesystem data"),
+            (SlotKind::Unallocated, 4096, 8158, "Unallocated"),
+            (SlotKind::Table, 8159, 8190, "Backup Partition Table"),
+            (SlotKind::Table, 8191, 8191, "Backup GPT Header"),
+        ]
+    );
+    assert_eq!(slots[4].partition_index, Some(1));
+    // Contiguous and covering every sector.
+    for pair in slots.windows(2) {
+        assert_eq!(pair[0].end_lba + 1, pair[1].start_lba);
+    }
+}
+
+#[test]
+fn hashes_every_slot_but_containers() {
+    let image = DiskImageBuilder::new(8192)
+        .primary(0x07, 2048, 2048)
+        .extended(4096, 4096)
+        .logical(0x83, 4097, 4095)
+        .write_at(2048, &common::random_bytes(2048 * 512, 3))
+        .build()
+        .unwrap();
+    // The logical partition claims more than was acquired.
+    let acquired = 6000 * 512;
+    let image = image[..acquired].to_vec();
+    let mut body = Cursor::new(image.clone());
+    let partitions = Partitions::new(&mut body).unwrap();
+    assert_eq!(partitions.image_size, acquired as u64);
+    let hashes = partitions.compute_hashes(&mut body).unwrap();
+
+    assert!(hashes.iter().all(|h| h.slot.kind != SlotKind::Container));
+    let described: Vec<(&str, u64, u64)> = hashes
+        .iter()
+        .map(|h| (h.slot.description.as_str(), h.first_byte_addr, h.size_bytes))
+        .collect();
+    assert_eq!(
+        described,
+        [
+            ("Primary Table (#0)", 0, 512),
+            ("Unallocated", 512, 2047 * 512),
+            ("NTFS/exFAT/IFS/HPFS", 2048 * 512, 2048 * 512),
+            ("Extended Table (#1)", 4096 * 512, 512),
+            // Only what is there is hashed.
+            ("Linux/GNU Hurd", 4097 * 512, acquired as u64 - 4097 * 512),
+        ]
+    );
+    for hash in &hashes {
+        let start = hash.first_byte_addr as usize;
+        let mut hasher = MultiHasher::new();
+        hasher.update(&image[start..start + hash.size_bytes as usize]);
+        assert_eq!(hash.hashes, hasher.finalize(), "{}", hash.slot.description);
+    }
+}
@@ -0,0 +1,148 @@
+use exhume_partitions::builder::DiskImageBuilder;
+use exhume_partitions::nested::DiscoveryOptions;
+use exhume_partitions::report::PartitionsReport;
+use exhume_partitions::Partitions;
+use std::io::Cursor;
+
+const LINUX: &str = "0fc63daf-8483-4772-8e79-3d69d8477de4";
+const DISK: &str = "5e0b1c39-3c4a-4b4e-9d57-2f1e0a7b8c11";
+
+/// Discovery with the hidden data pass on.
+fn options() -> DiscoveryOptions {
+    DiscoveryOptions {
+        hidden_data: true,
+        ..Default::default()
+    }
+}
+
+fn gpt_disk() -> Vec<u8> {
+    DiskImageBuilder::new(8192)
+        .gpt(DISK)
+        .gpt_partition(
+            LINUX,
+            "11111111-2222-3333-4444-555555555555",
+            "data",
+            2048,
+            6143,
+        )
+        .build()
+        .unwrap()
+}
+
+fn locations(partitions: &Partitions) -> Vec<&str> {
+    partitions
+        .hidden_data
+        .iter()
+        .map(|hidden| hidden.location.as_str())
+        .collect()
+}
+
+#[test]
+fn clean_tables_have_no_hidden_data() {
+    let partitions = Partitions::with_options(&mut Cursor::new(gpt_disk()), &options()).unwrap();
+    assert!(partitions.hidden_data.is_empty());
+
+    let mbr = DiskImageBuilder::new(16384)
+        .primary(0x07, 2048, 2048)
+        .extended(4096, 12288)
+        .logical(0x83, 4097, 1000)
+        .logical(0x82, 6000, 2000)
+        .build()
+        .unwrap();
+    let partitions = Partitions::with_options(&mut Cursor::new(mbr), &options()).unwrap();
+    assert!(partitions.hidden_data.is_empty());
+}
+
+#[test]
+fn finds_data_in_gpt_reserved_areas() {
+    let mut disk = gpt_disk();
+    disk[512 + 20] = 0x42; // Header reserved field
+    disk[512 + 200..512 + 206].copy_from_slice(b"secret"); // Header slack
+    disk[1024 + 5 * 128 + 56..1024 + 5 * 128 + 60].copy_from_slice(b"name"); // Unused entry #5
+    disk[512 + 80..512 + 84].copy_from_slice(&64u32.to_le_bytes()); // 64 entries only...
+    disk[1024 + 100 * 128] = 0x01; // ...so entry #100 is past the array
+    disk[512 + 40..512 + 48].copy_from_slice(&40u64.to_le_bytes()); // First usable LBA 40...
+    disk[36 * 512 + 7] = 0xFF; // ...and data between it and the entry array
+    let backup = 8191 * 512;
+    disk[backup + 511] = 0x55; // Backup header slack
+
+    // Only a discovery asking for it reads the reserved areas.
+    let partitions = Partitions::new(&mut Cursor::new(disk.clone())).unwrap();
+    assert!(partitions.hidden_data.is_empty());
+
+    let partitions = Partitions::with_options(&mut Cursor::new(disk), &options()).unwrap();
+    assert_eq!(
+        locations(&partitions),
+        [
+            "GPT header reserved field (LBA 1)",
+            "GPT header slack (LBA 1)",
+            "GPT header slack (LBA 8191)",
+            "Unused GPT entry #5",
+            "GPT entry array past num_partition_entries (64)",
+            "GPT reserved sectors (LBA 34 to 39)",
+        ]
+    );
+
+    let slack = &partitions.hidden_data[1];
+    assert_eq!(slack.first_byte_addr, 512 + 92);
+    assert_eq!(slack.size_bytes, 512 - 92);
+    assert_eq!(slack.nonzero_bytes, 6);
+    assert_eq!(slack.first_nonzero, 200 - 92);
+    assert!(slack.hexdump.starts_with("00000060  "));
+    assert!(slack.hexdump.contains("|............secr|"));
+
+    let past = &partitions.hidden_data[4];
+    assert_eq!(past.first_byte_addr, 1024 + 64 * 128);
+    assert_eq!(past.first_nonzero, 36 * 128);
+    let gap = &partitions.hidden_data[5];
+    assert_eq!(gap.first_byte_addr, 34 * 512);
+    assert_eq!(gap.first_nonzero, 2 * 512 + 7);
+}
+
+#[test]
+fn skips_areas_past_64_bit_byte_addresses() {
+    let mut disk = gpt_disk();
+    disk[512 + 20] = 0x42; // Header reserved field
+    disk[512 + 32..512 + 40].copy_from_slice(&(1u64 << 62).to_le_bytes()); // Backup LBA
+    disk[512 + 40..512 + 48].copy_from_slice(&u64::MAX.to_le_bytes()); // First usable LBA
+
+    let partitions = Partitions::with_options(&mut Cursor::new(disk), &options()).unwrap();
+    assert_eq!(
+        locations(&partitions),
+        ["GPT header reserved field (LBA 1)"]
+    );
+}
+
+#[test]
+fn finds_data_in_mbr_gap_and_ebr() {
+    let mut disk = DiskImageBuilder::new(16384)
+        .primary(0x07, 2048, 2048)
+        .extended(4096, 12288)
+        .logical(0x83, 4097, 1000)
+        .build()
+        .unwrap();
+    disk[10 * 512..10 * 512 + 4].copy_from_slice(b"GRUB");
+    disk[4096 * 512 + 446 + 32] = 0x83; // Third entry of the only EBR
+
+    let partitions = Partitions::with_options(&mut Cursor::new(disk.clone()), &options()).unwrap();
+    assert_eq!(
+        locations(&partitions),
+        [
+            "MBR gap (LBA 1 to 2047)",
+            "Unused entry 3 of EBR #1 (LBA 4096)",
+        ]
+    );
+    assert_eq!(partitions.hidden_data[0].first_byte_addr, 512);
+    assert_eq!(partitions.hidden_data[0].first_nonzero, 9 * 512);
+
+    // Report addresses are absolute in the evidence.
+    let mut wrapped = vec![0u8; 0x10000];
+    wrapped.extend(disk);
+    let options = DiscoveryOptions {
+        offset: 0x10000,
+        ..options()
+    };
+    let partitions = Partitions::with_options(&mut Cursor::new(wrapped), &options).unwrap();
+    let report = PartitionsReport::new(&partitions, None);
+    assert_eq!(report.hidden_data[0].first_byte_addr, 0x10000 + 512);
+}
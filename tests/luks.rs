@@ -0,0 +1,122 @@
+use exhume_partitions::builder::DiskImageBuilder;
+use exhume_partitions::encryption::Encryption;
+use exhume_partitions::luks::{self, LuksHeader};
+use exhume_partitions::nested::DiscoveryOptions;
+use exhume_partitions::Partitions;
+use std::io::Cursor;
+
+mod common;
+
+const LUKS: &str = "ca7d7ccb-63ed-4c53-861c-1742536059cc";
+const LINUX: &str = "0fc63daf-8483-4772-8e79-3d69d8477de4";
+const DISK: &str = "5e0b1c39-3c4a-4b4e-9d57-2f1e0a7b8c11";
+
+/// Discovery with the encryption pass on.
+fn options() -> DiscoveryOptions {
+    DiscoveryOptions {
+        encryption: true,
+        ..Default::default()
+    }
+}
+
+fn parse(volume: Vec<u8>, typed: bool) -> Option<LuksHeader> {
+    luks::parse(&mut Cursor::new(volume), 0, typed)
+}
+
+/// A disk with one partition of the given type holding `volume`.
+fn disk(type_guid: &str, volume: &[u8]) -> Partitions {
+    let image = DiskImageBuilder::new(4096)
+        .gpt(DISK)
+        .gpt_partition(
+            type_guid,
+            "00000001-0000-4000-8000-000000000000",
+            "cryptroot",
+            2048,
+            2175,
+        )
+        .write_at(2048, volume)
+        .build()
+        .unwrap();
+    Partitions::with_options(&mut Cursor::new(image), &options()).unwrap()
+}
+
+#[test]
+fn parses_luks2_metadata() {
+    let header = parse(common::luks2_header(common::LUKS2_JSON), false).unwrap();
+    let luks2 = header.luks2.unwrap();
+    assert!(luks2.issues.is_empty(), "{:?}", luks2.issues);
+    assert_eq!(luks2.headers.len(), 2);
+    assert!(luks2.headers.iter().all(|copy| copy.checksum_valid));
+    assert_eq!(luks2.headers[1].offset, common::LUKS2_HEADER_SIZE as u64);
+    assert_eq!(luks2.json_size, Some(12288));
+
+    assert_eq!(luks2.segments.len(), 1);
+    assert_eq!(luks2.segments[0].kind, "crypt");
+    assert_eq!(luks2.segments[0].offset, 16777216);
+    assert_eq!(luks2.segments[0].size, None);
+    assert_eq!(luks2.segments[0].sector_size, Some(512));
+
+    assert_eq!(luks2.tokens.len(), 1);
+    assert_eq!(luks2.tokens[0].kind, "systemd-tpm2");
+    assert_eq!(luks2.tokens[0].keyslots, vec![0]);
+    assert_eq!(luks2.digests.len(), 1);
+    assert_eq!(luks2.digests[0].keyslots, vec![0, 1]);
+    assert_eq!(luks2.digests[0].segments, vec![0]);
+    assert_eq!(luks2.digests[0].iterations, Some(117448));
+
+    assert_eq!(header.keyslots[0].memory, Some(1048576));
+    assert_eq!(header.keyslots[0].cpus, Some(4));
+    assert_eq!(header.keyslots[1].kdf_hash.as_deref(), Some("sha256"));
+}
+
+#[test]
+fn flags_bad_checksum_and_uses_secondary() {
+    let mut volume = common::luks2_header(common::LUKS2_JSON);
+    // Tamper with the primary JSON area: swap the PBKDF2 iteration count.
+    let json = String::from_utf8_lossy(&volume[4096..8192]).replace("1000,", "9999,");
+    volume[4096..8192].copy_from_slice(json.as_bytes());
+
+    let header = parse(volume, false).unwrap();
+    assert_eq!(header.keyslots[1].iterations, Some(1000));
+    let luks2 = header.luks2.unwrap();
+    assert!(!luks2.headers[0].checksum_valid);
+    assert!(luks2.headers[1].checksum_valid);
+    assert!(luks2.issues.iter().any(|issue| issue.contains("checksum")));
+    assert!(luks2.issues.iter().any(|issue| issue.contains("JSON")));
+}
+
+#[test]
+fn flags_sequence_id_disagreement() {
+    let newer = common::LUKS2_JSON
+        .replace("\"1000\"", "\"2000\"")
+        .replace("1000,", "2000,");
+    let mut volume = common::luks2_copy(false, 5, &newer);
+    volume.extend(common::luks2_copy(true, 4, common::LUKS2_JSON));
+
+    let header = parse(volume, false).unwrap();
+    assert_eq!(header.keyslots[1].iterations, Some(2000));
+    let issues = header.luks2.unwrap().issues;
+    assert!(issues
+        .iter()
+        .any(|issue| issue.contains("Sequence ids differ")));
+}
+
+#[test]
+fn recovers_from_secondary_header_on_luks_partition() {
+    let mut volume = common::luks2_header(common::LUKS2_JSON);
+    volume[..4096].fill(0);
+
+    assert!(parse(volume.clone(), false).is_none());
+    let header = parse(volume.clone(), true).unwrap();
+    assert_eq!(header.payload_offset, 16777216);
+    let luks2 = header.luks2.unwrap();
+    assert_eq!(luks2.headers.len(), 1);
+    assert!(luks2.issues.iter().any(|issue| issue.contains("Primary")));
+
+    let extents = disk(LUKS, &volume).extents();
+    match &extents[0].encryption {
+        Some(Encryption::Luks(header)) => assert_eq!(header.version, 2),
+        other => panic!("expected LUKS2, got {:?}", other),
+    }
+    assert!(disk(LINUX, &volume).extents()[0].encryption.is_none());
+}
@@ -0,0 +1,84 @@
+use exhume_partitions::builder::DiskImageBuilder;
+use exhume_partitions::mmls::Units;
+use exhume_partitions::Partitions;
+use std::io::Cursor;
+
+const DISK: &str = "5e0b1c39-3c4a-4b4e-9d57-2f1e0a7b8c11";
+const ESP: &str = "c12a7328-f81f-11d2-ba4b-00a0c93ec93b";
+const LINUX: &str = "0fc63daf-8483-4772-8e79-3d69d8477de4";
+
+fn discover(builder: DiskImageBuilder) -> Partitions {
+    Partitions::new(&mut Cursor::new(builder.build().unwrap())).unwrap()
+}
+
+#[test]
+fn lists_dos_partitions_like_mmls() {
+    let partitions = discover(
+        DiskImageBuilder::new(8192)
+            .primary(0x07, 63, 1985)
+            .extended(2048, 4096)
+            .logical(0x83, 2049, 1023)
+            .logical(0x82, 4097, 2047),
+    );
+    assert_eq!(
+        partitions.print_mmls(Units::Sectors),
+        "\
+DOS Partition Table
+Offset Sector: 0
+Units are in 512-byte sectors
+
+      Slot      Start        End          Length       Description
+000:  Meta      0000000000   0000000000   0000000001   Primary Table (#0)
+001:  -------   0000000001   0000000062   0000000062   Unallocated
+002:  000:000   0000000063   0000002047   0000001985   NTFS/exFAT/IFS/HPFS (0x07)
+003:  000:001   0000002048   0000006143   0000004096   W95 Ext'd (LBA) (0x0f)
+004:  Meta      0000002048   0000002048   0000000001   Extended Table (#1)
+005:  001:000   0000002049   0000003071   0000001023   Linux/GNU Hurd (0x83)
+006:  -------   0000003072   0000004095   0000001024   Unallocated
+007:  Meta      0000004096   0000004096   0000000001   Extended Table (#2)
+008:  002:000   0000004097   0000006143   0000002047   Linux Swap (0x82)
+009:  -------   0000006144   0000008191   0000002048   Unallocated
+"
+    );
+}
+
+#[test]
+fn lists_gpt_partitions_like_mmls_in_bytes() {
+    let partitions = discover(
+        DiskImageBuilder::new(8192)
+            .gpt(DISK)
+            .gpt_partition(
+                ESP,
+                "00000001-0000-4000-8000-000000000000",
+                "EFI system partition",
+                2048,
+                4095,
+            )
+            .gpt_partition(
+                LINUX,
+                "00000002-0000-4000-8000-000000000000",
+                "",
+                6144,
+                8158,
+            ),
+    );
+    assert_eq!(
+        partitions.print_mmls(Units::Bytes),
+        "\
+GUID Partition Table (EFI)
+Offset Sector: 0
+Units are in bytes
+
+      Slot      Start        End          Length       Description
+000:  Meta      0000000000   0000000511   0000000512   Safety Table
+001:  Meta      0000000512   0000001023   0000000512   GPT Header
+002:  Meta      0000001024   0000017407   0000016384   Partition Table
+003:  -------   0000017408   0001048575   0001031168   Unallocated
+004:  000       0001048576   0002097151   0001048576   EFI system partition
+005:  -------   0002097152   0003145727   0001048576   Unallocated
+006:  001       0003145728   0004177407   0001031680   Linux filesystem data
+007:  Meta      0004177408   0004193791   0000016384   Backup Partition Table
+008:  Meta      0004193792   0004194303   0000000512   Backup GPT Header
+"
+    );
+}
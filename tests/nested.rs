@@ -0,0 +1,112 @@
+use exhume_partitions::builder::DiskImageBuilder;
+use exhume_partitions::nested::DiscoveryOptions;
+use exhume_partitions::Partitions;
+use std::io::Cursor;
+
+const LINUX: &str = "0fc63daf-8483-4772-8e79-3d69d8477de4";
+const DISK: &str = "5e0b1c39-3c4a-4b4e-9d57-2f1e0a7b8c11";
+
+fn nested(max_depth: usize) -> DiscoveryOptions {
+    DiscoveryOptions {
+        nested: true,
+        max_depth,
+        ..Default::default()
+    }
+}
+
+fn discover(image: Vec<u8>, options: &DiscoveryOptions) -> Partitions {
+    Partitions::with_options(&mut Cursor::new(image), options).unwrap()
+}
+
+#[test]
+fn finds_an_mbr_image_stored_in_a_gpt_partition() {
+    let usb = DiskImageBuilder::new(4096)
+        .primary(0x0C, 2048, 1024)
+        .primary(0x83, 3072, 1024)
+        .build()
+        .unwrap();
+    let image = DiskImageBuilder::new(16384)
+        .gpt(DISK)
+        .gpt_partition(
+            LINUX,
+            "11111111-2222-3333-4444-555555555555",
+            "data",
+            2048,
+            10239,
+        )
+        .write_at(2048, &usb)
+        .build()
+        .unwrap();
+
+    let flat = discover(image.clone(), &DiscoveryOptions::default());
+    assert!(flat.nested.is_empty());
+
+    let partitions = discover(image, &nested(4));
+    assert_eq!(partitions.nested.len(), 1);
+    assert_eq!(partitions.nested[0].parent_index, 1);
+    assert_eq!(partitions.nested[0].partitions.base_offset, 2048 * 512);
+
+    let volumes = partitions.volumes();
+    assert_eq!(volumes.len(), 3);
+    assert_eq!(volumes[0].parent, None);
+    assert_eq!(volumes[1].parent, Some(0));
+    assert_eq!(volumes[1].depth, 1);
+    assert_eq!(volumes[1].extent.first_byte_addr, (2048 + 2048) * 512);
+    assert_eq!(volumes[2].extent.first_byte_addr, (2048 + 3072) * 512);
+}
+
+#[test]
+fn ignores_volume_boot_records() {
+    // A FAT boot sector ends with 0x55AA too, but holds no plausible partition table.
+    let mut vbr = vec![0u8; 512];
+    vbr[0..3].copy_from_slice(&[0xEB, 0x3C, 0x90]);
+    vbr[446..462].copy_from_slice(&[
+        0x80, 0, 0, 0, 0x0C, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0x0F, 0xFF, 0xFF, 0xFF, 0x0F,
+    ]);
+    vbr[510..512].copy_from_slice(&[0x55, 0xAA]);
+    let image = DiskImageBuilder::new(8192)
+        .primary(0x0C, 2048, 4096)
+        .write_at(2048, &vbr)
+        .build()
+        .unwrap();
+    assert!(discover(image, &nested(4)).nested.is_empty());
+}
+
+/// Each level holds an MBR whose only partition starts one sector further, so every
+/// partition holds yet another table.
+fn staircase(levels: u32) -> Vec<u8> {
+    let mut builder = DiskImageBuilder::new(8192).primary(0x83, 1, 8000);
+    for level in 1..=levels {
+        let table = DiskImageBuilder::new(8192)
+            .primary(0x83, 1, 8000 - level)
+            .build()
+            .unwrap();
+        builder = builder.write_at(level as u64, &table[..512]);
+    }
+    builder.build().unwrap()
+}
+
+#[test]
+fn stops_at_the_maximum_depth() {
+    let partitions = discover(staircase(10), &nested(3));
+    let volumes = partitions.volumes();
+    assert_eq!(volumes.iter().map(|v| v.depth).max(), Some(3));
+    assert_eq!(volumes.len(), 4);
+}
+
+#[test]
+fn skips_ranges_already_visited() {
+    let inner = DiskImageBuilder::new(4096)
+        .primary(0x83, 2048, 1024)
+        .build()
+        .unwrap();
+    // Two entries describing the same range.
+    let image = DiskImageBuilder::new(8192)
+        .primary(0x83, 2048, 4096)
+        .primary(0x83, 2048, 4096)
+        .write_at(2048, &inner)
+        .build()
+        .unwrap();
+    let partitions = discover(image, &nested(4));
+    assert_eq!(partitions.nested.len(), 1);
+}
@@ -0,0 +1,172 @@
+use exhume_partitions::builder::DiskImageBuilder;
+use exhume_partitions::nested::DiscoveryOptions;
+use exhume_partitions::report::PartitionsReport;
+use exhume_partitions::Partitions;
+use std::io::Cursor;
+
+const LINUX: &str = "0fc63daf-8483-4772-8e79-3d69d8477de4";
+const DISK: &str = "5e0b1c39-3c4a-4b4e-9d57-2f1e0a7b8c11";
+const HEADER: usize = 4096;
+
+fn at(offset: u64) -> DiscoveryOptions {
+    DiscoveryOptions {
+        offset,
+        ..Default::default()
+    }
+}
+
+/// A disk image preceded by a container header of `HEADER` bytes.
+fn wrapped(disk: &[u8]) -> Vec<u8> {
+    let mut image = vec![0xA5u8; HEADER];
+    image.extend_from_slice(disk);
+    image
+}
+
+#[test]
+fn finds_a_gpt_after_a_container_header() {
+    let disk = DiskImageBuilder::new(8192)
+        .gpt(DISK)
+        .gpt_partition(
+            LINUX,
+            "11111111-2222-3333-4444-555555555555",
+            "data",
+            2048,
+            6143,
+        )
+        .build()
+        .unwrap();
+    let image = wrapped(&disk);
+
+    let partitions = Partitions::with_options(&mut Cursor::new(image), &at(HEADER as u64)).unwrap();
+    assert_eq!(partitions.base_offset, HEADER as u64);
+    assert_eq!(partitions.image_size, disk.len() as u64);
+    assert_eq!(partitions.gpt.as_ref().unwrap().partition_entries.len(), 1);
+
+    let extents = partitions.extents();
+    assert_eq!(extents.len(), 1);
+    assert_eq!(extents[0].start_lba, 2048);
+    assert_eq!(extents[0].first_byte_addr, HEADER as u64 + 2048 * 512);
+}
+
+#[test]
+fn finds_an_mbr_and_its_logical_partitions_at_an_offset() {
+    let disk = DiskImageBuilder::new(8192)
+        .primary(0x83, 2048, 1024)
+        .extended(4096, 4096)
+        .logical(0x83, 4160, 2048)
+        .logical(0x07, 6400, 1024)
+        .build()
+        .unwrap();
+    let image = wrapped(&disk);
+
+    let partitions = Partitions::with_options(&mut Cursor::new(image), &at(HEADER as u64)).unwrap();
+    assert_eq!(partitions.ebr.as_ref().unwrap().len(), 2);
+    for extent in partitions.extents() {
+        assert_eq!(
+            extent.first_byte_addr,
+            HEADER as u64 + extent.start_lba * 512
+        );
+    }
+}
+
+#[test]
+fn hashes_the_bytes_at_the_offset() {
+    let disk = DiskImageBuilder::new(4096)
+        .primary(0x83, 2048, 1024)
+        .build()
+        .unwrap();
+    let image = wrapped(&disk);
+
+    let mut body = Cursor::new(image);
+    let shifted = Partitions::with_options(&mut body, &at(HEADER as u64)).unwrap();
+    let shifted_hashes = shifted.compute_hashes(&mut body).unwrap();
+
+    let mut plain_body = Cursor::new(disk);
+    let plain = Partitions::new(&mut plain_body).unwrap();
+    let plain_hashes = plain.compute_hashes(&mut plain_body).unwrap();
+
+    assert_eq!(shifted_hashes.len(), plain_hashes.len());
+    for (shifted, plain) in shifted_hashes.iter().zip(&plain_hashes) {
+        assert_eq!(
+            shifted.first_byte_addr,
+            plain.first_byte_addr + HEADER as u64
+        );
+        assert_eq!(shifted.hashes, plain.hashes);
+    }
+}
+
+#[test]
+fn rejects_an_offset_past_the_end() {
+    let disk = DiskImageBuilder::new(64).build().unwrap();
+    let size = disk.len() as u64;
+    assert!(Partitions::with_options(&mut Cursor::new(disk), &at(size)).is_err());
+}
+
+#[test]
+fn report_table_entries_have_absolute_addresses() {
+    let disk = DiskImageBuilder::new(8192)
+        .primary(0x83, 2048, 1024)
+        .extended(4096, 4096)
+        .logical(0x83, 4160, 2048)
+        .build()
+        .unwrap();
+    let partitions =
+        Partitions::with_options(&mut Cursor::new(wrapped(&disk)), &at(HEADER as u64)).unwrap();
+    let report = PartitionsReport::new(&partitions, None);
+    let mbr = report.mbr.as_ref().unwrap();
+    assert_eq!(mbr.entries[0].first_byte_addr, HEADER as u64 + 2048 * 512);
+    assert_eq!(
+        report.ebr[0].entries[0].first_byte_addr,
+        report.partitions.last().unwrap().first_byte_addr
+    );
+
+    let disk = DiskImageBuilder::new(8192)
+        .gpt(DISK)
+        .gpt_partition(
+            LINUX,
+            "11111111-2222-3333-4444-555555555555",
+            "data",
+            2048,
+            6143,
+        )
+        .build()
+        .unwrap();
+    let partitions =
+        Partitions::with_options(&mut Cursor::new(wrapped(&disk)), &at(HEADER as u64)).unwrap();
+    let report = PartitionsReport::new(&partitions, None);
+    let entry = &report.gpt.as_ref().unwrap().entries[0];
+    assert_eq!(entry.first_byte_addr, HEADER as u64 + 2048 * 512);
+    assert_eq!(entry.first_byte_addr, report.partitions[0].first_byte_addr);
+}
+
+#[test]
+fn table_output_prints_absolute_addresses() {
+    let disk = DiskImageBuilder::new(8192)
+        .primary(0x83, 2048, 1024)
+        .extended(4096, 4096)
+        .logical(0x83, 4160, 2048)
+        .build()
+        .unwrap();
+    let partitions =
+        Partitions::with_options(&mut Cursor::new(wrapped(&disk)), &at(HEADER as u64)).unwrap();
+    let table = partitions.print_info(false);
+    assert!(table.contains(&format!("0x{:x}", HEADER + 2048 * 512)));
+    assert!(table.contains(&format!("0x{:x}", HEADER + 4160 * 512)));
+    assert!(!table.contains(&format!("0x{:x} ", 2048 * 512)));
+
+    let disk = DiskImageBuilder::new(8192)
+        .gpt(DISK)
+        .gpt_partition(
+            LINUX,
+            "11111111-2222-3333-4444-555555555555",
+            "data",
+            2048,
+            6143,
+        )
+        .build()
+        .unwrap();
+    let partitions =
+        Partitions::with_options(&mut Cursor::new(wrapped(&disk)), &at(HEADER as u64)).unwrap();
+    let table = partitions.print_info(false);
+    assert!(table.contains(&format!("0x{:x}", HEADER + 2048 * 512)));
+}
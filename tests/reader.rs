@@ -0,0 +1,150 @@
+use exhume_partitions::builder::DiskImageBuilder;
+use exhume_partitions::reader::{first_segment, skip_later_segments, SectorReader, WithSectorSize};
+use exhume_partitions::{read_gpt_header_at, Partitions};
+use std::io::Cursor;
+
+const LINUX: &str = "0fc63daf-8483-4772-8e79-3d69d8477de4";
+const DISK: &str = "5e0b1c39-3c4a-4b4e-9d57-2f1e0a7b8c11";
+
+#[test]
+fn parses_from_a_cursor() {
+    let image = DiskImageBuilder::new(8192)
+        .primary(0x07, 2048, 2048)
+        .extended(4096, 4096)
+        .logical(0x83, 4097, 1000)
+        .build()
+        .unwrap();
+    let mut cursor = Cursor::new(image);
+    let partitions = Partitions::new(&mut cursor).unwrap();
+    assert_eq!(partitions.sector_size, 512);
+    assert_eq!(partitions.image_size, 8192 * 512);
+    assert_eq!(partitions.extents().len(), 2);
+}
+
+#[test]
+fn parses_from_a_file() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let path = dir.path().join("disk.raw");
+    DiskImageBuilder::new(4096)
+        .gpt(DISK)
+        .gpt_partition(
+            LINUX,
+            "11111111-2222-3333-4444-555555555555",
+            "root",
+            40,
+            4000,
+        )
+        .write_to(&path)
+        .unwrap();
+    let mut file = std::fs::File::open(&path).unwrap();
+    let header = read_gpt_header_at(&mut file, 1).unwrap();
+    assert_eq!(header.backup_lba, 4095);
+    let partitions = Partitions::new(&mut file).unwrap();
+    assert_eq!(partitions.extents()[0].name.as_deref(), Some("root"));
+}
+
+#[test]
+fn honours_the_sector_size_of_the_wrapper() {
+    let image = DiskImageBuilder::new(2048)
+        .sector_size(4096)
+        .gpt(DISK)
+        .gpt_partition(
+            LINUX,
+            "11111111-2222-3333-4444-555555555555",
+            "root",
+            256,
+            2000,
+        )
+        .build()
+        .unwrap();
+    let mut reader = WithSectorSize::new(Cursor::new(image), 4096);
+    assert_eq!(reader.sector_size(), 4096);
+    let partitions = Partitions::new(&mut reader).unwrap();
+    let gpt = partitions.gpt.as_ref().unwrap();
+    assert_eq!(gpt.header.partition_entry_lba, 2);
+    assert_eq!(gpt.header.first_usable_lba, 6);
+    assert_eq!(partitions.extents()[0].first_byte_addr, 256 * 4096);
+}
+
+#[test]
+fn addresses_mbr_and_ebr_entries_in_large_sectors() {
+    let image = DiskImageBuilder::new(2048)
+        .sector_size(4096)
+        .primary(0x83, 256, 256)
+        .extended(512, 1024)
+        .logical(0x83, 513, 255)
+        .build()
+        .unwrap();
+    let mut reader = WithSectorSize::new(Cursor::new(image), 4096);
+    let partitions = Partitions::new(&mut reader).unwrap();
+    let extents = partitions.extents();
+    assert_eq!(extents.len(), 2);
+    assert_eq!(extents[0].first_byte_addr, 256 * 4096);
+    assert_eq!(extents[0].size_bytes, 256 * 4096);
+    assert_eq!(extents[1].start_lba, 513);
+    assert_eq!(extents[1].first_byte_addr, 513 * 4096);
+    assert_eq!(extents[1].size_bytes, 255 * 4096);
+}
+
+#[test]
+fn works_through_a_trait_object() {
+    let image = DiskImageBuilder::new(4096)
+        .primary(0x83, 2048, 2048)
+        .build()
+        .unwrap();
+    let mut reader: Box<dyn SectorReader> = Box::new(Cursor::new(image));
+    let partitions = Partitions::new(reader.as_mut()).unwrap();
+    assert_eq!(partitions.extents()[0].start_lba, 2048);
+}
+
+#[test]
+fn names_the_first_segment_of_split_images() {
+    assert_eq!(first_segment("disk.E02").as_deref(), Some("disk.E01"));
+    assert_eq!(
+        first_segment("case/disk.e17").as_deref(),
+        Some("case/disk.e01")
+    );
+    assert_eq!(first_segment("disk.EAB").as_deref(), Some("disk.E01"));
+    assert_eq!(first_segment("disk.Ex02").as_deref(), Some("disk.Ex01"));
+    assert_eq!(first_segment("disk.L02").as_deref(), Some("disk.L01"));
+    assert_eq!(first_segment("disk.S03").as_deref(), Some("disk.S01"));
+    // Later segments only: the first one and ordinary files are not.
+    assert_eq!(first_segment("disk.E01"), None);
+    assert_eq!(first_segment("disk.dd"), None);
+    assert_eq!(first_segment("disk"), None);
+    assert_eq!(first_segment("disk.E001"), None);
+    // Look-alike extensions name a segment that only exists if it is in the input.
+    assert_eq!(
+        first_segment("pagefile.SYS").as_deref(),
+        Some("pagefile.S01")
+    );
+    assert_eq!(first_segment("setup.LOG").as_deref(), Some("setup.L01"));
+}
+
+#[test]
+fn skips_later_segments_only_next_to_their_first_one() {
+    let files: Vec<String> = [
+        "a/disk.E01",
+        "a/disk.E02",
+        "a/disk.EAA",
+        "b/other.E02",
+        "c/pagefile.SYS",
+        "c/setup.LOG",
+        "c/image.dd",
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect();
+    let (open, skipped) = skip_later_segments(&files);
+    assert_eq!(
+        open,
+        [
+            "a/disk.E01",
+            "b/other.E02",
+            "c/pagefile.SYS",
+            "c/setup.LOG",
+            "c/image.dd"
+        ]
+    );
+    assert_eq!(skipped, ["a/disk.E02", "a/disk.EAA"]);
+}
@@ -0,0 +1,93 @@
+use exhume_body::Body;
+use exhume_partitions::builder::{Corruption, DiskImageBuilder};
+use exhume_partitions::repair::{plan_gpt_repair, GptCopy};
+use std::path::Path;
+use tempfile::TempDir;
+
+const ESP: &str = "c12a7328-f81f-11d2-ba4b-00a0c93ec93b";
+const DISK: &str = "5e0b1c39-3c4a-4b4e-9d57-2f1e0a7b8c11";
+
+fn gpt_disk() -> DiskImageBuilder {
+    DiskImageBuilder::new(4096).gpt(DISK).gpt_partition(
+        ESP,
+        "11111111-2222-3333-4444-555555555555",
+        "EFI",
+        2048,
+        4000,
+    )
+}
+
+fn open(path: &Path) -> Body {
+    Body::new(path.to_string_lossy().to_string(), "raw")
+}
+
+/// Repair a damaged image into a copy and compare it to the undamaged image.
+fn repaired_matches_clean(corruption: Corruption, expected_target: GptCopy) {
+    let dir = TempDir::new().unwrap();
+    let evidence = dir.path().join("damaged.raw");
+    gpt_disk().corrupt(corruption).write_to(&evidence).unwrap();
+    let before = std::fs::read(&evidence).unwrap();
+
+    let mut body = open(&evidence);
+    let plan = plan_gpt_repair(&mut body).unwrap().unwrap();
+    assert_eq!(plan.target, expected_target);
+
+    let output = dir.path().join("repaired.raw");
+    plan.write_copy(&mut body, &evidence, &output).unwrap();
+    assert_eq!(std::fs::read(&output).unwrap(), gpt_disk().build().unwrap());
+    assert_eq!(std::fs::read(&evidence).unwrap(), before);
+}
+
+#[test]
+fn rebuilds_missing_primary_from_backup() {
+    repaired_matches_clean(Corruption::MissingPrimaryGpt, GptCopy::Primary);
+}
+
+#[test]
+fn rebuilds_primary_with_bad_crcs() {
+    repaired_matches_clean(Corruption::BadHeaderCrc, GptCopy::Primary);
+    repaired_matches_clean(Corruption::BadPartitionArrayCrc, GptCopy::Primary);
+}
+
+#[test]
+fn rebuilds_missing_backup_from_primary() {
+    repaired_matches_clean(Corruption::MissingBackupGpt, GptCopy::Backup);
+}
+
+#[test]
+fn intact_gpt_needs_no_repair() {
+    let dir = TempDir::new().unwrap();
+    let evidence = dir.path().join("disk.raw");
+    gpt_disk().write_to(&evidence).unwrap();
+    assert!(plan_gpt_repair(&mut open(&evidence)).unwrap().is_none());
+}
+
+#[test]
+fn never_writes_over_the_evidence() {
+    let dir = TempDir::new().unwrap();
+    let evidence = dir.path().join("damaged.raw");
+    gpt_disk()
+        .corrupt(Corruption::MissingBackupGpt)
+        .write_to(&evidence)
+        .unwrap();
+    let before = std::fs::read(&evidence).unwrap();
+    let mut body = open(&evidence);
+    let plan = plan_gpt_repair(&mut body).unwrap().unwrap();
+    assert!(plan.write_copy(&mut body, &evidence, &evidence).is_err());
+    assert_eq!(std::fs::read(&evidence).unwrap(), before);
+
+    assert!(plan.write_overlay(&evidence, &evidence).is_err());
+    // Not even through another path to the same file.
+    let alias = dir.path().join(".").join("damaged.raw");
+    assert!(plan.write_overlay(&evidence, &alias).is_err());
+    assert_eq!(std::fs::read(&evidence).unwrap(), before);
+
+    let overlay = dir.path().join("plan.json");
+    plan.write_overlay(&evidence, &overlay).unwrap();
+    assert!(plan.write_overlay(&evidence, &overlay).is_err());
+    let json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&overlay).unwrap()).unwrap();
+    assert_eq!(json["target"], "Backup");
+    assert_eq!(json["writes"][0]["lba"], 4095);
+    assert_eq!(json["writes"][0]["bytes"].as_str().unwrap().len(), 1024);
+}
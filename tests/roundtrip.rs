@@ -0,0 +1,111 @@
+use exhume_partitions::gpt::{GPTPartitionEntry, GPT};
+use exhume_partitions::mbr::{MBRPartitionEntry, MBR};
+
+mod common;
+use common::{gpt_entry, gpt_header, mbr_sector};
+
+// Computed independently (zlib) over `gpt_entry("EFI system", 1 << 63)` in slot 0 of a
+// 128 x 128 bytes array, and over the header holding that array CRC.
+const ARRAY_CRC32: u32 = 0x28fa22ac;
+const HEADER_CRC32: u32 = 0xb6761b83;
+
+#[test]
+fn mbr_round_trips() {
+    let mut sector = mbr_sector(&[
+        (0x80, 0x07, 2048, 204800),
+        (0x00, 0x0F, 206848, 1_000_000),
+        (0x00, 0x83, 1_206_848, 4096),
+    ]);
+    // Boot code and CHS values must survive too.
+    for (i, byte) in sector[..446].iter_mut().enumerate() {
+        *byte = (i * 7) as u8;
+    }
+    sector[447..450].copy_from_slice(&[0x20, 0x21, 0x00]);
+    sector[451..454].copy_from_slice(&[0xfe, 0xff, 0xff]);
+
+    let mbr = MBR::from_bytes(&sector);
+    assert_eq!(mbr.to_bytes().to_vec(), sector);
+    assert_eq!(
+        MBR::from_bytes(&mbr.to_bytes()).partition_table[1].start_lba,
+        206848
+    );
+}
+
+#[test]
+fn mbr_entry_round_trips() {
+    let sector = mbr_sector(&[(0x80, 0xEE, 1, u32::MAX)]);
+    let mbr = MBR::from_bytes(&sector);
+    let entry: &MBRPartitionEntry = &mbr.partition_table[0];
+    assert_eq!(entry.to_bytes().to_vec(), sector[446..462].to_vec());
+}
+
+#[test]
+fn gpt_entry_round_trips() {
+    let bytes = gpt_entry(
+        "EFI system partition - 36 characters",
+        0x8000_0000_0000_0001,
+    );
+    let entry = GPTPartitionEntry::from_bytes(&bytes);
+    assert_eq!(entry.to_bytes().to_vec(), bytes);
+}
+
+#[test]
+fn gpt_header_round_trips() {
+    let bytes = gpt_header(HEADER_CRC32, ARRAY_CRC32);
+    let gpt = GPT::from_bytes(&bytes);
+    assert_eq!(gpt.header.to_bytes().to_vec(), bytes);
+}
+
+#[test]
+fn gpt_crcs_are_recomputed() {
+    let mut gpt = GPT::from_bytes(&gpt_header(0, 0));
+    let mut entry = GPTPartitionEntry::from_bytes(&gpt_entry("EFI system", 1 << 63));
+    entry.id = Some(0);
+    gpt.partition_entries.push(entry);
+    assert!(!gpt.header.is_crc32_valid());
+
+    gpt.update_crc32();
+    assert_eq!(gpt.header.partition_array_crc32, ARRAY_CRC32);
+    assert_eq!(gpt.header.crc32, HEADER_CRC32);
+    assert!(gpt.header.is_crc32_valid());
+    assert_eq!(
+        gpt.header.to_bytes().to_vec(),
+        gpt_header(HEADER_CRC32, ARRAY_CRC32)
+    );
+}
+
+#[test]
+fn gpt_array_keeps_entry_positions() {
+    let mut gpt = GPT::from_bytes(&gpt_header(0, 0));
+    let mut entry = GPTPartitionEntry::from_bytes(&gpt_entry("data", 0));
+    entry.id = Some(5);
+    gpt.partition_entries.push(entry);
+    let array = gpt.partition_array_to_bytes();
+    assert_eq!(array.len(), 128 * 128);
+    assert!(array[..5 * 128].iter().all(|b| *b == 0));
+    assert_eq!(array[5 * 128..6 * 128].to_vec(), gpt_entry("data", 0));
+}
+
+#[test]
+fn gpt_header_crc_covers_the_raw_sector() {
+    // A 100-byte header whose reserved bytes are not zero: the CRC covers them as read.
+    let mut sector = gpt_header(0, ARRAY_CRC32);
+    sector.resize(512, 0);
+    sector[12..16].copy_from_slice(&100u32.to_le_bytes());
+    sector[92..100].copy_from_slice(b"reserved");
+    let crc = crc32fast::hash(&sector[..100]);
+    sector[16..20].copy_from_slice(&crc.to_le_bytes());
+    assert!(GPT::from_bytes(&sector).header.is_crc32_valid());
+
+    // The same CRC over zeroed reserved bytes does not match.
+    let mut zeroed = sector.clone();
+    zeroed[92..100].fill(0);
+    assert!(!GPT::from_bytes(&zeroed).header.is_crc32_valid());
+
+    // A header size past the sector, or below 92 bytes, is invalid whatever the CRC.
+    for size in [u32::MAX, 513, 91] {
+        let mut bad = sector.clone();
+        bad[12..16].copy_from_slice(&size.to_le_bytes());
+        assert!(!GPT::from_bytes(&bad).header.is_crc32_valid(), "{}", size);
+    }
+}
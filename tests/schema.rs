@@ -0,0 +1,139 @@
+use exhume_partitions::gpt::{GPTPartitionEntry, GPT};
+use exhume_partitions::mbr::MBR;
+use exhume_partitions::report::{self, PartitionsReport, SCHEMA_VERSION};
+use exhume_partitions::Partitions;
+use serde_json::{json, Value};
+
+mod common;
+use common::{gpt_entry, gpt_header, mbr_sector};
+
+fn gpt_fixture() -> Partitions {
+    let mut gpt = GPT::from_bytes(&gpt_header(0, 0));
+    let mut entry = GPTPartitionEntry::from_bytes(&gpt_entry("ESP", 0));
+    entry.id = Some(0);
+    entry.size_sectors = entry.ending_lba - entry.starting_lba + 1;
+    entry.first_byte_addr = entry.starting_lba * 512;
+    gpt.partition_entries.push(entry);
+    Partitions {
+        mbr: Some(MBR::from_bytes(&mbr_sector(&[(0, 0xEE, 1, 2047)]))),
+        ebr: Some(Vec::new()),
+        gpt: Some(gpt),
+        sector_size: 512,
+        image_size: 2048 * 512,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn committed_schema_is_up_to_date() {
+    let committed: Value =
+        serde_json::from_str(include_str!("../schema/partitions.schema.json")).unwrap();
+    assert_eq!(
+        report::json_schema(),
+        committed,
+        "schema/partitions.schema.json is stale, regenerate it with `exhume_partitions schema`"
+    );
+}
+
+#[test]
+fn schema_version_is_pinned() {
+    // Bumping the version is a breaking change for consumers: update this test knowingly.
+    assert_eq!(SCHEMA_VERSION, 1);
+}
+
+#[test]
+fn report_field_names_are_locked() {
+    let report = PartitionsReport::new(&gpt_fixture(), Some("disk.raw"));
+    let value = serde_json::to_value(&report).unwrap();
+
+    let keys = |v: &Value| {
+        let mut keys: Vec<String> = v.as_object().unwrap().keys().cloned().collect();
+        keys.sort();
+        keys
+    };
+    assert_eq!(
+        keys(&value),
+        [
+            "ab_slots",
+            "base_offset",
+            "bootcode",
+            "dps",
+            "ebr",
+            "entropy",
+            "esp",
+            "evidence",
+            "gpt",
+            "grub",
+            "hashes",
+            "hidden_data",
+            "image_size",
+            "mbr",
+            "partitions",
+            "schema_version",
+            "sector_size",
+            "volumes"
+        ]
+    );
+    assert_eq!(
+        keys(&value["mbr"]["entries"][0]),
+        [
+            "boot_indicator",
+            "bootable",
+            "description",
+            "end_chs",
+            "entry_id",
+            "first_byte_addr",
+            "partition_type",
+            "size_sectors",
+            "start_chs",
+            "start_lba"
+        ]
+    );
+    assert_eq!(
+        value["gpt"]["entries"][0],
+        json!({
+            "entry_id": 0,
+            "partition_guid": "22222222-2222-2222-2222-222222222222",
+            "partition_type_guid": "c12a7328-f81f-11d2-ba4b-00a0c93ec93b",
+            "description": "EFI System partition",
+            "name": "ESP",
+            "start_lba": 34,
+            "end_lba": 1057,
+            "size_sectors": 1024,
+            "first_byte_addr": 17408,
+            "attributes": 0
+        })
+    );
+    assert_eq!(value["schema_version"], json!(SCHEMA_VERSION));
+    assert_eq!(
+        value["gpt"]["header"]["disk_guid"],
+        json!("11111111-1111-1111-1111-111111111111")
+    );
+    assert_eq!(
+        value["partitions"][0]["guid"],
+        json!("22222222-2222-2222-2222-222222222222")
+    );
+}
+
+#[test]
+fn report_round_trips() {
+    let mbr = MBR::from_bytes(&mbr_sector(&[
+        (0x80, 0x07, 2048, 4096),
+        (0, 0x83, 6144, 2048),
+    ]));
+    let partitions = Partitions {
+        mbr: Some(mbr),
+        ebr: Some(Vec::new()),
+        gpt: None,
+        sector_size: 512,
+        image_size: 8192 * 512,
+        ..Default::default()
+    };
+    let report = PartitionsReport::new(&partitions, None);
+    let text = serde_json::to_string(&report).unwrap();
+    let back: PartitionsReport = serde_json::from_str(&text).unwrap();
+    assert_eq!(serde_json::to_string(&back).unwrap(), text);
+    assert!(back.mbr.as_ref().unwrap().entries[0].bootable);
+    assert_eq!(back.partitions.len(), 2);
+    assert_eq!(back.partitions[1].first_byte_addr, 6144 * 512);
+}
@@ -0,0 +1,138 @@
+use exhume_partitions::builder::DiskImageBuilder;
+use exhume_partitions::types::{self, PartitionType, PartitionTypeRegistry};
+use exhume_partitions::Partitions;
+use std::io::Cursor;
+use tempfile::NamedTempFile;
+
+const DISK: &str = "5e0b1c39-3c4a-4b4e-9d57-2f1e0a7b8c11";
+
+#[test]
+fn builtin_table_is_consistent() {
+    let registry = PartitionTypeRegistry::builtin();
+    let table = registry.to_table();
+    assert!(table.mbr.len() > 150 && table.gpt.len() > 100);
+    // Lookups ignore case, so the HFS+ GUID needs a single entry.
+    let hfs = table
+        .gpt
+        .iter()
+        .filter(|t| {
+            t.type_id
+                .eq_ignore_ascii_case("48465300-0000-11aa-aa11-00306543ecac")
+        })
+        .count();
+    assert_eq!(hfs, 1);
+    assert_eq!(
+        registry
+            .gpt("48465300-0000-11AA-AA11-00306543ECAC")
+            .map(|t| t.description.as_str()),
+        Some("Apple HFS+")
+    );
+
+    let root = registry
+        .gpt("d27f46ed-2919-4cb8-bd25-9531f3c16534")
+        .unwrap();
+    assert_eq!(root.description, "Linux root (ARC)");
+    assert_eq!(root.role.as_deref(), Some("root"));
+    assert_eq!(root.arch.as_deref(), Some("arc"));
+    let usr = registry
+        .gpt("8484680c-9521-48c6-9c11-b0720656f69e")
+        .unwrap();
+    assert_eq!(
+        (usr.role.as_deref(), usr.arch.as_deref()),
+        (Some("usr"), Some("x86-64"))
+    );
+    let esp = registry.mbr(0xEF).unwrap();
+    assert_eq!(esp.role.as_deref(), Some("esp"));
+    assert_eq!(registry.mbr(0x83).unwrap().os.as_deref(), Some("Linux"));
+
+    // The table round-trips through its own format.
+    let mut copy = PartitionTypeRegistry::default();
+    copy.extend(table.clone()).unwrap();
+    assert_eq!(copy.to_table().gpt, table.gpt);
+}
+
+#[test]
+fn user_tables_override_and_are_validated() {
+    let mut registry = PartitionTypeRegistry::builtin();
+    registry
+        .extend_from_json(
+            r#"{"mbr": [{"type": "0x83", "description": "Appliance data", "os": "Acme"}],
+                "gpt": [{"type": "DEADBEEF-0000-4000-8000-000000000001", "description": "Acme"}]}"#,
+        )
+        .unwrap();
+    assert_eq!(registry.mbr(0x83).unwrap().description, "Appliance data");
+    let acme = registry
+        .gpt("deadbeef-0000-4000-8000-000000000001")
+        .unwrap();
+    assert_eq!(acme.type_id, "deadbeef-0000-4000-8000-000000000001");
+
+    for bad in [
+        r#"{"mbr": [{"type": "83", "description": "x"}]}"#,
+        r#"{"gpt": [{"type": "not-a-guid", "description": "x"}]}"#,
+        r#"{"gpt": [{"type": "deadbeef-0000-4000-8000-000000000002", "description": "x"},
+                    {"type": "DEADBEEF-0000-4000-8000-000000000002", "description": "y"}]}"#,
+        r#"{"gpt": [{"description": "x"}]}"#,
+    ] {
+        assert!(registry.extend_from_json(bad).is_err(), "{}", bad);
+    }
+    // A rejected table changes nothing.
+    assert!(registry
+        .gpt("deadbeef-0000-4000-8000-000000000002")
+        .is_none());
+}
+
+#[test]
+fn registered_types_describe_parsed_partitions() {
+    const VENDOR: &str = "0badc0de-1111-4222-8333-444455556666";
+    let file = NamedTempFile::new().unwrap();
+    std::fs::write(
+        file.path(),
+        format!(
+            r#"{{"gpt": [{{"type": "{}", "description": "Vendor recovery", "role": "recovery"}}]}}"#,
+            VENDOR
+        ),
+    )
+    .unwrap();
+    types::register_file(file.path()).unwrap();
+    types::update_registry(|registry| {
+        registry.register_mbr(
+            0x42,
+            PartitionType {
+                type_id: "0x42".to_string(),
+                description: "Vendor MBR type".to_string(),
+                os: None,
+                role: None,
+                arch: None,
+            },
+        )
+    });
+
+    let image = DiskImageBuilder::new(8192)
+        .gpt(DISK)
+        .gpt_partition(
+            VENDOR,
+            "00000001-0000-4000-8000-000000000000",
+            "recovery",
+            2048,
+            4095,
+        )
+        .build()
+        .unwrap();
+    let partitions = Partitions::new(&mut Cursor::new(image)).unwrap();
+    let entry = &partitions.gpt.as_ref().unwrap().partition_entries[0];
+    assert_eq!(entry.description, "Vendor recovery");
+    assert_eq!(
+        entry.type_info().and_then(|t| t.role),
+        Some("recovery".to_string())
+    );
+
+    let image = DiskImageBuilder::new(8192)
+        .primary(0x42, 2048, 2048)
+        .build()
+        .unwrap();
+    let partitions = Partitions::new(&mut Cursor::new(image)).unwrap();
+    assert_eq!(
+        partitions.mbr.unwrap().partition_table[0].description,
+        "Vendor MBR type"
+    );
+}